@@ -0,0 +1,33 @@
+//! Record the build information printed by `ddf --version --verbose`.
+
+use std::{env, path::Path, process::Command};
+
+fn main() {
+  let target = env::var("TARGET").unwrap_or_default();
+  println!("cargo:rustc-env=DDF_TARGET={target}");
+
+  let mut features: Vec<String> = env::vars()
+    .filter_map(|(name, _)| {
+      let feature = name.strip_prefix("CARGO_FEATURE_")?;
+      (feature != "DEFAULT").then(|| feature.to_lowercase().replace('_', "-"))
+    })
+    .collect();
+  features.sort();
+  println!("cargo:rustc-env=DDF_FEATURES={}", features.join(", "));
+
+  let commit = Command::new("git")
+    .args(["rev-parse", "--short=12", "HEAD"])
+    .output()
+    .ok()
+    .filter(|output| output.status.success())
+    .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    .unwrap_or_default();
+  println!("cargo:rustc-env=DDF_GIT_COMMIT={commit}");
+  // Rebuild when the checked out commit changes, when building from a git checkout.
+  for file in ["../.git/HEAD", "../.git/index"] {
+    if Path::new(file).exists() {
+      println!("cargo:rerun-if-changed={file}");
+    }
+  }
+  println!("cargo:rerun-if-changed=build.rs");
+}
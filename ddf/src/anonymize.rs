@@ -0,0 +1,99 @@
+//! Replace identifying names by stable hashes, so the output can be shared publicly.
+
+/// 32 bits FNV-1a hash, stable across runs, platforms and versions.
+pub(crate) fn stable_hash(value: &str) -> String {
+  let hash = value.bytes().fold(0x811c_9dc5_u32, |hash, byte| {
+    (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+  });
+  format!("{hash:08x}")
+}
+
+/// Whether `value` looks like a `8-4-4-4-12` hexadecimal UUID.
+fn is_uuid(value: &str) -> bool {
+  let groups: Vec<&str> = value.split('-').collect();
+  groups.len() == 5
+    && groups
+      .iter()
+      .zip([8, 4, 4, 4, 12])
+      .all(|(group, len)| group.len() == len && group.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Hash every UUID found in the components of a path.
+pub(crate) fn anonymize_path(path: &str) -> String {
+  path
+    .split('/')
+    .map(|component| {
+      // Handle prefixed components like `luks-<uuid>`.
+      match component
+        .char_indices()
+        .find(|(i, _)| is_uuid(&component[*i..]))
+      {
+        Some((i, _)) => format!("{}{}", &component[..i], stable_hash(&component[i..])),
+        None => component.to_string(),
+      }
+    })
+    .collect::<Vec<_>>()
+    .join("/")
+}
+
+/// Anonymize a host name, the same way as the servers of network devices.
+pub(crate) fn anonymize_host(hostname: &str) -> String {
+  format!("host-{}", stable_hash(hostname))
+}
+
+/// Anonymize a device name.
+///
+/// Network sources (`server:/export`, `//server/share`) get their server name hashed, block
+/// devices are replaced entirely and pseudo devices like `tmpfs` are kept as is.
+pub(crate) fn anonymize_device(dev_name: &str) -> String {
+  if let Some(rest) = dev_name.strip_prefix("//") {
+    let (server, share) = rest.split_once('/').unwrap_or((rest, ""));
+    format!("//host-{}/{}", stable_hash(server), anonymize_path(share))
+  } else if let Some((server, export)) = dev_name.split_once(":/") {
+    format!("host-{}:/{}", stable_hash(server), anonymize_path(export))
+  } else if dev_name.starts_with('/') {
+    format!("/dev/dev-{}", stable_hash(dev_name))
+  } else if let Some(uuid) = dev_name.strip_prefix("UUID=") {
+    format!("UUID={}", stable_hash(uuid))
+  } else {
+    dev_name.to_string()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_stable() {
+    assert_eq!(stable_hash("/dev/sda1"), stable_hash("/dev/sda1"));
+    assert_ne!(stable_hash("/dev/sda1"), stable_hash("/dev/sda2"));
+    assert_eq!(stable_hash(""), "811c9dc5");
+  }
+
+  #[test]
+  fn test_anonymize_device() {
+    assert_eq!(anonymize_device("tmpfs"), "tmpfs");
+    assert!(anonymize_device("/dev/sda1").starts_with("/dev/dev-"));
+    assert!(!anonymize_device("nas.example.com:/export/home").contains("nas"));
+    assert!(anonymize_device("nas.example.com:/export/home").ends_with(":/export/home"));
+    assert!(!anonymize_device("//fileserver/share").contains("fileserver"));
+  }
+
+  #[test]
+  fn test_anonymize_host() {
+    let host = anonymize_host("nas.example.com");
+    assert!(host.starts_with("host-"));
+    assert!(!host.contains("nas"));
+    // Matches the server of the devices it exports.
+    assert!(anonymize_device("nas.example.com:/export").starts_with(&format!("{host}:/")));
+  }
+
+  #[test]
+  fn test_anonymize_path() {
+    assert_eq!(anonymize_path("/boot/efi"), "/boot/efi");
+    let path = anonymize_path("/run/media/luks-0b7c8a9e-1234-4f6e-9abc-0123456789ab");
+    assert!(path.starts_with("/run/media/luks-"));
+    assert!(!path.contains("0b7c8a9e"));
+  }
+}
@@ -0,0 +1,552 @@
+//! The filesystem listing: collect the filesystems, filter and sort them, then render them.
+//! `main` only picks the collector and the renderer from the arguments.
+
+use crate::{
+  args::{App, MountFilter},
+  bundle, disk,
+  expr::Expr,
+  filesystem::Filesystem,
+  fsext::{self, read_fs_list, sync_filesystems, MountInfo, RawStatfs},
+  import,
+  output::{self, Format, Record, Report, Sample},
+  problems,
+  render::{self, Column},
+  settings::{Settings, Severity, SortKey},
+  tui,
+};
+use anyhow::{anyhow, Result};
+use std::{
+  io::Write,
+  path::{Path, PathBuf},
+};
+
+/// How the collected filesystems are filtered and sorted.
+pub(crate) struct Options<'a> {
+  pub(crate) settings: &'a Settings,
+  /// Keep the empty filesystems and ignore `include` and `exclude`.
+  pub(crate) all: bool,
+  /// Used ratio a filesystem must exceed to be kept.
+  pub(crate) only_above: Option<f64>,
+  /// Keep only the filesystems at this usage level or above.
+  pub(crate) min_severity: Option<Severity>,
+  /// `--where` expression the filesystems must satisfy.
+  pub(crate) filter: Option<Expr>,
+  /// Sort key with whether it is reversed, none for the mount table order.
+  pub(crate) sort: Option<(SortKey, bool)>,
+  /// List the filesystems mounted more than once at one mount only.
+  pub(crate) dedupe: bool,
+  /// Leave the space reserved for root out of the sizes.
+  pub(crate) no_reserved: bool,
+  /// Types and mount options asked for, even with `all`.
+  pub(crate) mount_filter: MountFilter,
+}
+
+impl<'a> Options<'a> {
+  /// Whether the mount is kept by the `include` and `exclude` rules, or `all`, and by the mount
+  /// filter.
+  fn selects(&self, mount_info: &MountInfo) -> bool {
+    self.mount_filter.wants(mount_info)
+      && (self.all || (self.settings.includes(mount_info) && !self.settings.excludes(mount_info)))
+  }
+
+  pub(crate) fn new(args: &App, settings: &'a Settings) -> Result<Self> {
+    let filter = match &args.where_expr {
+      Some(source) => Some(
+        settings
+          .parse_where(source)
+          .map_err(|error| anyhow!("--where: {error}"))?,
+      ),
+      None => None,
+    };
+    Ok(Self {
+      settings,
+      all: args.all,
+      only_above: args.only_above,
+      min_severity: if args.critical_only {
+        Some(Severity::High)
+      } else {
+        args.check.then_some(Severity::Medium)
+      },
+      filter,
+      sort: args.sort(settings),
+      dedupe: !args.all && !args.no_dedupe,
+      no_reserved: args.no_reserved,
+      mount_filter: args.mount_filter(),
+    })
+  }
+}
+
+/// Where the filesystems come from.
+pub(crate) trait Collector {
+  /// Filesystems to list, read lazily when possible.
+  fn collect<'a>(&'a self, options: &'a Options)
+    -> Result<Box<dyn Iterator<Item = Filesystem> + 'a>>;
+}
+
+/// What the filesystems are turned into.
+pub(crate) trait Renderer {
+  /// Called with each filesystem as soon as it is read, for the streamed outputs.
+  fn filesystem(&mut self, _fs: &Filesystem) -> Result<()> {
+    Ok(())
+  }
+
+  /// Called once every filesystem is read.
+  fn finish(&mut self, filesystems: Vec<Filesystem>, report: &Report) -> Result<()>;
+}
+
+/// Collect, filter, sort and render the filesystems, returning their report.
+pub(crate) fn run(
+  options: &Options,
+  collector: &dyn Collector,
+  renderer: &mut dyn Renderer,
+) -> Result<Report> {
+  let mut filesystems = vec![];
+  for fs in arrange(options, collector.collect(options)?) {
+    renderer.filesystem(&fs)?;
+    filesystems.push(fs);
+  }
+  let report = Report::new(&filesystems, options.settings);
+  renderer.finish(filesystems, &report)?;
+  Ok(report)
+}
+
+/// Filesystems of `collector`, filtered and sorted, for the outputs refreshed in a loop.
+pub(crate) fn load(options: &Options, collector: &dyn Collector) -> Result<Vec<Filesystem>> {
+  Ok(arrange(options, collector.collect(options)?).collect())
+}
+
+/// Keep the filesystems above the usage threshold and satisfying `--where`, in the order asked
+/// for.
+fn arrange<'a>(
+  options: &'a Options,
+  filesystems: Box<dyn Iterator<Item = Filesystem> + 'a>,
+) -> Box<dyn Iterator<Item = Filesystem> + 'a> {
+  let filesystems: Box<dyn Iterator<Item = Filesystem>> = if options.no_reserved {
+    Box::new(filesystems.map(|mut fs| {
+      fs.usage.without_reserved();
+      fs
+    }))
+  } else {
+    filesystems
+  };
+  let filesystems: Box<dyn Iterator<Item = Filesystem>> = match options.only_above {
+    Some(threshold) => {
+      Box::new(filesystems.filter(move |fs| fs.used_ratio().is_some_and(|ratio| ratio > threshold)))
+    }
+    None => filesystems,
+  };
+  let filesystems: Box<dyn Iterator<Item = Filesystem>> = match options.min_severity {
+    Some(min) => Box::new(filesystems.filter(move |fs| {
+      fs.used_ratio()
+        .is_some_and(|ratio| options.settings.severity(fs, ratio) >= min)
+    })),
+    None => filesystems,
+  };
+  let filesystems: Box<dyn Iterator<Item = Filesystem>> = match &options.filter {
+    Some(expr) => {
+      Box::new(filesystems.filter(move |fs| expr.matches(&Record::new(fs, options.settings))))
+    }
+    None => filesystems,
+  };
+  match options.sort {
+    Some(order) => Box::new(sorted(filesystems.collect(), order, options.settings).into_iter()),
+    None => filesystems,
+  }
+}
+
+/// `filesystems` sorted by `key`, reversed when asked. Equal ones keep the mount table order.
+fn sorted(
+  mut filesystems: Vec<Filesystem>,
+  (key, reverse): (SortKey, bool),
+  config: &Settings,
+) -> Vec<Filesystem> {
+  filesystems.sort_by(|a, b| {
+    let order = key.compare(a, b, config);
+    if reverse {
+      order.reverse()
+    } else {
+      order
+    }
+  });
+  filesystems
+}
+
+/// Drop the empty and dummy filesystems and the ones not included or excluded by the settings,
+/// unless `all` is set, and the ones below the minimum size.
+fn select<'a>(
+  filesystems: impl Iterator<Item = Filesystem> + 'a,
+  options: &'a Options,
+) -> impl Iterator<Item = Filesystem> + 'a {
+  let (config, all) = (options.settings, options.all);
+  let min_size = config.min_size();
+  filesystems
+    .filter(move |fs| all || (fs.usage.blocks > 0 && !config.is_dummy(&fs.mount_info)))
+    .filter(move |fs| fs.size().is_none_or(|size| size >= min_size))
+    .filter(move |fs| options.selects(&fs.mount_info))
+}
+
+/// One mount per device of `mounts`, chosen among the ones kept by `include` and `exclude`: an
+/// excluded mount doesn't hide the other mounts of its device.
+fn dedupe_selected(mounts: impl Iterator<Item = MountInfo>, options: &Options) -> Vec<MountInfo> {
+  let mut mounts: Vec<MountInfo> = mounts.filter(|m| options.selects(m)).collect();
+  mounts.iter_mut().for_each(MountInfo::fill_dev_id);
+  fsext::dedupe(mounts, |m| m)
+}
+
+/// Filesystems of this machine: the `FILES` arguments, or the whole mount table when there are
+/// none.
+pub(crate) struct Local<'a> {
+  pub(crate) args: &'a App,
+}
+
+impl Collector for Local<'_> {
+  fn collect<'a>(
+    &'a self,
+    options: &'a Options,
+  ) -> Result<Box<dyn Iterator<Item = Filesystem> + 'a>> {
+    let args = self.args;
+    if args.sync {
+      sync_filesystems(args.files.as_deref().unwrap_or_default());
+    }
+    let filesystems: Box<dyn Iterator<Item = Filesystem>> = if args.files.is_none() {
+      let mounts = fsext::mounts()?;
+      let mounts: Box<dyn Iterator<Item = MountInfo>> = if options.dedupe {
+        Box::new(dedupe_selected(mounts, options).into_iter())
+      } else {
+        Box::new(mounts)
+      };
+      Box::new(select(
+        mounts.filter_map(|m| Filesystem::new(m, None)),
+        options,
+      ))
+    } else {
+      // The deepest mount containing a path is only known once the whole table is read.
+      let mounts = read_fs_list()?;
+      Box::new(args.paths().into_iter().filter_map(move |file| {
+        let filesystem = Filesystem::from_path(&mounts, &file);
+        if filesystem.is_none() {
+          // `--strict` lists it with the other errors.
+          if !args.strict {
+            eprintln!("ddf: {file}: No such file or directory");
+          }
+          problems::report(format!("{file}: No such file or directory"));
+        }
+        filesystem.filter(|fs| options.mount_filter.wants(&fs.mount_info))
+      }))
+    };
+    let columns = options.settings.columns.as_deref().unwrap_or_default();
+    let show = args.shown_columns();
+    let shown = |column| show.contains(&column) || columns.contains(&column);
+    let (disk, mounted) = (shown(Column::Disk), shown(Column::Mounted));
+    let raw = args.raw || Column::RAW.into_iter().any(shown);
+    Ok(Box::new(filesystems.map(move |mut fs| {
+      if raw {
+        fs.usage.raw = RawStatfs::read(&fs.mount_info.mount_dir);
+      }
+      // Once here rather than every time the id is needed.
+      fs.mount_info.fill_uuid();
+      fs.mount_info.fill_dev_id();
+      if disk {
+        fs.mount_info.disk = disk::disk(&fs.mount_info.dev_id).unwrap_or_default();
+      }
+      if mounted {
+        fs.mount_info.fill_mounted();
+      }
+      fs
+    })))
+  }
+}
+
+/// Filesystems of a support bundle, which keeps the raw fields it was collected with.
+pub(crate) struct Bundle {
+  pub(crate) path: PathBuf,
+}
+
+impl Bundle {
+  pub(crate) fn new(path: &Path) -> Self {
+    Self {
+      path: path.to_path_buf(),
+    }
+  }
+}
+
+impl Collector for Bundle {
+  fn collect<'a>(
+    &'a self,
+    options: &'a Options,
+  ) -> Result<Box<dyn Iterator<Item = Filesystem> + 'a>> {
+    let mut filesystems = bundle::load(&self.path)?;
+    if options.dedupe {
+      filesystems = fsext::dedupe(filesystems, |fs| &fs.mount_info);
+    }
+    Ok(Box::new(select(filesystems.into_iter(), options)))
+  }
+}
+
+/// Filesystems of the output of `df` on another machine.
+pub(crate) struct Imported<'a> {
+  pub(crate) path: &'a Path,
+}
+
+impl Collector for Imported<'_> {
+  fn collect<'a>(
+    &'a self,
+    options: &'a Options,
+  ) -> Result<Box<dyn Iterator<Item = Filesystem> + 'a>> {
+    Ok(Box::new(select(
+      import::load(self.path)?.into_iter(),
+      options,
+    )))
+  }
+}
+
+type Reload<'a> = Box<dyn FnMut() -> Result<Vec<Filesystem>> + 'a>;
+
+/// Standard output in the format of the arguments.
+pub(crate) struct Output<'a> {
+  args: &'a App,
+  settings: &'a Settings,
+  /// Sample of the streamed NDJSON lines.
+  sample: Sample,
+  /// Reload of the interactive table.
+  reload: Option<Reload<'a>>,
+}
+
+impl<'a> Output<'a> {
+  pub(crate) fn new(args: &'a App, settings: &'a Settings) -> Self {
+    Self {
+      args,
+      settings,
+      sample: Sample::now(settings),
+      reload: None,
+    }
+  }
+
+  /// Let the interactive table reload the filesystems with `reload`.
+  pub(crate) fn reload_with(mut self, reload: impl FnMut() -> Result<Vec<Filesystem>> + 'a) -> Self {
+    self.reload = Some(Box::new(reload));
+    self
+  }
+}
+
+impl Renderer for Output<'_> {
+  fn filesystem(&mut self, fs: &Filesystem) -> Result<()> {
+    let (args, config) = (self.args, self.settings);
+    let mut out = std::io::stdout().lock();
+    if let Some(template) = &args.format_string {
+      writeln!(
+        out,
+        "{}",
+        template.render(&Record::new(fs, config), &args.size_format(config))
+      )?;
+    } else if args.format == Format::Ndjson {
+      // Each line goes out as soon as its filesystem is statted.
+      output::write_ndjson_line(&self.sample, &Record::new(fs, config), &mut out)?;
+    }
+    Ok(())
+  }
+
+  fn finish(&mut self, filesystems: Vec<Filesystem>, report: &Report) -> Result<()> {
+    let (args, config) = (self.args, self.settings);
+    if args.format_string.is_some() || args.format == Format::Ndjson {
+      return Ok(());
+    }
+    // Quiet cron mails when nothing is above the threshold.
+    if filesystems.is_empty()
+      && (args.only_above.is_some() || args.critical_only || args.check)
+      && matches!(args.format, Format::Table | Format::Markdown)
+      && !args.tui
+    {
+      return Ok(());
+    }
+    let mut out = std::io::stdout().lock();
+    if let (Format::Table, Some(block_size)) = (args.format, args.posix_block_size()) {
+      return output::write_posix(report, block_size, &mut out);
+    }
+    if args.format == Format::Markdown {
+      return output::write_markdown(report, &args.size_format(config), args.emoji, &mut out);
+    }
+    if args.format == Format::Nagios {
+      return output::write_nagios(report, &args.size_format(config), &mut out);
+    }
+    if args.format != Format::Table {
+      return output::write(args.format, report, &mut out);
+    }
+    drop(out);
+
+    if args.tui {
+      let mut tui = tui::Tui::new(filesystems, config, args.size_format(config));
+      if let Some(reload) = self.reload.take() {
+        tui = tui.reload_with(reload, args.watch);
+      }
+      return tui.run();
+    }
+    render::render_table(
+      filesystems,
+      config,
+      &args.size_format(config),
+      args.table_options(config),
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::fsext::{FsUsage, MountInfo};
+
+  struct Fixed(Vec<Filesystem>);
+
+  impl Collector for Fixed {
+    fn collect<'a>(
+      &'a self,
+      options: &'a Options,
+    ) -> Result<Box<dyn Iterator<Item = Filesystem> + 'a>> {
+      Ok(Box::new(select(self.0.clone().into_iter(), options)))
+    }
+  }
+
+  #[derive(Default)]
+  struct Collected {
+    streamed: Vec<String>,
+    finished: Vec<String>,
+  }
+
+  impl Renderer for Collected {
+    fn filesystem(&mut self, fs: &Filesystem) -> Result<()> {
+      self.streamed.push(fs.mount_info.mount_dir.clone());
+      Ok(())
+    }
+
+    fn finish(&mut self, filesystems: Vec<Filesystem>, _report: &Report) -> Result<()> {
+      self.finished = filesystems
+        .into_iter()
+        .map(|fs| fs.mount_info.mount_dir)
+        .collect();
+      Ok(())
+    }
+  }
+
+  fn filesystem(mount_dir: &str, fs_type: &str, blocks: u64, bfree: u64) -> Filesystem {
+    Filesystem {
+      mount_info: MountInfo {
+        dev_name: format!("/dev/{}", mount_dir.len()),
+        mount_dir: mount_dir.to_string(),
+        fs_type: fs_type.to_string(),
+        ..Default::default()
+      },
+      usage: FsUsage {
+        blocksize: 1024,
+        blocks,
+        bfree,
+        bavail: bfree,
+        files: 0,
+        ffree: 0,
+        raw: None,
+      },
+    }
+  }
+
+  #[test]
+  fn test_run() {
+    let collector = Fixed(vec![
+      filesystem("/", "ext4", 100, 50),
+      filesystem("/proc", "proc", 0, 0),
+      filesystem("/run", "tmpfs", 100, 100),
+      filesystem("/home", "ext4", 100, 5),
+    ]);
+    let settings = Settings {
+      exclude: Some(vec![crate::settings::MountRule::FsType(
+        "tmpfs".to_string(),
+      )]),
+      ..Default::default()
+    };
+    let mut options = Options {
+      settings: &settings,
+      all: false,
+      only_above: None,
+      min_severity: None,
+      filter: None,
+      sort: Some((SortKey::Pcent, true)),
+      dedupe: true,
+      no_reserved: false,
+      mount_filter: MountFilter::default(),
+    };
+    let mut renderer = Collected::default();
+    let report = run(&options, &collector, &mut renderer).unwrap();
+    assert_eq!(renderer.finished, ["/home", "/"]);
+    assert_eq!(renderer.streamed, renderer.finished);
+    assert_eq!(report.filesystems.len(), 2);
+    assert_eq!(report.filesystems[0].target, "/home");
+
+    options.only_above = Some(0.9);
+    let mounts: Vec<_> = load(&options, &collector)
+      .unwrap()
+      .into_iter()
+      .map(|fs| fs.mount_info.mount_dir)
+      .collect();
+    assert_eq!(mounts, ["/home"]);
+
+    options.only_above = None;
+    options.min_severity = Some(Severity::Medium);
+    let report = run(&options, &collector, &mut Collected::default()).unwrap();
+    assert_eq!(report.filesystems.len(), 1);
+    assert_eq!(report.worst(), Severity::High);
+    options.min_severity = None;
+    options.filter = Some(settings.parse_where("pcent > 90 || target == '/'").unwrap());
+    let mounts: Vec<_> = load(&options, &collector)
+      .unwrap()
+      .into_iter()
+      .map(|fs| fs.mount_info.mount_dir)
+      .collect();
+    assert_eq!(mounts, ["/home", "/"]);
+
+    // Applied by every collector, like the bundle and imported ones.
+    options.filter = None;
+    options.all = true;
+    options.mount_filter.types = vec!["tmpfs".to_string()];
+    let mounts: Vec<_> = load(&options, &collector)
+      .unwrap()
+      .into_iter()
+      .map(|fs| fs.mount_info.mount_dir)
+      .collect();
+    assert_eq!(mounts, ["/run"]);
+  }
+
+  #[test]
+  fn test_dedupe_selected() {
+    let mount = |mount_dir: &str| MountInfo {
+      dev_id: "8:2".to_string(),
+      mount_dir: mount_dir.to_string(),
+      ..Default::default()
+    };
+    let settings = Settings {
+      exclude: Some(vec![crate::settings::MountRule::MountDirStartsWith(
+        "/data".to_string(),
+      )]),
+      ..Default::default()
+    };
+    let mut options = Options {
+      settings: &settings,
+      all: false,
+      only_above: None,
+      min_severity: None,
+      filter: None,
+      sort: None,
+      dedupe: true,
+      no_reserved: false,
+      mount_filter: MountFilter::default(),
+    };
+    let mounts = || [mount("/srv/volume"), mount("/data")].into_iter();
+    let kept = |options: &Options| -> Vec<String> {
+      dedupe_selected(mounts(), options)
+        .into_iter()
+        .map(|m| m.mount_dir)
+        .collect()
+    };
+    // The excluded shorter mount doesn't hide the other one.
+    assert_eq!(kept(&options), ["/srv/volume"]);
+    options.all = true;
+    assert_eq!(kept(&options), ["/data"]);
+  }
+}
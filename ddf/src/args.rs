@@ -1,15 +1,484 @@
-use clap::Parser;
+use crate::{
+  fsext::MountInfo,
+  hyperlink::Hyperlinks,
+  icons::IconStyle,
+  kubelet,
+  output::Format,
+  render::{parse_columns_width, Column, ColumnsWidth, Shown, TableOptions},
+  settings::{Settings, SortKey, Threshold},
+  template::{parse_template, Template},
+  units::{parse_block_size, parse_size, BlockSize, ByteSize, SizeFormat},
+};
+use clap::{Parser, Subcommand};
 use clap_complete::Shell;
+use std::{path::PathBuf, time::Duration};
 
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, disable_version_flag = true)]
 pub(crate) struct App {
-  /// List of file systems or mount points to display (optional).
+  #[command(subcommand)]
+  pub(crate) command: Option<Command>,
+  /// List of file systems or mount points to display (optional). Use `--` before paths starting
+  /// with a dash.
   pub(crate) files: Option<Vec<String>>,
+  /// Read the settings from FILE instead of the user's configuration directory.
+  #[arg(long, value_name = "FILE", global = true)]
+  config: Option<PathBuf>,
+  /// Only show the filesystems of type TYPE.
+  #[arg(short = 't', long = "type", value_name = "TYPE", global = true)]
+  types: Vec<String>,
+  /// Only show the filesystems mounted with all of OPTIONS, e.g. `ro` or `nosuid,noexec`.
+  #[arg(
+    long = "options",
+    value_name = "OPTIONS",
+    value_delimiter = ',',
+    global = true
+  )]
+  mount_options: Vec<String>,
+  /// Hide the filesystems mounted with any of OPTIONS, e.g. `nodev`.
+  #[arg(long, value_name = "OPTIONS", value_delimiter = ',', global = true)]
+  options_exclude: Vec<String>,
+  /// Hide the filesystems of type TYPE, in addition to the exclusions of the settings.
+  #[arg(short = 'x', long, value_name = "TYPE", global = true)]
+  exclude_type: Vec<String>,
+  /// Hide the mounts whose directory starts with PREFIX, or matches it when it is a glob, in
+  /// addition to the exclusions of the settings.
+  #[arg(long, value_name = "PREFIX", global = true)]
+  exclude_mount: Vec<String>,
+  /// Hide the filesystems smaller than SIZE (e.g. `1G`, `500M`).
+  #[arg(
+    long,
+    visible_alias = "exclude-below",
+    value_name = "SIZE",
+    value_parser = parse_size,
+    global = true
+  )]
+  min_size: Option<u64>,
+  /// Print version, with `--verbose` the commit, target, features and mount backend.
+  #[arg(short = 'V', long)]
+  pub(crate) version: bool,
+  /// Print the build details with `--version`.
+  #[arg(long, requires = "version")]
+  pub(crate) verbose: bool,
+  /// Validate the settings file and exit: 0 when valid, 66 when not found, 78 when invalid.
+  #[arg(long)]
+  pub(crate) check_config: bool,
+  /// Check that mounts can be listed, statted and rendered, then exit: 0 when every stage passed.
+  #[arg(long)]
+  pub(crate) self_test: bool,
+  /// Include pseudo, empty and excluded filesystems.
+  #[arg(short, long, global = true)]
+  pub(crate) all: bool,
+  /// Flush pending writes before reading the usage.
+  #[arg(long, global = true)]
+  pub(crate) sync: bool,
+  /// Print one line per filesystem from TEMPLATE, e.g. `{source}\t{pcent}\t{target}`.
+  #[arg(long, value_name = "TEMPLATE", value_parser = parse_template, global = true)]
+  pub(crate) format_string: Option<Template>,
+  /// Output format.
+  #[arg(
+    long,
+    visible_alias = "output",
+    value_enum,
+    default_value_t,
+    global = true
+  )]
+  pub(crate) format: Format,
+  /// Scale sizes by SIZE before printing them (e.g. `1M`, `64K`, `1GB`).
+  #[arg(short = 'B', long, value_name = "SIZE", value_parser = parse_block_size, group = "unit", global = true)]
+  block_size: Option<BlockSize>,
+  /// Print sizes with powers of 1000 (kB, MB, GB) instead of 1024.
+  #[arg(short = 'H', long, group = "unit", global = true)]
+  si: bool,
+  /// Print sizes as raw counts of KiB, like `-B 1K` without the unit suffix.
+  #[arg(short = 'k', group = "unit", global = true)]
+  kibibytes: bool,
+  /// Print sizes as raw counts of MiB.
+  #[arg(short = 'm', group = "unit", global = true)]
+  mebibytes: bool,
+  /// Print sizes as raw counts of GiB.
+  #[arg(short = 'g', group = "unit", global = true)]
+  gibibytes: bool,
+  /// Prefix the used percentage of the Markdown table with a severity emoji.
+  #[arg(long, global = true)]
+  pub(crate) emoji: bool,
+  /// Print the POSIX `df -P` format, in 512-byte blocks when `POSIXLY_CORRECT` is set.
+  #[arg(short = 'P', long, global = true)]
+  portability: bool,
+  /// Redraw the table every SECONDS (2 by default) until `q` is pressed.
+  #[arg(
+    short,
+    long,
+    value_name = "SECONDS",
+    num_args = 0..=1,
+    require_equals = true,
+    default_missing_value = "2",
+    value_parser = parse_interval,
+  )]
+  pub(crate) watch: Option<Duration>,
+  /// Show the change of the used space since the previous refresh of `--watch`.
+  #[arg(long, requires = "watch")]
+  delta: bool,
+  /// Show the time before each filesystem is full at its growth rate during `--watch`, like
+  /// `~3d`.
+  #[arg(long, requires = "watch")]
+  eta: bool,
+  /// Show the most used filesystem in the terminal title (the pane title in tmux) during
+  /// `--watch`.
+  #[arg(long, requires = "watch")]
+  set_title: bool,
+  /// Sample the usage N times and print its min, average, max and growth per filesystem.
+  #[arg(long, value_name = "N", conflicts_with_all = ["watch", "tui"], value_parser = clap::value_parser!(u32).range(1..))]
+  pub(crate) repeat: Option<u32>,
+  /// Time between the samples of `--repeat`, in seconds.
+  #[arg(long, value_name = "SECONDS", requires = "repeat", default_value = "1", value_parser = parse_interval)]
+  pub(crate) interval: Duration,
+  /// Group the mounts of a Kubernetes node by pod and show the ephemeral storage of each pod,
+  /// `DIR` being the root directory of the kubelet.
+  #[arg(
+    long,
+    value_name = "DIR",
+    num_args = 0..=1,
+    require_equals = true,
+    default_missing_value = kubelet::DEFAULT_ROOT,
+    conflicts_with_all = ["watch", "repeat", "tui"],
+  )]
+  pub(crate) kubelet: Option<PathBuf>,
+  /// Show the untouched `statvfs` fields of every filesystem, as columns of the table or under
+  /// `raw` in the serialized formats.
+  #[arg(long, global = true)]
+  pub(crate) raw: bool,
+  /// Exit with an error listing what couldn't be read (mount table lines, statfs, paths) instead
+  /// of printing partial data silently.
+  #[arg(long, global = true)]
+  pub(crate) strict: bool,
+  /// List the filesystems mounted more than once (bind mounts, btrfs subvolumes) at each of their
+  /// mounts instead of the shortest one.
+  #[arg(long, global = true)]
+  pub(crate) no_dedupe: bool,
+  /// Leave the space reserved for root out of the sizes, so the size is the used plus the
+  /// available space.
+  #[arg(long, global = true)]
+  pub(crate) no_reserved: bool,
+  /// Used ratio above which a filesystem is shown in yellow (e.g. `0.7` or `70%`), or free space
+  /// below which (`20G`), instead of `threshold.medium` of the settings.
+  #[arg(long, value_name = "THRESHOLD", value_parser = parse_threshold, global = true)]
+  medium_threshold: Option<Threshold>,
+  /// Used ratio above which a filesystem is shown in red (e.g. `0.85` or `85%`), or free space
+  /// below which (`5G`), instead of `threshold.high` of the settings.
+  #[arg(long, value_name = "THRESHOLD", value_parser = parse_threshold, global = true)]
+  high_threshold: Option<Threshold>,
+  /// Only show the filesystems more used than PERCENT (e.g. `80%`), printing nothing when there
+  /// are none.
+  #[arg(long, value_name = "PERCENT", value_parser = parse_percent, global = true)]
+  pub(crate) only_above: Option<f64>,
+  /// Only show the filesystems above their high threshold in the settings.
+  #[arg(long, conflicts_with = "only_above", global = true)]
+  pub(crate) critical_only: bool,
+  /// Only show the filesystems above their medium threshold, and exit with 1 when there are
+  /// some, 2 when some are above their high threshold.
+  #[arg(long, conflicts_with_all = ["only_above", "critical_only", "watch", "repeat", "tui"])]
+  pub(crate) check: bool,
+  /// Send a desktop notification for the filesystems crossing their high threshold, and again
+  /// after `on_high_cooldown` while they stay above. At each refresh with `--watch`.
+  #[arg(long)]
+  pub(crate) notify: bool,
+  /// Read the details needing root, like the progress of the btrfs balances, through `sudo` when
+  /// not root. Only this collection runs privileged.
+  #[arg(long, global = true)]
+  pub(crate) sudo: bool,
+  /// Only show the filesystems satisfying EXPR, e.g. `pcent > 80 && fstype == "ext4"`, or the
+  /// name of a `[filter.where]` setting.
+  #[arg(long = "where", value_name = "EXPR", global = true)]
+  pub(crate) where_expr: Option<String>,
+  /// Sort the filesystems by KEY instead of the mount table order, the `sort` setting by default.
+  #[arg(long, value_name = "KEY", value_enum, global = true)]
+  sort: Option<SortKey>,
+  /// Reverse the order of `--sort`.
+  #[arg(long, global = true)]
+  reverse: bool,
+  /// Open a full-screen interactive table instead of printing it.
+  #[arg(long, global = true)]
+  pub(crate) tui: bool,
+  /// Widths of the filesystem and mount directory columns, e.g. `20,40` or `,40`.
+  #[arg(long, value_name = "DEVICE,MOUNT", value_parser = parse_columns_width, global = true)]
+  columns_width: Option<ColumnsWidth>,
+  /// Wrap the mount directories longer than their column instead of truncating them.
+  #[arg(long, global = true)]
+  wrap: bool,
+  /// Link the mount directories so a click opens them in the file manager.
+  #[arg(long, value_name = "WHEN", value_enum, default_value_t, global = true)]
+  hyperlinks: Hyperlinks,
+  /// Start each row with an icon of the kind of filesystem (disk, network, USB, memory,
+  /// virtual), the `icons` setting by default. `text` uses words for the fonts without symbols.
+  #[arg(
+    long,
+    value_name = "STYLE",
+    value_enum,
+    num_args = 0..=1,
+    require_equals = true,
+    default_missing_value = "nerd",
+    global = true
+  )]
+  pub(crate) icons: Option<IconStyle>,
+  /// Show after the percentages whether the usage grew (↑), shrank (↓) or stayed flat (→) since a
+  /// previous run, the `trend` setting.
+  #[arg(long, global = true)]
+  trend: bool,
+  /// Show the optional COLUMNS before the gauge, e.g. `options,uuid`.
+  #[arg(
+    long,
+    value_name = "COLUMNS",
+    value_enum,
+    value_delimiter = ',',
+    global = true
+  )]
+  pub(crate) show: Vec<Column>,
+  /// Show the free percentage in the gauges instead of the used one, the `free_percent` setting
+  /// by default.
+  #[arg(long, global = true)]
+  free_percent: bool,
+  /// Draw the usage as a stacked bar of the used, reserved and available space.
+  #[arg(long, global = true)]
+  stacked: bool,
+  /// Draw usage pies below the table on terminals supporting the kitty graphics protocol.
+  #[arg(long)]
+  graphics: bool,
+  /// Print the hostname, kernel, uptime, boot id and sample time above the table.
+  #[arg(long)]
+  pub(crate) header: bool,
+  /// Replace device names, server names, the hostname and UUIDs by stable hashes.
+  #[arg(long, global = true)]
+  anonymize: bool,
   #[arg(long, value_enum)]
   completion: Option<Shell>,
 }
 
+/// Parse a positive number of seconds.
+fn parse_interval(value: &str) -> Result<Duration, String> {
+  match value.parse::<f64>() {
+    Ok(seconds) if seconds > 0.0 && seconds.is_finite() => Ok(Duration::from_secs_f64(seconds)),
+    _ => Err(format!(
+      "invalid interval `{value}`: expected a positive number of seconds"
+    )),
+  }
+}
+
+/// Parse a percentage such as `80%` or `80` as a ratio.
+fn parse_percent(value: &str) -> Result<f64, String> {
+  match value.strip_suffix('%').unwrap_or(value).parse::<f64>() {
+    Ok(percent) if (0.0..=100.0).contains(&percent) => Ok(percent / 100.0),
+    _ => Err(format!(
+      "invalid percentage `{value}`: expected a number between 0 and 100"
+    )),
+  }
+}
+
+/// A threshold: a ratio between 0 and 1, a percentage with a `%`, or a free space with a unit.
+fn parse_threshold(value: &str) -> Result<Threshold, String> {
+  if value.ends_with('%') {
+    return parse_percent(value).map(Threshold::Ratio);
+  }
+  match value.parse::<f64>() {
+    Ok(ratio) if (0.0..=1.0).contains(&ratio) => Ok(Threshold::Ratio(ratio)),
+    Err(_) if value.ends_with(|c: char| c.is_ascii_alphabetic()) => {
+      parse_size(value).map(|bytes| Threshold::Free(ByteSize(bytes)))
+    }
+    _ => Err(format!(
+      "invalid threshold `{value}`: expected a ratio between 0 and 1, a percentage or a size"
+    )),
+  }
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum Command {
+  /// Collect diagnostic data to attach to a bug report.
+  Doctor {
+    /// Write a support bundle with the mount table and statfs results to FILE.
+    #[arg(long, value_name = "FILE")]
+    collect: PathBuf,
+  },
+  /// Show the largest directories below a path, without leaving its filesystem.
+  Top {
+    #[command(flatten)]
+    scan: ScanArgs,
+  },
+  /// Show the disk usage per user below a path, without leaving its filesystem.
+  Users {
+    #[command(flatten)]
+    scan: ScanArgs,
+  },
+  /// Render a support bundle as if it was the local machine.
+  Analyze {
+    /// Support bundle produced by `ddf doctor --collect`.
+    bundle: PathBuf,
+  },
+  /// Render the output of `df` captured on another machine, like `df -P`, `df -hT` or
+  /// `df --output`.
+  ImportDf {
+    /// File with the output of `df`, `-` for the standard input.
+    file: PathBuf,
+  },
+  /// Manage the settings file.
+  Config {
+    #[command(subcommand)]
+    action: ConfigCommand,
+  },
+  /// Write the shell completions and the man pages to a directory, for packaging.
+  Generate {
+    /// Directory to create the `completions` and `man` subdirectories in.
+    #[arg(long, value_name = "DIR")]
+    out_dir: PathBuf,
+  },
+  /// Print the progress of the btrfs balances, run by `--sudo` as root.
+  #[command(hide = true)]
+  BalanceHelper { mount_dirs: Vec<String> },
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum ConfigCommand {
+  /// Write a settings file with the default exclusions, to edit.
+  Init {
+    /// Replace the settings file if it exists.
+    #[arg(long)]
+    force: bool,
+  },
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct ScanArgs {
+  /// Directory to scan.
+  #[arg(default_value = ".")]
+  pub(crate) path: PathBuf,
+  /// Number of entries to print.
+  #[arg(short = 'n', long, default_value_t = 10)]
+  pub(crate) count: usize,
+  /// Skip the directories whose path or name matches GLOB.
+  #[arg(long, value_name = "GLOB")]
+  pub(crate) exclude_dir: Vec<glob::Pattern>,
+  /// Stop the scan after DURATION (e.g. `30s`, `2m`) and print the partial results.
+  #[arg(long, value_name = "DURATION", value_parser = humantime::parse_duration)]
+  pub(crate) time_budget: Option<Duration>,
+}
+
+/// Types and mount options the filesystems must have, kept whatever the collector.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MountFilter {
+  pub(crate) types: Vec<String>,
+  pub(crate) options: Vec<String>,
+  pub(crate) options_exclude: Vec<String>,
+}
+
+impl MountFilter {
+  /// Whether `--type`, `--options` and `--options-exclude` let the mount through.
+  pub(crate) fn wants(&self, mount_info: &MountInfo) -> bool {
+    (self.types.is_empty() || self.types.contains(&mount_info.fs_type))
+      && self.options.iter().all(|name| mount_info.has_option(name))
+      && !self
+        .options_exclude
+        .iter()
+        .any(|name| mount_info.has_option(name))
+  }
+}
+
+impl App {
+  /// Format used for the size, used and available columns.
+  pub(crate) fn size_format(&self, settings: &Settings) -> SizeFormat {
+    let fixed = |exponent: u32| {
+      SizeFormat::Blocks(BlockSize {
+        bytes: 1024u64.pow(exponent),
+        suffix: None,
+      })
+    };
+    match &self.block_size {
+      Some(block_size) => SizeFormat::Blocks(block_size.clone()),
+      None if self.kibibytes => fixed(1),
+      None if self.mebibytes => fixed(2),
+      None if self.gibibytes => fixed(3),
+      None if settings.si.unwrap_or_default() => SizeFormat::Si,
+      None => SizeFormat::Human,
+    }
+  }
+
+  /// Mounts let through by `--type`, `--options` and `--options-exclude`.
+  pub(crate) fn mount_filter(&self) -> MountFilter {
+    MountFilter {
+      types: self.types.clone(),
+      options: self.mount_options.clone(),
+      options_exclude: self.options_exclude.clone(),
+    }
+  }
+
+  /// Order of the filesystems, with whether it is reversed, none for the mount table order.
+  pub(crate) fn sort(&self, settings: &Settings) -> Option<(SortKey, bool)> {
+    Some((self.sort.or(settings.sort)?, self.reverse))
+  }
+
+  /// Block size of the POSIX output when it is selected: the one of `-B`, `-k`, `-m` or `-g`, else
+  /// 512 bytes when `POSIXLY_CORRECT` is set and 1024 bytes otherwise, like GNU df.
+  pub(crate) fn posix_block_size(&self) -> Option<u64> {
+    if !self.portability {
+      return None;
+    }
+    Some(match self.size_format(&Settings::default()) {
+      SizeFormat::Blocks(block_size) => block_size.bytes,
+      _ if std::env::var_os("POSIXLY_CORRECT").is_some() => 512,
+      _ => 1024,
+    })
+  }
+
+  pub(crate) fn table_options(&self, settings: &Settings) -> TableOptions {
+    TableOptions {
+      columns_width: self.columns_width.unwrap_or_default(),
+      wrap: self.wrap,
+      header: self.header,
+      hyperlinks: self.hyperlinks,
+      graphics: self.graphics,
+      stacked: self.stacked,
+      delta: self.delta,
+      eta: self.eta,
+      show: Shown::new(&self.shown_columns()),
+      set_title: self.set_title,
+      icons: self.icons.or(settings.icons),
+      trend: self.trend || settings.trend.unwrap_or_default(),
+    }
+  }
+
+  /// Columns of `--show`, with the `statvfs` fields of `--raw`.
+  pub(crate) fn shown_columns(&self) -> Vec<Column> {
+    let raw = Column::RAW.into_iter().filter(|_| self.raw);
+    self.show.iter().copied().chain(raw).collect()
+  }
+
+  /// The `FILES` arguments, with the globs expanded where the shell doesn't do it.
+  pub(crate) fn paths(&self) -> Vec<String> {
+    let files = self.files.iter().flatten();
+    if cfg!(windows) {
+      files.flat_map(|file| expand_glob(file)).collect()
+    } else {
+      files.cloned().collect()
+    }
+  }
+}
+
+/// Paths matching `arg` when it is a glob matching something, `arg` itself otherwise.
+fn expand_glob(arg: &str) -> Vec<String> {
+  if !arg.contains(['*', '?', '[']) {
+    return vec![arg.to_string()];
+  }
+  let matches: Vec<String> = glob::glob(arg)
+    .into_iter()
+    .flatten()
+    .flatten()
+    .map(|path| path.display().to_string())
+    .collect();
+  if matches.is_empty() {
+    vec![arg.to_string()]
+  } else {
+    matches
+  }
+}
+
 pub(crate) fn gen_completions(args: &App) {
   if let Some(generator) = args.completion {
     use clap::{Command, CommandFactory};
@@ -24,3 +493,47 @@ pub(crate) fn gen_completions(args: &App) {
     std::process::exit(0);
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_percent() {
+    assert_eq!(parse_percent("80%"), Ok(0.8));
+    assert_eq!(parse_percent("50"), Ok(0.5));
+    assert!(parse_percent("120%").is_err());
+    assert!(parse_percent("high").is_err());
+    assert_eq!(parse_threshold("0.7"), Ok(Threshold::Ratio(0.7)));
+    assert_eq!(parse_threshold("85%"), Ok(Threshold::Ratio(0.85)));
+    assert_eq!(
+      parse_threshold("5G"),
+      Ok(Threshold::Free(ByteSize(5 << 30)))
+    );
+    assert!(parse_threshold("85").is_err());
+  }
+
+  #[test]
+  fn test_posix_block_size() {
+    let parse = |args: &[&str]| App::try_parse_from([&["ddf"], args].concat());
+    let block_size = |args: &[&str]| parse(args).unwrap().posix_block_size();
+    assert_eq!(block_size(&[]), None);
+    assert_eq!(block_size(&["-P", "-B1M"]), Some(1 << 20));
+    assert_eq!(block_size(&["-P", "-k"]), Some(1024));
+  }
+
+  #[test]
+  fn test_expand_glob() {
+    let tmp = tempfile::TempDir::new().expect("Failed to create temp dir");
+    for name in ["a1", "a2", "b"] {
+      std::fs::create_dir(tmp.path().join(name)).unwrap();
+    }
+    let root = tmp.path().display();
+    assert_eq!(
+      expand_glob(&format!("{root}/a*")),
+      [format!("{root}/a1"), format!("{root}/a2")]
+    );
+    assert_eq!(expand_glob(&format!("{root}/c*")), [format!("{root}/c*")]);
+    assert_eq!(expand_glob("/weird/--path"), ["/weird/--path"]);
+  }
+}
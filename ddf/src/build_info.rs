@@ -0,0 +1,24 @@
+//! `--version --verbose`: what a bug report needs to know about the build.
+
+use crate::fsext;
+
+/// Version line, followed with `verbose` by the build and platform details.
+pub(crate) fn version(verbose: bool) -> String {
+  let mut text = format!("ddf {}\n", env!("CARGO_PKG_VERSION"));
+  if verbose {
+    let or_unknown = |value: &'static str| if value.is_empty() { "unknown" } else { value };
+    let features = env!("DDF_FEATURES");
+    text.push_str(&format!(
+      "commit: {}\ntarget: {}\nfeatures: {}\nmount backend: {}\n",
+      or_unknown(env!("DDF_GIT_COMMIT")),
+      or_unknown(env!("DDF_TARGET")),
+      if features.is_empty() {
+        "none"
+      } else {
+        features
+      },
+      fsext::mount_backend(),
+    ));
+  }
+  text
+}
@@ -0,0 +1,162 @@
+//! Support bundles: a snapshot of the mount table and of the `statfs` results of a machine,
+//! collected by `ddf doctor --collect` and rendered elsewhere by `ddf analyze`. Bundles are
+//! `.tar.gz`, or `.tar.zst` when the file name asks for it.
+
+use crate::{
+  compress::{self, Compression},
+  filesystem::Filesystem,
+  fsext::{parse_mountinfo, read_fs_list, statfs, FsUsage, LINUX_MOUNTINFO},
+};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, io::Read, path::Path};
+use tracing::{debug, instrument};
+
+const MOUNTINFO_ENTRY: &str = "mountinfo";
+const STATFS_ENTRY: &str = "statfs.json";
+
+/// `statfs` result of one mount, found by its device and directory for stacked mounts not to
+/// share a result.
+#[derive(Debug, Serialize, Deserialize)]
+struct StatfsDump {
+  /// Empty in the bundles of older versions, matched by the directory only.
+  #[serde(default)]
+  dev_id: String,
+  mount_dir: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  usage: Option<FsUsage>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  error: Option<String>,
+}
+
+fn append<W: std::io::Write>(tar: &mut tar::Builder<W>, name: &str, data: &[u8]) -> Result<()> {
+  let mut header = tar::Header::new_gnu();
+  header.set_size(data.len() as u64);
+  header.set_mode(0o644);
+  header.set_mtime(
+    std::time::SystemTime::now()
+      .duration_since(std::time::UNIX_EPOCH)?
+      .as_secs(),
+  );
+  header.set_cksum();
+  tar.append_data(&mut header, name, data)?;
+  Ok(())
+}
+
+/// Write a support bundle of the local machine to `path`.
+#[instrument]
+pub(crate) fn collect(path: &Path) -> Result<()> {
+  let mountinfo = std::fs::read(LINUX_MOUNTINFO).context("Can't read the mount table")?;
+  let dumps: Vec<StatfsDump> = read_fs_list()?
+    .into_iter()
+    .map(|mount| match statfs(mount.mount_dir.clone()) {
+      Ok(stat) => StatfsDump {
+        dev_id: mount.dev_id,
+        mount_dir: mount.mount_dir,
+        usage: Some(FsUsage::new(stat)),
+        error: None,
+      },
+      Err(error) => StatfsDump {
+        dev_id: mount.dev_id,
+        mount_dir: mount.mount_dir,
+        usage: None,
+        error: Some(error),
+      },
+    })
+    .collect();
+
+  // Bundles are always compressed: zstd when asked by the extension, gzip otherwise.
+  let compression = match Compression::from_path(path) {
+    Compression::Zstd => Compression::Zstd,
+    _ => Compression::Gzip,
+  };
+  let mut tar = tar::Builder::new(compress::create(path, compression)?);
+  append(&mut tar, MOUNTINFO_ENTRY, &mountinfo)?;
+  append(&mut tar, STATFS_ENTRY, &serde_json::to_vec_pretty(&dumps)?)?;
+  tar.into_inner()?.finish()?;
+
+  eprintln!("Support bundle written to {}", path.display());
+  Ok(())
+}
+
+/// Read the filesystems recorded in the support bundle at `path`.
+#[instrument]
+pub(crate) fn load(path: &Path) -> Result<Vec<Filesystem>> {
+  let mut archive = tar::Archive::new(compress::open(path)?);
+  let mut mountinfo = None;
+  let mut dumps: Option<Vec<StatfsDump>> = None;
+  for entry in archive.entries()? {
+    let mut entry = entry?;
+    let name = entry.path()?.to_string_lossy().to_string();
+    let mut data = Vec::new();
+    entry.read_to_end(&mut data)?;
+    match name.as_str() {
+      MOUNTINFO_ENTRY => mountinfo = Some(data),
+      STATFS_ENTRY => dumps = Some(serde_json::from_slice(&data)?),
+      _ => debug!("Ignore bundle entry {name}"),
+    }
+  }
+  let mountinfo = mountinfo.with_context(|| format!("No {MOUNTINFO_ENTRY} in the bundle"))?;
+  let usages: HashMap<(String, String), FsUsage> = dumps
+    .with_context(|| format!("No {STATFS_ENTRY} in the bundle"))?
+    .into_iter()
+    .filter_map(|dump| Some(((dump.dev_id, dump.mount_dir), dump.usage?)))
+    .collect();
+
+  Ok(
+    parse_mountinfo(mountinfo.as_slice())
+      .into_iter()
+      .filter_map(|mount_info| {
+        let usage = usages
+          .get(&(mount_info.dev_id.clone(), mount_info.mount_dir.clone()))
+          .or_else(|| usages.get(&(String::new(), mount_info.mount_dir.clone())))?
+          .clone();
+        Some(Filesystem { mount_info, usage })
+      })
+      .collect(),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Mounts of `filesystems` with their size, comparable between two reads.
+  fn summary(filesystems: &[Filesystem]) -> Vec<(String, String, String, u64, u64)> {
+    let mut summary: Vec<_> = filesystems
+      .iter()
+      .map(|fs| {
+        (
+          fs.mount_info.dev_id.clone(),
+          fs.mount_info.mount_dir.clone(),
+          fs.mount_info.fs_type.clone(),
+          fs.usage.blocksize,
+          fs.usage.blocks,
+        )
+      })
+      .collect();
+    summary.sort();
+    summary
+  }
+
+  #[test]
+  fn test_collect_load() {
+    if !Path::new(LINUX_MOUNTINFO).exists() {
+      return;
+    }
+    let tmp = tempfile::TempDir::new().expect("Failed to create temp dir");
+    for name in ["bundle.tar.gz", "bundle.tar.zst"] {
+      let path = tmp.path().join(name);
+      collect(&path).unwrap();
+      let local: Vec<Filesystem> = read_fs_list()
+        .unwrap()
+        .into_iter()
+        .filter_map(|mount_info| {
+          let usage = FsUsage::new(statfs(mount_info.mount_dir.clone()).ok()?);
+          Some(Filesystem { mount_info, usage })
+        })
+        .collect();
+      assert_eq!(summary(&load(&path).unwrap()), summary(&local));
+    }
+  }
+}
@@ -0,0 +1,126 @@
+//! Compression of the files ddf writes: chosen from the file extension when writing, detected
+//! from the magic bytes when reading, so readers don't care how a file was written.
+
+use anyhow::{Context, Result};
+use flate2::{read::MultiGzDecoder, write::GzEncoder};
+use std::{
+  fs::File,
+  io::{BufRead, BufReader, Read, Write},
+  path::Path,
+};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Compression {
+  None,
+  Gzip,
+  Zstd,
+}
+
+impl Compression {
+  /// Compression named by the extension of `path`: `.zst` for zstd, `.gz` or `.tgz` for gzip.
+  pub(crate) fn from_path(path: &Path) -> Self {
+    match path.extension().and_then(|extension| extension.to_str()) {
+      Some("zst" | "zstd" | "tzst") => Self::Zstd,
+      Some("gz" | "tgz") => Self::Gzip,
+      _ => Self::None,
+    }
+  }
+
+  fn detect(header: &[u8]) -> Self {
+    if header.starts_with(ZSTD_MAGIC) {
+      Self::Zstd
+    } else if header.starts_with(GZIP_MAGIC) {
+      Self::Gzip
+    } else {
+      Self::None
+    }
+  }
+}
+
+/// File being written through an encoder. [`Writer::finish`] must be called to write the end of
+/// the compressed stream.
+pub(crate) enum Writer {
+  Plain(File),
+  Gzip(GzEncoder<File>),
+  Zstd(zstd::Encoder<'static, File>),
+}
+
+impl Writer {
+  pub(crate) fn finish(self) -> Result<()> {
+    match self {
+      Self::Plain(mut file) => file.flush()?,
+      Self::Gzip(encoder) => encoder.finish()?.flush()?,
+      Self::Zstd(encoder) => encoder.finish()?.flush()?,
+    }
+    Ok(())
+  }
+}
+
+impl Write for Writer {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    match self {
+      Self::Plain(file) => file.write(buf),
+      Self::Gzip(encoder) => encoder.write(buf),
+      Self::Zstd(encoder) => encoder.write(buf),
+    }
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    match self {
+      Self::Plain(file) => file.flush(),
+      Self::Gzip(encoder) => encoder.flush(),
+      Self::Zstd(encoder) => encoder.flush(),
+    }
+  }
+}
+
+/// Create `path`, compressed with `compression`.
+pub(crate) fn create(path: &Path, compression: Compression) -> Result<Writer> {
+  let file = File::create(path).with_context(|| format!("Can't create {}", path.display()))?;
+  Ok(match compression {
+    Compression::None => Writer::Plain(file),
+    Compression::Gzip => Writer::Gzip(GzEncoder::new(file, flate2::Compression::default())),
+    Compression::Zstd => Writer::Zstd(zstd::Encoder::new(file, 0)?),
+  })
+}
+
+/// Open `path` for reading, decompressing it when it starts with a gzip or zstd header.
+pub(crate) fn open(path: &Path) -> Result<Box<dyn Read>> {
+  let file = File::open(path).with_context(|| format!("Can't open {}", path.display()))?;
+  let mut reader = BufReader::new(file);
+  Ok(match Compression::detect(reader.fill_buf()?) {
+    Compression::None => Box::new(reader),
+    Compression::Gzip => Box::new(MultiGzDecoder::new(reader)),
+    Compression::Zstd => Box::new(zstd::Decoder::with_buffer(reader)?),
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_round_trip() {
+    let tmp = tempfile::TempDir::new().expect("Failed to create temp dir");
+    for (name, compression) in [
+      ("plain.json", Compression::None),
+      ("data.json.gz", Compression::Gzip),
+      ("data.json.zst", Compression::Zstd),
+    ] {
+      let path = tmp.path().join(name);
+      assert_eq!(Compression::from_path(&path), compression);
+      let mut writer = create(&path, compression).unwrap();
+      writer.write_all(b"{\"used\": 42}").unwrap();
+      writer.finish().unwrap();
+      // Detection ignores the name.
+      let renamed = tmp.path().join(format!("{name}.renamed"));
+      std::fs::rename(&path, &renamed).unwrap();
+      let mut data = String::new();
+      open(&renamed).unwrap().read_to_string(&mut data).unwrap();
+      assert_eq!(data, "{\"used\": 42}");
+    }
+  }
+}
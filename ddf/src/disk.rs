@@ -0,0 +1,103 @@
+//! Physical disk under a block device, read from sysfs: its model and whether it spins, to tell
+//! which drive is filling up.
+
+use std::{
+  fs,
+  path::{Path, PathBuf},
+};
+
+/// Device mapper and md layers followed down to a disk, at most.
+const MAX_DEPTH: usize = 4;
+
+fn read_attribute(dir: &Path, name: &str) -> Option<String> {
+  let value = fs::read_to_string(dir.join(name)).ok()?;
+  Some(value.trim().to_string()).filter(|value| !value.is_empty())
+}
+
+/// Sysfs directory of the disk holding the block device `dir`: the parent of a partition, the
+/// first device below a device mapper or md array.
+fn disk_dir(dir: &Path, depth: usize) -> Option<PathBuf> {
+  if dir.join("partition").exists() {
+    return dir.parent().map(Path::to_path_buf);
+  }
+  let slave = fs::read_dir(dir.join("slaves"))
+    .ok()
+    .and_then(|mut slaves| slaves.next()?.ok());
+  match slave {
+    Some(slave) if depth < MAX_DEPTH => disk_dir(&fs::canonicalize(slave.path()).ok()?, depth + 1),
+    _ => Some(dir.to_path_buf()),
+  }
+}
+
+/// Model and kind of the disk at the sysfs directory `dir`, like `Samsung SSD 980 (NVMe)`.
+fn describe(dir: &Path) -> Option<String> {
+  let name = dir.file_name()?.to_string_lossy();
+  let path = dir.to_string_lossy();
+  let kind = if name.starts_with("nvme") {
+    "NVMe"
+  } else if path.contains("/virtual/") {
+    "virtual"
+  } else if path.contains("/virtio") {
+    // Virtio disks report whatever the host says about rotation.
+    "virtio"
+  } else {
+    match read_attribute(dir, "queue/rotational")?.as_str() {
+      "1" => "HDD",
+      _ => "SSD",
+    }
+  };
+  Some(match read_attribute(dir, "device/model") {
+    Some(model) => format!("{model} ({kind})"),
+    None => kind.to_string(),
+  })
+}
+
+/// Model and kind of the disk under the block device with the `major:minor` number `dev_id`, none
+/// when there is no block device (network and virtual filesystems).
+pub(crate) fn disk(dev_id: &str) -> Option<String> {
+  if dev_id.is_empty() {
+    return None;
+  }
+  let dir = fs::canonicalize(Path::new("/sys/dev/block").join(dev_id)).ok()?;
+  describe(&disk_dir(&dir, 0)?)
+}
+
+/// Whether the block device with the `major:minor` number `dev_id` is on a USB bus.
+pub(crate) fn is_usb(dev_id: &str) -> bool {
+  !dev_id.is_empty()
+    && fs::canonicalize(Path::new("/sys/dev/block").join(dev_id))
+      .ok()
+      .and_then(|dir| disk_dir(&dir, 0))
+      .is_some_and(|dir| dir.to_string_lossy().contains("/usb"))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_disk() {
+    let tmp = tempfile::tempdir().unwrap();
+    let sda = tmp.path().join("pci0000:00/ata1/block/sda");
+    fs::create_dir_all(sda.join("sda2")).unwrap();
+    fs::create_dir_all(sda.join("queue")).unwrap();
+    fs::create_dir_all(sda.join("device")).unwrap();
+    fs::write(sda.join("sda2/partition"), "2\n").unwrap();
+    fs::write(sda.join("queue/rotational"), "1\n").unwrap();
+    fs::write(sda.join("device/model"), "WDC WD40EFRX-68N\n").unwrap();
+    let dm = tmp.path().join("virtual/block/dm-0");
+    fs::create_dir_all(dm.join("slaves")).unwrap();
+    std::os::unix::fs::symlink(sda.join("sda2"), dm.join("slaves/sda2")).unwrap();
+
+    let disk = disk_dir(&dm, 0).unwrap();
+    assert_eq!(
+      fs::canonicalize(&disk).unwrap(),
+      fs::canonicalize(&sda).unwrap()
+    );
+    assert_eq!(describe(&disk).as_deref(), Some("WDC WD40EFRX-68N (HDD)"));
+    fs::write(sda.join("queue/rotational"), "0\n").unwrap();
+    fs::remove_file(sda.join("device/model")).unwrap();
+    assert_eq!(describe(&sda).as_deref(), Some("SSD"));
+    assert_eq!(describe(&dm).as_deref(), Some("virtual"));
+  }
+}
@@ -0,0 +1,363 @@
+//! `--where` expressions: conditions over the fields of each filesystem, like
+//! `pcent > 80 && fstype == "ext4"`.
+//!
+//! Fields are the ones of `--format-string`. Sizes compare in bytes and accept the suffixes of
+//! `--block-size` (`size > 1T`), `pcent` compares in percents (`pcent >= 90%`). `=~` matches a
+//! regular expression. Conditions combine with `&&`, `||`, `!` and parentheses, and the names of
+//! the `[filter.where]` settings stand for their expressions.
+
+use crate::{output::Record, template::Field, units::parse_size};
+use regex::Regex;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+  Ident(String),
+  Number(f64),
+  Text(String),
+  Op(&'static str),
+}
+
+const OPERATORS: [&str; 13] = [
+  "==", "!=", "<=", ">=", "=~", "&&", "||", "<", ">", "!", "(", ")", "=",
+];
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+  let mut tokens = vec![];
+  let mut rest = source.trim_start();
+  while let Some(c) = rest.chars().next() {
+    if c == '"' || c == '\'' {
+      let end = rest[1..]
+        .find(c)
+        .ok_or_else(|| format!("unclosed string {rest}"))?;
+      tokens.push(Token::Text(rest[1..=end].to_string()));
+      rest = &rest[end + 2..];
+    } else if c.is_ascii_alphanumeric() || c == '_' || c == '.' {
+      let end = rest
+        .find(|c: char| !(c.is_ascii_alphanumeric() || "_.%-".contains(c)))
+        .unwrap_or(rest.len());
+      let word = &rest[..end];
+      tokens.push(if c.is_ascii_digit() || c == '.' {
+        Token::Number(parse_number(word)?)
+      } else {
+        Token::Ident(word.to_string())
+      });
+      rest = &rest[end..];
+    } else {
+      let op = OPERATORS
+        .into_iter()
+        .find(|op| rest.starts_with(op))
+        .ok_or_else(|| format!("unexpected `{c}`"))?;
+      if op == "=" {
+        return Err("`=` compares nothing, use `==`".to_string());
+      }
+      tokens.push(Token::Op(op));
+      rest = &rest[op.len()..];
+    }
+    rest = rest.trim_start();
+  }
+  Ok(tokens)
+}
+
+/// A number, a percentage (`80%`) or a size (`10G`).
+fn parse_number(word: &str) -> Result<f64, String> {
+  let number = word.strip_suffix('%').unwrap_or(word);
+  if let Ok(number) = number.parse() {
+    return Ok(number);
+  }
+  parse_size(word)
+    .map(|bytes| bytes as f64)
+    .map_err(|error| format!("`{word}`: {error}"))
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Operand {
+  Field(Field),
+  Number(f64),
+  Text(String),
+}
+
+impl Operand {
+  fn is_number(&self) -> bool {
+    match self {
+      Self::Field(field) => matches!(
+        field,
+        Field::Size | Field::Used | Field::Avail | Field::Pcent
+      ),
+      Self::Number(_) => true,
+      Self::Text(_) => false,
+    }
+  }
+
+  /// Value of the operand for `record`, none when its number is unknown.
+  fn number(&self, record: &Record) -> Option<f64> {
+    match self {
+      Self::Field(Field::Pcent) => record.pcent,
+      Self::Field(field) => Some(field.size(record)?? as f64),
+      Self::Number(number) => Some(*number),
+      Self::Text(_) => None,
+    }
+  }
+
+  fn text<'a>(&'a self, record: &'a Record) -> &'a str {
+    match self {
+      Self::Field(Field::Source) => &record.source,
+      Self::Field(Field::Alias) => record.alias.as_deref().unwrap_or_default(),
+      Self::Field(Field::Label) => record.alias.as_deref().unwrap_or(&record.source),
+      Self::Field(Field::FsType) => &record.fstype,
+      Self::Field(Field::Target) => &record.target,
+      Self::Text(text) => text,
+      Self::Field(_) | Self::Number(_) => "",
+    }
+  }
+}
+
+/// A parsed `--where` expression.
+#[derive(Debug, Clone)]
+pub(crate) enum Expr {
+  And(Box<Expr>, Box<Expr>),
+  Or(Box<Expr>, Box<Expr>),
+  Not(Box<Expr>),
+  Compare(Operand, &'static str, Operand),
+  Matches(Operand, Regex),
+}
+
+impl Expr {
+  /// Whether `record` satisfies the expression. Comparisons with an unknown size are false.
+  pub(crate) fn matches(&self, record: &Record) -> bool {
+    match self {
+      Self::And(left, right) => left.matches(record) && right.matches(record),
+      Self::Or(left, right) => left.matches(record) || right.matches(record),
+      Self::Not(expr) => !expr.matches(record),
+      Self::Matches(operand, regex) => regex.is_match(operand.text(record)),
+      Self::Compare(left, op, right) => {
+        let order = if left.is_number() {
+          let (Some(left), Some(right)) = (left.number(record), right.number(record)) else {
+            return false;
+          };
+          left.partial_cmp(&right)
+        } else {
+          Some(left.text(record).cmp(right.text(record)))
+        };
+        let Some(order) = order else { return false };
+        match *op {
+          "==" => order.is_eq(),
+          "!=" => order.is_ne(),
+          "<" => order.is_lt(),
+          "<=" => order.is_le(),
+          ">" => order.is_gt(),
+          _ => order.is_ge(),
+        }
+      }
+    }
+  }
+}
+
+struct Parser<'a> {
+  tokens: Vec<Token>,
+  pos: usize,
+  named: &'a HashMap<String, String>,
+  /// Named filters being expanded, to catch the ones referring to themselves.
+  expanding: Vec<String>,
+}
+
+impl Parser<'_> {
+  fn peek(&self) -> Option<&Token> {
+    self.tokens.get(self.pos)
+  }
+
+  fn eat(&mut self, op: &str) -> bool {
+    let found = matches!(self.peek(), Some(Token::Op(found)) if *found == op);
+    if found {
+      self.pos += 1;
+    }
+    found
+  }
+
+  fn next(&mut self) -> Result<Token, String> {
+    let token = self
+      .peek()
+      .cloned()
+      .ok_or_else(|| "unexpected end of the expression".to_string())?;
+    self.pos += 1;
+    Ok(token)
+  }
+
+  fn or(&mut self) -> Result<Expr, String> {
+    let mut expr = self.and()?;
+    while self.eat("||") {
+      expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+    }
+    Ok(expr)
+  }
+
+  fn and(&mut self) -> Result<Expr, String> {
+    let mut expr = self.not()?;
+    while self.eat("&&") {
+      expr = Expr::And(Box::new(expr), Box::new(self.not()?));
+    }
+    Ok(expr)
+  }
+
+  fn not(&mut self) -> Result<Expr, String> {
+    if self.eat("!") {
+      return Ok(Expr::Not(Box::new(self.not()?)));
+    }
+    if self.eat("(") {
+      let expr = self.or()?;
+      if !self.eat(")") {
+        return Err("missing `)`".to_string());
+      }
+      return Ok(expr);
+    }
+    if let Some(Token::Ident(name)) = self.peek() {
+      if Field::parse(name).is_none() {
+        let name = name.clone();
+        self.pos += 1;
+        return self.named(&name);
+      }
+    }
+    self.comparison()
+  }
+
+  /// Expression of the named filter `name`.
+  fn named(&mut self, name: &str) -> Result<Expr, String> {
+    let source = self
+      .named
+      .get(name)
+      .ok_or_else(|| format!("unknown field or filter `{name}`"))?;
+    if self.expanding.iter().any(|expanding| expanding == name) {
+      return Err(format!("the filter `{name}` refers to itself"));
+    }
+    let mut parser = Parser {
+      tokens: tokenize(source)?,
+      pos: 0,
+      named: self.named,
+      expanding: [self.expanding.as_slice(), &[name.to_string()]].concat(),
+    };
+    parser
+      .all()
+      .map_err(|error| format!("filter `{name}`: {error}"))
+  }
+
+  fn comparison(&mut self) -> Result<Expr, String> {
+    let left = self.operand()?;
+    let op = match self.next()? {
+      Token::Op(op) if ["==", "!=", "<", "<=", ">", ">=", "=~"].contains(&op) => op,
+      token => return Err(format!("expected a comparison, found {}", describe(&token))),
+    };
+    let right = self.operand()?;
+    if op == "=~" {
+      let (Operand::Field(_), Operand::Text(pattern)) = (&left, &right) else {
+        return Err("`=~` matches a field with a quoted regular expression".to_string());
+      };
+      if left.is_number() {
+        return Err("`=~` applies to the text fields".to_string());
+      }
+      let regex = Regex::new(pattern).map_err(|error| error.to_string())?;
+      return Ok(Expr::Matches(left, regex));
+    }
+    if left.is_number() != right.is_number() {
+      return Err(format!("`{op}` compares a number with a text"));
+    }
+    Ok(Expr::Compare(left, op, right))
+  }
+
+  fn operand(&mut self) -> Result<Operand, String> {
+    match self.next()? {
+      Token::Ident(name) => Field::parse(&name)
+        .map(Operand::Field)
+        .ok_or_else(|| format!("unknown field `{name}`")),
+      Token::Number(number) => Ok(Operand::Number(number)),
+      Token::Text(text) => Ok(Operand::Text(text)),
+      token => Err(format!(
+        "expected a field or a value, found {}",
+        describe(&token)
+      )),
+    }
+  }
+
+  fn all(&mut self) -> Result<Expr, String> {
+    let expr = self.or()?;
+    match self.peek() {
+      Some(token) => Err(format!("unexpected {}", describe(token))),
+      None => Ok(expr),
+    }
+  }
+}
+
+fn describe(token: &Token) -> String {
+  match token {
+    Token::Ident(name) => format!("`{name}`"),
+    Token::Number(number) => format!("`{number}`"),
+    Token::Text(text) => format!("\"{text}\""),
+    Token::Op(op) => format!("`{op}`"),
+  }
+}
+
+/// Parse `source`, where the names of `named` stand for their expressions.
+pub(crate) fn parse(source: &str, named: &HashMap<String, String>) -> Result<Expr, String> {
+  Parser {
+    tokens: tokenize(source)?,
+    pos: 0,
+    named,
+    expanding: vec![],
+  }
+  .all()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn record(fstype: &str, size: Option<u64>, pcent: f64) -> Record {
+    Record {
+      source: "/dev/sda1".to_string(),
+      fstype: fstype.to_string(),
+      size,
+      pcent: Some(pcent),
+      target: "/home".to_string(),
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn test_matches() {
+    let named = HashMap::from([
+      ("full".to_string(), "pcent >= 90%".to_string()),
+      ("big_full".to_string(), "full && size > 1G".to_string()),
+      ("loop".to_string(), "!loop".to_string()),
+    ]);
+    let eval = |source: &str, record: &Record| parse(source, &named).unwrap().matches(record);
+    let ext4 = record("ext4", Some(2 << 30), 91.0);
+    let xfs = record("xfs", None, 50.0);
+    assert!(eval(r#"pcent > 80 && fstype == "ext4""#, &ext4));
+    assert!(!eval(r#"pcent > 80 && fstype == "ext4""#, &xfs));
+    assert!(eval("fstype == 'xfs' || (full)", &ext4));
+    assert!(eval("!full", &xfs));
+    assert!(eval("big_full", &ext4));
+    assert!(!eval("size > 0 || size <= 0", &xfs));
+    assert!(eval(r#"target =~ "^/ho" && label != "x""#, &ext4));
+
+    assert_eq!(
+      parse("pcent > 80 &&", &named).unwrap_err(),
+      "unexpected end of the expression"
+    );
+    assert_eq!(
+      parse("fstype > 10", &named).unwrap_err(),
+      "`>` compares a number with a text"
+    );
+    assert_eq!(
+      parse("fstype = 'ext4'", &named).unwrap_err(),
+      "`=` compares nothing, use `==`"
+    );
+    assert_eq!(
+      parse("large", &named).unwrap_err(),
+      "unknown field or filter `large`"
+    );
+    assert_eq!(
+      parse("loop", &named).unwrap_err(),
+      "filter `loop`: the filter `loop` refers to itself"
+    );
+  }
+}
@@ -8,11 +8,15 @@
 //! filesystem mounted at a particular directory. It also includes
 //! information on amount of space available and amount of space used.
 // spell-checker:ignore canonicalized
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 #[cfg(unix)]
 use crate::fsext::statfs;
-use crate::fsext::{FsUsage, MountInfo};
+use crate::{
+  anonymize::stable_hash,
+  fsext::{FsUsage, MountInfo},
+  problems,
+};
 
 /// Summary representation of a filesystem.
 ///
@@ -56,7 +60,7 @@ where
   // TODO Refactor this function with `Stater::find_mount_point()`
   // in the `stat` crate.
   let path = if canonicalize {
-    path.as_ref().canonicalize().ok()?
+    normalize(path.as_ref().canonicalize().ok()?)
   } else {
     path.as_ref().to_path_buf()
   };
@@ -83,6 +87,19 @@ where
   })
 }
 
+/// Drop the `\\?\` verbatim prefix Windows adds to canonicalized paths, which the mount
+/// directories don't have.
+fn normalize(path: PathBuf) -> PathBuf {
+  let text = path.to_string_lossy();
+  if let Some(share) = text.strip_prefix(r"\\?\UNC\") {
+    PathBuf::from(format!(r"\\{share}"))
+  } else if let Some(local) = text.strip_prefix(r"\\?\") {
+    PathBuf::from(local)
+  } else {
+    path
+  }
+}
+
 impl Filesystem {
   // TODO: resolve uuid in `mount_info.dev_name` if exists
   pub(crate) fn new(mount_info: MountInfo, _file: Option<String>) -> Option<Self> {
@@ -100,12 +117,86 @@ impl Filesystem {
       mount_info.mount_dir.clone()
     };
     #[cfg(unix)]
-    let usage = FsUsage::new(statfs(_stat_path).ok()?);
+    let usage = FsUsage::new(
+      statfs(_stat_path.clone())
+        .map_err(|error| problems::report(format!("{_stat_path}: {error}")))
+        .ok()?,
+    );
     #[cfg(windows)]
     let usage = FsUsage::new(Path::new(&_stat_path)).ok()?;
     Some(Self { mount_info, usage })
   }
 
+  /// Total size in bytes.
+  pub(crate) fn size(&self) -> Option<u64> {
+    self.usage.bytes(self.usage.blocks)
+  }
+
+  /// Used space in bytes.
+  pub(crate) fn used(&self) -> Option<u64> {
+    self
+      .usage
+      .bytes(self.usage.blocks - self.usage.free_blocks())
+  }
+
+  /// Space available to unprivileged users in bytes.
+  pub(crate) fn avail(&self) -> Option<u64> {
+    self.usage.bytes(self.usage.avail_blocks())
+  }
+
+  /// Free space only root can use in bytes.
+  pub(crate) fn reserved(&self) -> Option<u64> {
+    self
+      .usage
+      .bytes(self.usage.free_blocks() - self.usage.avail_blocks())
+  }
+
+  /// Ratio of the space usable by unprivileged users that is used, unknown when the usage is
+  /// suspicious. Above 1 when root overran the reservation.
+  pub(crate) fn used_ratio(&self) -> Option<f64> {
+    if self.usage.is_suspicious() {
+      return None;
+    }
+    let bused = self.usage.blocks - self.usage.free_blocks();
+    match (bused + self.usage.avail_blocks()).saturating_sub(self.usage.overrun_blocks()) {
+      // Pseudo filesystems have no blocks at all.
+      0 => Some(0.0),
+      usable => Some(bused as f64 / usable as f64),
+    }
+  }
+
+  /// Identifier that stays the same across reboots: `uuid:UUID` when the device has a filesystem
+  /// UUID, which also follows the filesystem mounted elsewhere, `name:HASH` of the device name and
+  /// the mount directory otherwise, and `dev:MAJOR:MINOR` when there is no device name. Device
+  /// numbers change across reboots, the pseudo filesystems get new ones at every boot. Followed by
+  /// `/HASH` of the mounted directory when a subdirectory is mounted (bind mounts, btrfs
+  /// subvolumes). The UUID is the one filled when the mounts were collected.
+  pub(crate) fn id(&self) -> String {
+    let mount_info = &self.mount_info;
+    let uuid = Some(mount_info.uuid.clone())
+      .filter(|uuid| !uuid.is_empty())
+      .or_else(|| {
+        mount_info
+          .dev_name
+          .strip_prefix("UUID=")
+          .map(str::to_lowercase)
+      });
+    let device = match uuid {
+      Some(uuid) => format!("uuid:{uuid}"),
+      None if mount_info.dev_name.is_empty() && !mount_info.dev_id.is_empty() => {
+        format!("dev:{}", mount_info.dev_id)
+      }
+      None => format!(
+        "name:{}",
+        stable_hash(&format!("{} {}", mount_info.dev_name, mount_info.mount_dir))
+      ),
+    };
+    match mount_info.mount_root.as_str() {
+      "" | "/" => device,
+      root => format!("{device}/{}", stable_hash(root)),
+    }
+  }
+
   /// Find and create the filesystem that best matches a given path.
   ///
   /// This function returns a new `Filesystem` derived from the
@@ -138,6 +229,57 @@ impl Filesystem {
 #[cfg(test)]
 mod tests {
 
+  #[test]
+  fn test_id() {
+    use super::Filesystem;
+    use crate::fsext::{FsUsage, MountInfo};
+    let mounted = |dev_name: &str, dev_id: &str, mount_dir: &str, mount_root: &str| Filesystem {
+      mount_info: MountInfo {
+        dev_id: dev_id.to_string(),
+        dev_name: dev_name.to_string(),
+        mount_dir: mount_dir.to_string(),
+        mount_root: mount_root.to_string(),
+        ..Default::default()
+      },
+      usage: FsUsage {
+        blocksize: 4096,
+        blocks: 0,
+        bfree: 0,
+        bavail: 0,
+        files: 0,
+        ffree: 0,
+        raw: None,
+      },
+    };
+    let filesystem =
+      |dev_name: &str, dev_id: &str, mount_root: &str| mounted(dev_name, dev_id, "/mnt", mount_root);
+    assert_eq!(filesystem("UUID=AB-CD", "8:1", "/").id(), "uuid:ab-cd");
+    // The device number of a pseudo filesystem changes at the next boot, not its id.
+    let tmpfs = filesystem("tmpfs", "0:24", "/").id();
+    assert!(tmpfs.starts_with("name:"));
+    assert_eq!(tmpfs, filesystem("tmpfs", "0:25", "/").id());
+    assert_ne!(tmpfs, mounted("tmpfs", "0:24", "/tmp", "/").id());
+    assert_eq!(filesystem("", "0:24", "/").id(), "dev:0:24");
+    let bind = filesystem("tmpfs", "0:24", "/sub").id();
+    assert!(bind.starts_with(&format!("{tmpfs}/")));
+    assert_ne!(bind, filesystem("tmpfs", "0:24", "/other").id());
+  }
+
+  #[test]
+  fn test_normalize() {
+    use super::normalize;
+    use std::path::PathBuf;
+    assert_eq!(
+      normalize(PathBuf::from(r"\\?\C:\Data")),
+      PathBuf::from(r"C:\Data")
+    );
+    assert_eq!(
+      normalize(PathBuf::from(r"\\?\UNC\server\share")),
+      PathBuf::from(r"\\server\share")
+    );
+    assert_eq!(normalize(PathBuf::from("/home")), PathBuf::from("/home"));
+  }
+
   mod mount_info_from_path {
 
     use crate::filesystem::mount_info_from_path;
@@ -146,9 +288,8 @@ mod tests {
     // Create a fake `MountInfo` with the given directory name.
     fn mount_info(mount_dir: &str) -> MountInfo {
       MountInfo {
-        dev_name: String::default(),
-        fs_type: String::default(),
         mount_dir: String::from(mount_dir),
+        ..Default::default()
       }
     }
 
@@ -10,12 +10,14 @@
 #[cfg(any(target_os = "linux", target_os = "android"))]
 const LINUX_MTAB: &str = "/etc/mtab";
 #[cfg(any(target_os = "linux", target_os = "android"))]
-const LINUX_MOUNTINFO: &str = "/proc/self/mountinfo";
+pub(crate) const LINUX_MOUNTINFO: &str = "/proc/self/mountinfo";
 #[cfg(windows)]
 const MAX_PATH: usize = 266;
 #[cfg(windows)]
 static EXIT_ERR: i32 = 1;
 
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use crate::problems;
 #[cfg(any(
   windows,
   target_os = "freebsd",
@@ -27,6 +29,7 @@ static EXIT_ERR: i32 = 1;
 use crate::show_warning;
 
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 #[cfg(windows)]
 use std::ffi::OsStr;
 #[cfg(windows)]
@@ -58,6 +61,7 @@ fn to_nul_terminated_wide_string(s: impl AsRef<OsStr>) -> Vec<u16> {
 
 #[cfg(unix)]
 use libc::strerror;
+use std::collections::HashMap;
 #[cfg(unix)]
 use std::ffi::CStr;
 #[cfg(unix)]
@@ -67,6 +71,9 @@ use std::io::Error as IOError;
 use std::mem;
 #[cfg(windows)]
 use std::path::Path;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::time::Duration;
+use std::time::SystemTime;
 
 #[cfg(any(
   target_os = "linux",
@@ -104,20 +111,36 @@ pub use libc::statfs as statfs_fn;
 ))]
 pub use libc::statvfs as statfs_fn;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct MountInfo {
+  /// `major:minor` device number on Linux, empty when the mount table doesn't have it
+  pub dev_id: String,
   /// Stores `volume_name` in windows platform and `dev_id` in unix platform
   pub dev_name: String,
   pub fs_type: String,
+  /// Directory of the filesystem mounted at `mount_dir`, `/` unless a subdirectory is mounted
+  pub mount_root: String,
   pub mount_dir: String,
+  /// Comma separated options of the mount, like `rw,nosuid,relatime`
+  pub mount_option: String,
+  /// Filesystem UUID of the device, empty until [`MountInfo::fill_uuid`] or when it has none
+  pub uuid: String,
+  /// Model and kind of the disk under the device, like `Samsung SSD 980 (NVMe)`, empty until
+  /// filled from sysfs or when there is no disk
+  pub disk: String,
+  /// When the filesystem was mounted, none until [`MountInfo::fill_mounted`] or when unknown
+  pub mounted: Option<SystemTime>,
 }
 
 impl MountInfo {
   #[cfg(any(target_os = "linux", target_os = "android"))]
   fn new(file_name: &str, raw: &[&str]) -> Option<Self> {
+    let mut dev_id = String::new();
     let dev_name;
     let fs_type;
+    let mut mount_root = String::new();
     let mount_dir;
+    let mount_option;
 
     match file_name {
       // spell-checker:ignore (word) noatime
@@ -126,23 +149,47 @@ impl MountInfo {
       LINUX_MOUNTINFO => {
         const FIELDS_OFFSET: usize = 6;
         let after_fields =
-          raw[FIELDS_OFFSET..].iter().position(|c| *c == "-").unwrap() + FIELDS_OFFSET + 1;
+          raw.get(FIELDS_OFFSET..)?.iter().position(|c| *c == "-")? + FIELDS_OFFSET + 1;
+        if raw.len() < after_fields + 2 {
+          return None;
+        }
+        dev_id = raw[2].to_string();
         dev_name = raw[after_fields + 1].to_string();
         fs_type = raw[after_fields].to_string();
+        mount_root = raw[3].to_string();
         mount_dir = raw[4].to_string();
+        // The options of the mount, then the ones of the superblock it doesn't repeat, like
+        // `findmnt -o OPTIONS`. The `ro` or `rw` of the mount is the one that applies.
+        let mut options: Vec<&str> = raw[5].split(',').collect();
+        for option in raw.get(after_fields + 2).unwrap_or(&"").split(',') {
+          if !["", "ro", "rw"].contains(&option) && !options.contains(&option) {
+            options.push(option);
+          }
+        }
+        mount_option = options.join(",");
       }
       LINUX_MTAB => {
+        if raw.len() < 4 {
+          return None;
+        }
         dev_name = raw[0].to_string();
         fs_type = raw[2].to_string();
         mount_dir = raw[1].to_string();
+        mount_option = raw[3].to_string();
       }
       _ => return None,
     };
 
     Some(Self {
+      dev_id,
       dev_name,
       fs_type,
+      mount_root,
       mount_dir,
+      mount_option,
+      uuid: String::new(),
+      disk: String::new(),
+      mounted: None,
     })
   }
 
@@ -212,6 +259,9 @@ impl MountInfo {
       mount_root,
       mount_dir: String::new(),
       mount_option: String::new(),
+      uuid: String::new(),
+      disk: String::new(),
+      mounted: None,
       remote,
       dummy: false,
     })
@@ -246,7 +296,7 @@ impl From<StatFs> for MountInfo {
     };
 
     let dev_id = mount_dev_id(&mount_dir);
-    let dummy = is_dummy_filesystem(&fs_type, "");
+    let dummy = is_dummy_filesystem(&fs_type, "", DUMMY_FILESYSTEMS);
     let remote = is_remote_filesystem(&dev_name, &fs_type);
 
     Self {
@@ -256,6 +306,9 @@ impl From<StatFs> for MountInfo {
       mount_dir,
       mount_root: String::new(),
       mount_option: String::new(),
+      uuid: String::new(),
+      disk: String::new(),
+      mounted: None,
       remote,
       dummy,
     }
@@ -325,24 +378,249 @@ use std::ptr;
 ))]
 use std::slice;
 
-/// Read file system list.
-pub fn read_fs_list() -> Result<Vec<MountInfo>> {
+/// Parse a mount table in the `/proc/self/mountinfo` format.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn parse_mountinfo<R: BufRead>(reader: R) -> Vec<MountInfo> {
+  parse_mount_table(LINUX_MOUNTINFO, reader).collect()
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn parse_mount_table<R: BufRead>(
+  file_name: &'static str,
+  reader: R,
+) -> impl Iterator<Item = MountInfo> {
+  reader
+    .lines()
+    .map_while(move |line| {
+      line
+        .map_err(|error| problems::report(format!("{file_name}: {error}")))
+        .ok()
+    })
+    .enumerate()
+    .filter_map(move |(i, line)| {
+      let raw_data = line.split_whitespace().collect::<Vec<&str>>();
+      let mount_info = MountInfo::new(file_name, &raw_data);
+      if mount_info.is_none() {
+        problems::report(format!("{file_name}:{}: unreadable line", i + 1));
+      }
+      mount_info
+    })
+}
+
+/// Iterate the file system list. On Linux the mount table is parsed line by line as the
+/// iterator advances, so callers can stop early and start printing before the end of the table.
+pub fn mounts() -> Result<Box<dyn Iterator<Item = MountInfo>>> {
   #[cfg(any(target_os = "linux", target_os = "android"))]
   {
     let (file_name, f) = File::open(LINUX_MOUNTINFO)
       .map(|f| (LINUX_MOUNTINFO, f))
       .or_else(|_| File::open(LINUX_MTAB).map(|f| (LINUX_MTAB, f)))?;
-    let reader = BufReader::new(f);
-    Ok(
-      reader
-        .lines()
-        .map_while(Result::ok)
-        .filter_map(|line| {
-          let raw_data = line.split_whitespace().collect::<Vec<&str>>();
-          MountInfo::new(file_name, &raw_data)
-        })
-        .collect::<Vec<_>>(),
-    )
+    Ok(Box::new(parse_mount_table(file_name, BufReader::new(f))))
+  }
+  #[cfg(not(any(target_os = "linux", target_os = "android")))]
+  {
+    Ok(Box::new(read_fs_list()?.into_iter()))
+  }
+}
+
+/// Filesystems whose root directory is created when they are mounted.
+pub(crate) const IN_MEMORY_FILESYSTEMS: &[&str] =
+  &["tmpfs", "ramfs", "devtmpfs", "autofs", "devpts", "mqueue"];
+
+/// Age of the mount at `mount_dir` in the `age:` line of `/proc/self/mountstats`, only written for
+/// NFS mounts.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn mount_age(stats: &str, mount_dir: &str) -> Option<Duration> {
+  let mut lines = stats.lines();
+  let header = format!(" mounted on {mount_dir} with fstype ");
+  lines.find(|line| line.starts_with("device ") && line.contains(&header))?;
+  lines
+    .take_while(|line| !line.starts_with("device "))
+    .find_map(|line| line.trim().strip_prefix("age:"))
+    .and_then(|age| age.trim().parse().ok())
+    .map(Duration::from_secs)
+}
+
+/// Filesystem types holding no user data, or whose usage is that of another filesystem: kernel
+/// interfaces, read-only images like the snap squashfs loops and the stacked filesystems.
+pub(crate) const DUMMY_FILESYSTEMS: &[&str] = &[
+  "autofs",
+  "proc",
+  "subfs",
+  // for Linux 2.6/3.x
+  "debugfs",
+  "devpts",
+  "fusectl",
+  "mqueue",
+  "rpc_pipefs",
+  "sysfs",
+  // FreeBSD, Linux 2.4
+  "devfs",
+  // for NetBSD 3.0
+  "kernfs",
+  // for Irix 6.5
+  "ignore",
+  // Snaps and live images, always 100% used.
+  "squashfs",
+  // Containers, the usage is the one of the filesystem below.
+  "overlay",
+  "fuse.portal",
+];
+
+/// Bind mount option of the `none` filesystems that are not dummy.
+const MOUNT_OPT_BIND: &str = "bind";
+
+/// Whether `fs_type` is one of the dummy filesystems of `dummies`, or a `none` mount other than a
+/// bind mount.
+pub(crate) fn is_dummy_filesystem(
+  fs_type: &str,
+  mount_option: &str,
+  dummies: &[impl AsRef<str>],
+) -> bool {
+  dummies.iter().any(|dummy| dummy.as_ref() == fs_type)
+    || (fs_type == "none" && !mount_option.contains(MOUNT_OPT_BIND))
+}
+
+/// Filesystem UUID of a device, given as `UUID=` or found in `/dev/disk/by-uuid`.
+pub(crate) fn device_uuid(dev_name: &str) -> Option<String> {
+  if let Some(uuid) = dev_name.strip_prefix("UUID=") {
+    return Some(uuid.to_lowercase());
+  }
+  if !dev_name.starts_with("/dev/") {
+    return None;
+  }
+  let device = std::path::Path::new(dev_name).canonicalize().ok()?;
+  std::fs::read_dir("/dev/disk/by-uuid")
+    .ok()?
+    .map_while(Result::ok)
+    .find(|entry| {
+      entry
+        .path()
+        .canonicalize()
+        .is_ok_and(|target| target == device)
+    })
+    .map(|entry| entry.file_name().to_string_lossy().to_lowercase())
+}
+
+impl MountInfo {
+  /// Fill the filesystem UUID of the device, left empty when it has none.
+  pub(crate) fn fill_uuid(&mut self) {
+    if self.uuid.is_empty() {
+      self.uuid = device_uuid(&self.dev_name).unwrap_or_default();
+    }
+  }
+
+  /// Whether the mount has the option `name`, alone or with a value (`uid` for `uid=1000`).
+  pub(crate) fn has_option(&self, name: &str) -> bool {
+    self
+      .mount_option
+      .split(',')
+      .any(|option| option == name || option.split_once('=').is_some_and(|(key, _)| key == name))
+  }
+
+  /// Fill the mount time, when Linux tells it: the age of an NFS mount in
+  /// `/proc/self/mountstats`, or the creation of the root directory of a filesystem living in
+  /// memory, created with the mount. Disk filesystems keep their root from `mkfs`, their mount
+  /// time stays unknown.
+  pub(crate) fn fill_mounted(&mut self) {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    if self.mounted.is_none() {
+      self.mounted = if self.fs_type.starts_with("nfs") {
+        std::fs::read_to_string("/proc/self/mountstats")
+          .ok()
+          .and_then(|stats| mount_age(&stats, &self.mount_dir))
+          .and_then(|age| SystemTime::now().checked_sub(age))
+      } else if IN_MEMORY_FILESYSTEMS.contains(&self.fs_type.as_str()) && self.mount_root == "/" {
+        std::fs::metadata(&self.mount_dir)
+          .and_then(|metadata| metadata.created())
+          .ok()
+      } else {
+        None
+      };
+    }
+  }
+
+  /// Fill a missing `dev_id` with the `st_dev` of the mount directory, as `major:minor` on Linux.
+  pub(crate) fn fill_dev_id(&mut self) {
+    #[cfg(unix)]
+    if self.dev_id.is_empty() {
+      use std::os::unix::fs::MetadataExt;
+      if let Ok(metadata) = std::fs::metadata(&self.mount_dir) {
+        let dev = metadata.dev();
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+          self.dev_id = format!("{}:{}", libc::major(dev), libc::minor(dev));
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        {
+          self.dev_id = dev.to_string();
+        }
+      }
+    }
+  }
+}
+
+/// Keep one entry per device like GNU df: bind mounts and btrfs subvolumes share the device of
+/// their filesystem, which is listed once at its shortest mount directory. Entries without a
+/// `dev_id` are all kept.
+pub(crate) fn dedupe<T>(items: Vec<T>, mount_info: impl Fn(&T) -> &MountInfo) -> Vec<T> {
+  let mut kept: HashMap<String, usize> = HashMap::new();
+  let mut keep = vec![true; items.len()];
+  for (i, item) in items.iter().enumerate() {
+    let info = mount_info(item);
+    if info.dev_id.is_empty() {
+      continue;
+    }
+    match kept.get(&info.dev_id) {
+      Some(&other) if mount_info(&items[other]).mount_dir.len() <= info.mount_dir.len() => {
+        keep[i] = false;
+      }
+      Some(&other) => {
+        keep[other] = false;
+        kept.insert(info.dev_id.clone(), i);
+      }
+      None => {
+        kept.insert(info.dev_id.clone(), i);
+      }
+    }
+  }
+  items
+    .into_iter()
+    .zip(keep)
+    .filter_map(|(item, keep)| keep.then_some(item))
+    .collect()
+}
+
+/// Source of the mount table on this platform, the table file in use on Linux.
+pub(crate) fn mount_backend() -> &'static str {
+  #[cfg(any(target_os = "linux", target_os = "android"))]
+  {
+    if std::path::Path::new(LINUX_MOUNTINFO).exists() {
+      LINUX_MOUNTINFO
+    } else {
+      LINUX_MTAB
+    }
+  }
+  #[cfg(any(
+    target_os = "freebsd",
+    target_vendor = "apple",
+    target_os = "netbsd",
+    target_os = "openbsd"
+  ))]
+  {
+    "getmntinfo"
+  }
+  #[cfg(windows)]
+  {
+    "FindFirstVolumeW"
+  }
+}
+
+/// Read file system list.
+pub fn read_fs_list() -> Result<Vec<MountInfo>> {
+  #[cfg(any(target_os = "linux", target_os = "android"))]
+  {
+    Ok(mounts()?.collect())
   }
   #[cfg(any(
     target_os = "freebsd",
@@ -419,15 +697,122 @@ pub fn read_fs_list() -> Result<Vec<MountInfo>> {
   }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FsUsage {
   pub blocksize: u64,
   pub blocks: u64,
   pub bfree: u64,
   pub bavail: u64,
+  /// Inodes, and free inodes. Zero when the filesystem doesn't report them.
+  #[serde(default)]
+  pub files: u64,
+  #[serde(default)]
+  pub ffree: u64,
+  /// Untouched `statvfs` fields, read for `--raw` only.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub raw: Option<RawStatfs>,
+}
+
+/// Fields of `statvfs` as returned by the system, for `--raw`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RawStatfs {
+  pub f_bsize: u64,
+  pub f_frsize: u64,
+  pub f_blocks: u64,
+  pub f_bfree: u64,
+  pub f_bavail: u64,
+  pub f_files: u64,
+  pub f_ffree: u64,
+  pub f_flag: u64,
+}
+
+impl RawStatfs {
+  /// `statvfs` of `path`, none when it fails.
+  #[cfg(unix)]
+  #[allow(clippy::useless_conversion)]
+  pub(crate) fn read(path: &str) -> Option<Self> {
+    let path = CString::new(path).ok()?;
+    let mut buffer: libc::statvfs = unsafe { mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut buffer) } != 0 {
+      return None;
+    }
+    // The field types vary between platforms.
+    Some(Self {
+      f_bsize: buffer.f_bsize as u64,
+      f_frsize: buffer.f_frsize as u64,
+      f_blocks: buffer.f_blocks.into(),
+      f_bfree: buffer.f_bfree.into(),
+      f_bavail: buffer.f_bavail.into(),
+      f_files: buffer.f_files.into(),
+      f_ffree: buffer.f_ffree.into(),
+      f_flag: buffer.f_flag as u64,
+    })
+  }
+
+  #[cfg(windows)]
+  pub(crate) fn read(_path: &str) -> Option<Self> {
+    None
+  }
 }
 
 impl FsUsage {
+  /// Whether `statfs` returned values that can't describe a real filesystem, like a zero block
+  /// size or more free blocks than blocks. Some fuse filesystems do.
+  pub fn is_suspicious(&self) -> bool {
+    self.blocksize == 0
+      || self.bfree > self.blocks
+      || (self.bavail > self.bfree && self.overrun_blocks() == 0)
+      || self.blocks.checked_mul(self.blocksize).is_none()
+  }
+
+  /// Blocks root used beyond the space of the other users, when the reservation is overrun. The
+  /// available blocks are then negative, wrapped around in `bavail`.
+  pub fn overrun_blocks(&self) -> u64 {
+    match self.bavail as i64 {
+      avail if avail < 0 && self.bfree <= self.blocks && avail.unsigned_abs() <= self.bfree => {
+        avail.unsigned_abs()
+      }
+      _ => 0,
+    }
+  }
+
+  /// Leave out the blocks only root can use, for the point of view of the other users.
+  pub fn without_reserved(&mut self) {
+    self.blocks -= self.free_blocks() - self.avail_blocks();
+    self.bfree = self.avail_blocks();
+  }
+
+  /// Free blocks, clamped to the number of blocks.
+  pub fn free_blocks(&self) -> u64 {
+    self.bfree.min(self.blocks)
+  }
+
+  /// Blocks available to unprivileged users, clamped to the free blocks, none when the reservation
+  /// is overrun.
+  pub fn avail_blocks(&self) -> u64 {
+    if self.overrun_blocks() > 0 {
+      0
+    } else {
+      self.bavail.min(self.free_blocks())
+    }
+  }
+
+  /// Ratio of the inodes that are used, unknown when the filesystem has no inode count.
+  pub fn inode_ratio(&self) -> Option<f64> {
+    match self.files {
+      0 => None,
+      files => Some((files - self.ffree.min(files)) as f64 / files as f64),
+    }
+  }
+
+  /// Size of `blocks` in bytes, unknown when the block size is zero or on overflow.
+  pub fn bytes(&self, blocks: u64) -> Option<u64> {
+    match self.blocksize {
+      0 => None,
+      blocksize => blocks.checked_mul(blocksize),
+    }
+  }
+
   #[cfg(unix)]
   pub fn new(statvfs: StatFs) -> Self {
     {
@@ -440,6 +825,9 @@ impl FsUsage {
         blocks: statvfs.f_blocks,
         bfree: statvfs.f_bfree,
         bavail: statvfs.f_bavail,
+        files: statvfs.f_files,
+        ffree: statvfs.f_ffree,
+        raw: None,
       };
       #[cfg(all(
         not(any(target_os = "freebsd", target_os = "openbsd")),
@@ -453,6 +841,7 @@ impl FsUsage {
         bavail_top_bit_set: ((statvfs.f_bavail as u64) & (1u64.rotate_right(1))) != 0,
         files: statvfs.f_files.into(),
         ffree: statvfs.f_ffree.into(),
+        raw: None,
       };
       #[cfg(target_os = "freebsd")]
       return Self {
@@ -465,6 +854,7 @@ impl FsUsage {
           != 0,
         files: statvfs.f_files,
         ffree: statvfs.f_ffree.try_into().unwrap(),
+        raw: None,
       };
       #[cfg(target_os = "openbsd")]
       return Self {
@@ -477,6 +867,7 @@ impl FsUsage {
           != 0,
         files: statvfs.f_files,
         ffree: statvfs.f_ffree,
+        raw: None,
       };
     }
   }
@@ -541,6 +932,7 @@ impl FsUsage {
       files: 0, // Not available on windows
       // Total number of free file nodes (inodes).
       ffree: 0, // Meaningless on Windows
+      raw: None,
     })
   }
 }
@@ -572,10 +964,59 @@ where
   }
 }
 
+/// Flush pending writes before statting, so free space accounts for them.
+///
+/// With `paths`, only the filesystems containing them are synced (`syncfs`), otherwise every
+/// filesystem is (`sync`).
+#[cfg(unix)]
+pub fn sync_filesystems(paths: &[String]) {
+  #[cfg(any(target_os = "linux", target_os = "android"))]
+  if !paths.is_empty() {
+    use std::os::fd::AsRawFd;
+    for path in paths {
+      if let Ok(file) = File::open(path) {
+        // SAFETY: the descriptor stays open for the duration of the call.
+        unsafe { libc::syncfs(file.as_raw_fd()) };
+      }
+    }
+    return;
+  }
+  #[cfg(not(any(target_os = "linux", target_os = "android")))]
+  let _ = paths;
+  // SAFETY: `sync` has no preconditions.
+  unsafe { libc::sync() };
+}
+
+/// Nothing to flush without `sync`, the statistics are read as they are.
+#[cfg(not(unix))]
+pub fn sync_filesystems(_paths: &[String]) {}
+
 #[cfg(test)]
 mod tests {
   use super::*;
 
+  #[test]
+  #[cfg(any(target_os = "linux", target_os = "android"))]
+  fn test_mount_age() {
+    let stats = "device proc mounted on /proc with fstype proc
+device nas:/export mounted on /mnt/nas with fstype nfs4 statvers=1.1
+\topts:\trw,vers=4.2
+\tage:\t3725
+device nas:/other mounted on /mnt/other with fstype nfs4 statvers=1.1
+\tage:\t12
+";
+    assert_eq!(
+      mount_age(stats, "/mnt/nas"),
+      Some(Duration::from_secs(3725))
+    );
+    assert_eq!(
+      mount_age(stats, "/mnt/other"),
+      Some(Duration::from_secs(12))
+    );
+    assert_eq!(mount_age(stats, "/proc"), None);
+    assert_eq!(mount_age(stats, "/mnt"), None);
+  }
+
   #[test]
   #[cfg(any(target_os = "linux", target_os = "android"))]
   fn test_mountinfo() {
@@ -591,6 +1032,7 @@ mod tests {
     assert_eq!(info.mount_dir, "/mnt");
     assert_eq!(info.fs_type, "xfs");
     assert_eq!(info.dev_name, "/dev/fs0");
+    assert_eq!(info.mount_option, "rw,relatime");
 
     // Test parsing with different amounts of optional fields.
     let info = MountInfo::new(
@@ -614,5 +1056,93 @@ mod tests {
 
     assert_eq!(info.fs_type, "xfs");
     assert_eq!(info.dev_name, "/dev/fs0");
+
+    let info = MountInfo::new(
+      LINUX_MOUNTINFO,
+      &"29 1 8:2 / / ro,nodev - ext4 /dev/sda2 rw,errors=remount-ro"
+        .split_ascii_whitespace()
+        .collect::<Vec<_>>(),
+    )
+    .unwrap();
+    assert_eq!(info.mount_option, "ro,nodev,errors=remount-ro");
+    assert!(info.has_option("ro") && info.has_option("errors"));
+    assert!(!info.has_option("remount-ro"));
+
+    // Truncated lines are skipped instead of panicking.
+    for line in [
+      "106 109 253:6 / /mnt rw,relatime",
+      "106 109 253:6 / /mnt rw - xfs",
+    ] {
+      let raw: Vec<_> = line.split_ascii_whitespace().collect();
+      assert!(MountInfo::new(LINUX_MOUNTINFO, &raw).is_none());
+    }
+  }
+
+  #[test]
+  fn test_dummy_filesystem() {
+    assert!(is_dummy_filesystem("squashfs", "ro", DUMMY_FILESYSTEMS));
+    assert!(is_dummy_filesystem("none", "rw", DUMMY_FILESYSTEMS));
+    assert!(!is_dummy_filesystem("none", "rw,bind", DUMMY_FILESYSTEMS));
+    assert!(!is_dummy_filesystem("ext4", "rw", DUMMY_FILESYSTEMS));
+    assert!(!is_dummy_filesystem("squashfs", "ro", &["proc"]));
+  }
+
+  #[test]
+  fn test_dedupe() {
+    let mount = |dev_id: &str, mount_dir: &str| MountInfo {
+      dev_id: dev_id.to_string(),
+      mount_dir: mount_dir.to_string(),
+      ..Default::default()
+    };
+    let mounts = vec![
+      mount("8:2", "/var/lib/docker/btrfs"),
+      mount("8:1", "/boot"),
+      mount("8:2", "/"),
+      mount("8:2", "/home"),
+      mount("", "/mnt/a"),
+      mount("", "/mnt/b"),
+    ];
+    let kept: Vec<_> = dedupe(mounts, |m| m)
+      .into_iter()
+      .map(|m| m.mount_dir)
+      .collect();
+    assert_eq!(kept, ["/boot", "/", "/mnt/a", "/mnt/b"]);
+  }
+
+  #[test]
+  fn test_degenerate_usage() {
+    let usage = |blocksize, blocks, bfree, bavail| FsUsage {
+      blocksize,
+      blocks,
+      bfree,
+      bavail,
+      files: 0,
+      ffree: 0,
+      raw: None,
+    };
+    assert!(!usage(4096, 100, 50, 40).is_suspicious());
+
+    let zero_bsize = usage(0, 100, 50, 40);
+    assert!(zero_bsize.is_suspicious());
+    assert_eq!(zero_bsize.bytes(100), None);
+
+    let too_free = usage(4096, 100, 150, u64::MAX);
+    assert!(too_free.is_suspicious());
+    assert_eq!(too_free.free_blocks(), 100);
+    assert_eq!(too_free.avail_blocks(), 100);
+
+    let overrun = usage(4096, 100, 4, -4i64 as u64);
+    assert!(!overrun.is_suspicious());
+    assert_eq!((overrun.overrun_blocks(), overrun.avail_blocks()), (4, 0));
+
+    assert!(usage(u64::MAX, 2, 0, 0).is_suspicious());
+    assert_eq!(usage(u64::MAX, 2, 0, 0).bytes(2), None);
+
+    let mut reserved = usage(4096, 100, 50, 40);
+    reserved.without_reserved();
+    assert_eq!(
+      (reserved.blocks, reserved.bfree, reserved.bavail),
+      (90, 40, 40)
+    );
   }
 }
@@ -0,0 +1,44 @@
+//! `ddf generate --out-dir DIR`: write the shell completions and the man pages to files, for
+//! packagers who would rather not capture `--completion` from stdout.
+
+use crate::args::App;
+use anyhow::{Context, Result};
+use clap::{CommandFactory, ValueEnum};
+use clap_complete::Shell;
+use std::{fs, path::Path};
+
+/// Write the completions of every shell to `out_dir/completions` and the man pages of `ddf` and of
+/// its subcommands to `out_dir/man`.
+pub(crate) fn run(out_dir: &Path) -> Result<()> {
+  let mut cmd = App::command();
+  let completions = out_dir.join("completions");
+  fs::create_dir_all(&completions).with_context(|| format!("creating {}", completions.display()))?;
+  for shell in Shell::value_variants() {
+    let path = clap_complete::generate_to(*shell, &mut cmd, "ddf", &completions)?;
+    eprintln!("{}", path.display());
+  }
+  let man = out_dir.join("man");
+  fs::create_dir_all(&man).with_context(|| format!("creating {}", man.display()))?;
+  clap_mangen::generate_to(cmd, &man)?;
+  eprintln!("{}", man.display());
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_generate() {
+    let dir = tempfile::tempdir().unwrap();
+    run(dir.path()).unwrap();
+    for file in [
+      "completions/ddf.bash",
+      "completions/_ddf",
+      "man/ddf.1",
+      "man/ddf-top.1",
+    ] {
+      assert!(dir.path().join(file).is_file(), "{file}");
+    }
+  }
+}
@@ -0,0 +1,112 @@
+//! `--graphics`: usage pies drawn with the kitty graphics protocol below the table.
+
+use crate::{
+  filesystem::Filesystem,
+  settings::{Settings, Severity},
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use std::io::{self, Write};
+
+/// Side of a pie in pixels, scaled by the terminal to two cells.
+const SIDE: usize = 32;
+/// Largest payload of one escape sequence allowed by the protocol.
+const CHUNK: usize = 4096;
+
+/// Whether the terminal described by the environment `var` speaks the kitty graphics protocol.
+pub(crate) fn supported(var: impl Fn(&str) -> Option<String>) -> bool {
+  var("KITTY_WINDOW_ID").is_some()
+    || var("TERM").is_some_and(|term| term == "xterm-kitty" || term == "xterm-ghostty")
+    || var("TERM_PROGRAM").is_some_and(|program| program == "WezTerm" || program == "ghostty")
+}
+
+/// RGBA pixels of a pie with `ratio` of its surface in `color`, clockwise from the top, and the
+/// rest in gray.
+fn pie(ratio: f64, color: [u8; 3]) -> Vec<u8> {
+  let center = SIDE as f64 / 2.0;
+  let mut pixels = Vec::with_capacity(SIDE * SIDE * 4);
+  for y in 0..SIDE {
+    for x in 0..SIDE {
+      let (dx, dy) = (x as f64 + 0.5 - center, y as f64 + 0.5 - center);
+      if dx.hypot(dy) > center {
+        pixels.extend([0, 0, 0, 0]);
+        continue;
+      }
+      // Fraction of the turn from the top, clockwise.
+      let turn = (dx.atan2(-dy) / std::f64::consts::TAU).rem_euclid(1.0);
+      let [r, g, b] = if turn < ratio { color } else { [96, 96, 96] };
+      pixels.extend([r, g, b, 255]);
+    }
+  }
+  pixels
+}
+
+/// Display `pixels`, a `SIDE` pixels square, over two cells at the cursor position.
+fn write_image(out: &mut impl Write, pixels: &[u8]) -> io::Result<()> {
+  let data = STANDARD.encode(pixels);
+  let chunks: Vec<&[u8]> = data.as_bytes().chunks(CHUNK).collect();
+  for (i, chunk) in chunks.iter().enumerate() {
+    let more = u8::from(i + 1 < chunks.len());
+    if i == 0 {
+      write!(
+        out,
+        "\x1b_Ga=T,f=32,s={SIDE},v={SIDE},c=2,r=1,q=2,m={more};"
+      )?;
+    } else {
+      write!(out, "\x1b_Gm={more};")?;
+    }
+    out.write_all(chunk)?;
+    write!(out, "\x1b\\")?;
+  }
+  Ok(())
+}
+
+/// Print a pie of the used space of every filesystem with its mount directory.
+pub(crate) fn write_pies(
+  filesystems: &[Filesystem],
+  settings: &Settings,
+  out: &mut impl Write,
+) -> io::Result<()> {
+  for fs in filesystems {
+    let ratio = fs.used_ratio().unwrap_or_default();
+    let color = match settings.severity(fs, ratio) {
+      Severity::High => [205, 49, 49],
+      Severity::Medium => [229, 229, 16],
+      Severity::Low => [13, 188, 121],
+    };
+    write_image(out, &pie(ratio, color))?;
+    writeln!(
+      out,
+      " {:>3.0}% {}",
+      100.0 * ratio,
+      settings.mount_label(&fs.mount_info)
+    )?;
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_pie() {
+    let pixels = pie(0.25, [255, 0, 0]);
+    let at = |x: usize, y: usize| &pixels[(y * SIDE + x) * 4..][..4];
+    assert_eq!(at(0, 0), [0, 0, 0, 0]);
+    // Top right quarter is used, the others are free.
+    assert_eq!(at(SIDE * 3 / 4, SIDE / 4), [255, 0, 0, 255]);
+    assert_eq!(at(SIDE / 4, SIDE / 4), [96, 96, 96, 255]);
+    assert_eq!(at(SIDE * 3 / 4, SIDE * 3 / 4), [96, 96, 96, 255]);
+  }
+
+  #[test]
+  fn test_write_image() {
+    let mut out = vec![];
+    write_image(&mut out, &pie(0.5, [0, 0, 255])).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    // 4096 bytes of pixels are 5464 bytes of base64, sent in two chunks.
+    assert!(out.starts_with("\x1b_Ga=T,f=32,s=32,v=32,c=2,r=1,q=2,m=1;"));
+    assert_eq!(out.matches("\x1b_Gm=0;").count(), 1);
+    assert!(out.ends_with("\x1b\\"));
+  }
+}
@@ -0,0 +1,245 @@
+//! Used space of the filesystems over the last runs, kept in the cache directory for the
+//! `change` column and the trend arrows: what changed since yesterday, from a plain `ddf`.
+
+use crate::{
+  compress::{self, Compression},
+  output::Report,
+  problems, settings,
+  units::SizeFormat,
+};
+use anyhow::{Context, Result};
+use std::{
+  collections::{HashMap, VecDeque},
+  fs,
+  path::{Path, PathBuf},
+  sync::OnceLock,
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::warn;
+
+/// File of the cache directory with the history, compressed as its extension says.
+const HISTORY_FILE: &str = "history.json.zst";
+
+/// Minimum time between two samples of a filesystem, and age of the sample the change is
+/// measured from: the change over a few seconds says nothing.
+const SAMPLE_EVERY: u64 = 3600;
+
+/// Age of the samples forgotten.
+const KEEP: u64 = 8 * 86400;
+
+/// Change of the used space, relative to the size, below which a filesystem is flat.
+const FLAT: f64 = 0.001;
+
+/// `(seconds since the epoch, used bytes)` samples of every filesystem by identifier, oldest
+/// first.
+type History = HashMap<String, VecDeque<(u64, u64)>>;
+
+fn path() -> Option<PathBuf> {
+  settings::cache_dir().map(|dir| dir.join(HISTORY_FILE))
+}
+
+fn now() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .unwrap_or_default()
+    .as_secs()
+}
+
+/// History of the file at `path`, empty when there is none yet.
+fn read(path: &Path) -> Result<History> {
+  if !path.exists() {
+    return Ok(History::default());
+  }
+  serde_json::from_reader(compress::open(path)?)
+    .with_context(|| format!("{}: invalid history", path.display()))
+}
+
+/// Write `history` to `path`, creating its directory.
+fn write(path: &Path, history: &History) -> Result<()> {
+  if let Some(dir) = path.parent() {
+    fs::create_dir_all(dir)?;
+  }
+  let mut writer = compress::create(path, Compression::from_path(path))?;
+  serde_json::to_writer(&mut writer, history)?;
+  writer.finish()
+}
+
+/// Report a history that can't be read or written.
+fn fail(error: anyhow::Error) {
+  warn!("{error:#}");
+  problems::report(format!("{error:#}"));
+}
+
+/// History read at the first use, before the current run is recorded. Empty when it can't be
+/// read.
+fn loaded() -> &'static History {
+  static HISTORY: OnceLock<History> = OnceLock::new();
+  HISTORY.get_or_init(|| {
+    path()
+      .map_or(Ok(History::default()), |path| read(&path))
+      .unwrap_or_else(|error| {
+        fail(error);
+        History::default()
+      })
+  })
+}
+
+/// Newest sample of `samples` taken at least `age` seconds before `now`.
+fn baseline(samples: &VecDeque<(u64, u64)>, now: u64, age: u64) -> Option<(u64, u64)> {
+  samples
+    .iter()
+    .rev()
+    .find(|(time, _)| now.saturating_sub(*time) >= age)
+    .copied()
+}
+
+/// Add the samples of `report` to `history`, at most one every [`SAMPLE_EVERY`], and forget the
+/// ones older than [`KEEP`].
+fn record(history: &mut History, report: &Report, now: u64) {
+  for record in &report.filesystems {
+    let Some(used) = record.used else { continue };
+    let samples = history.entry(record.id.clone()).or_default();
+    if samples
+      .back()
+      .is_none_or(|(time, _)| now.saturating_sub(*time) >= SAMPLE_EVERY)
+    {
+      samples.push_back((now, used));
+    }
+  }
+  for samples in history.values_mut() {
+    samples.retain(|(time, _)| now.saturating_sub(*time) < KEEP);
+  }
+  history.retain(|_, samples| !samples.is_empty());
+}
+
+/// Record the filesystems of `report` in the history file. A file that can't be read is left
+/// as it is, for its samples not to be lost.
+pub(crate) fn save(report: &Report) {
+  let Some(path) = path() else {
+    return;
+  };
+  let mut history = match read(&path) {
+    Ok(history) => history,
+    Err(error) => return fail(error),
+  };
+  record(&mut history, report, now());
+  if let Err(error) = write(&path, &history) {
+    fail(error.context(format!("{}: can't write the history", path.display())));
+  }
+}
+
+/// Elapsed time of the `change` column: minutes, hours up to two days, then days.
+fn format_elapsed(secs: u64) -> String {
+  match secs {
+    0..3600 => format!("{}m", secs / 60),
+    3600..172800 => format!("{}h", secs / 3600),
+    _ => format!("{}d", secs / 86400),
+  }
+}
+
+/// Change of the used space of the filesystem `id` since a previous run, like `+2.3GiB/24h`.
+/// None without a run at least an hour ago.
+pub(crate) fn change(id: &str, used: u64, size_format: &SizeFormat) -> Option<String> {
+  let now = now();
+  let (time, then) = baseline(loaded().get(id)?, now, SAMPLE_EVERY)?;
+  Some(format!(
+    "{}/{}",
+    size_format.format_signed(i128::from(used) - i128::from(then)),
+    format_elapsed(now - time)
+  ))
+}
+
+/// Direction of the used space of a filesystem since a previous sample.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Trend {
+  Up,
+  Down,
+  Flat,
+}
+
+impl Trend {
+  /// Trend from `then` to `used` bytes, of a filesystem of `size` bytes.
+  fn new(then: u64, used: u64, size: u64) -> Self {
+    let change = (used as f64 - then as f64) / size.max(1) as f64;
+    if change.abs() < FLAT {
+      Self::Flat
+    } else if change > 0.0 {
+      Self::Up
+    } else {
+      Self::Down
+    }
+  }
+
+  pub(crate) fn arrow(self) -> char {
+    match self {
+      Self::Up => '↑',
+      Self::Down => '↓',
+      Self::Flat => '→',
+    }
+  }
+}
+
+/// Trend of the filesystem `id` since the latest sample at least `window` old, at least an hour.
+pub(crate) fn trend(id: &str, used: u64, size: u64, window: Duration) -> Option<Trend> {
+  let age = window.as_secs().max(SAMPLE_EVERY);
+  let (_, then) = baseline(loaded().get(id)?, now(), age)?;
+  Some(Trend::new(then, used, size))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{
+    output::{Record, Sample},
+    settings::Settings,
+  };
+
+  #[test]
+  fn test_record() {
+    let report = |used: u64| Report {
+      sample: Sample::now(&Settings::default()),
+      filesystems: vec![Record {
+        id: "uuid:1234".to_string(),
+        used: Some(used),
+        ..Default::default()
+      }],
+    };
+    let mut history = History::from([("gone".to_string(), VecDeque::from([(0, 10)]))]);
+    let day = 86400;
+    record(&mut history, &report(100), 10 * day);
+    assert!(!history.contains_key("gone"));
+    // Too close to the previous sample.
+    record(&mut history, &report(150), 10 * day + 60);
+    record(&mut history, &report(300), 11 * day);
+    let samples = &history["uuid:1234"];
+    assert_eq!(samples, &[(10 * day, 100), (11 * day, 300)]);
+    assert_eq!(
+      baseline(samples, 11 * day + 60, SAMPLE_EVERY),
+      Some((10 * day, 100))
+    );
+    assert_eq!(
+      baseline(samples, 12 * day, SAMPLE_EVERY),
+      Some((11 * day, 300))
+    );
+    assert_eq!(baseline(samples, 12 * day, 2 * day), Some((10 * day, 100)));
+    assert_eq!(baseline(samples, 10 * day + 60, SAMPLE_EVERY), None);
+    assert_eq!(format_elapsed(day), "24h");
+    assert_eq!(format_elapsed(3 * day), "3d");
+    assert_eq!(Trend::new(100, 300, 1000), Trend::Up);
+    assert_eq!(Trend::new(300, 100, 1000), Trend::Down);
+    assert_eq!(Trend::new(1_000_000, 1_000_500, 1 << 30), Trend::Flat);
+  }
+
+  #[test]
+  fn test_read_write() {
+    let tmp = tempfile::TempDir::new().expect("Failed to create temp dir");
+    let path = tmp.path().join("cache").join(HISTORY_FILE);
+    assert!(read(&path).unwrap().is_empty());
+    let history = History::from([("/".into(), VecDeque::from([(1, 2), (3, 4)]))]);
+    write(&path, &history).unwrap();
+    assert_ne!(fs::read(&path).unwrap().first(), Some(&b'{'));
+    assert_eq!(read(&path).unwrap(), history);
+    fs::write(&path, "{").unwrap();
+    assert!(read(&path).is_err());
+  }
+}
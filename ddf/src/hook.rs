@@ -0,0 +1,301 @@
+//! Actions on the filesystems above a threshold: the `on_high` command, to clean up or page
+//! without a monitoring stack, the desktop notifications of `--notify` and the `[alert.webhook]`
+//! POST requests. The last runs are kept in the state directory so that a filesystem staying full
+//! only triggers them again after `on_high_cooldown`.
+
+use crate::{
+  output::{Record, Sample},
+  problems,
+  settings::{self, Settings, Severity, Webhook},
+  template::Template,
+  units::SizeFormat,
+};
+use serde::Serialize;
+use std::{
+  collections::HashMap,
+  fs,
+  io::Write,
+  path::Path,
+  process::{Command, Stdio},
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Time before the command runs again for a filesystem still above its high threshold.
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(3600);
+
+/// Files of the state directory with the last run of each filesystem, in seconds since the
+/// epoch.
+const ON_HIGH_STATE: &str = "on-high.json";
+const NOTIFY_STATE: &str = "notify.json";
+const WEBHOOK_STATE: &str = "webhook.json";
+
+/// Attempts of a webhook request after the first one, by default.
+const DEFAULT_RETRIES: u32 = 3;
+
+/// Print a failure of the hook, which also fails `--strict`.
+fn fail(problem: String) {
+  eprintln!("ddf: {problem}");
+  problems::report(problem);
+}
+
+/// `value` as a single `sh` word.
+fn shell_quote(value: &str) -> String {
+  format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// The filesystems of `high` the command is due for, forgetting the ones that went back below
+/// the threshold so that crossing it again runs the command at once.
+fn due<'a>(
+  last_runs: &mut HashMap<String, u64>,
+  high: &[&'a Record],
+  now: u64,
+  cooldown: Duration,
+) -> Vec<&'a Record> {
+  last_runs.retain(|id, _| high.iter().any(|record| record.id == *id));
+  high
+    .iter()
+    .copied()
+    .filter(|record| {
+      last_runs
+        .get(&record.id)
+        .is_none_or(|last| now >= last.saturating_add(cooldown.as_secs()))
+    })
+    .collect()
+}
+
+fn load(path: &Path) -> HashMap<String, u64> {
+  fs::read_to_string(path)
+    .ok()
+    .and_then(|text| serde_json::from_str(&text).ok())
+    .unwrap_or_default()
+}
+
+/// Call `action` with the filesystems of `records` at `severity` or above that it is due for,
+/// recording the run in the `state` file of the state directory when it succeeds.
+fn run_due(
+  settings: &Settings,
+  records: &[Record],
+  state: &str,
+  severity: Severity,
+  action: impl FnOnce(&[&Record]) -> bool,
+) {
+  let above: Vec<_> = records
+    .iter()
+    .filter(|record| record.severity >= Some(severity))
+    .collect();
+  let Some(path) = settings::state_dir().map(|dir| dir.join(state)) else {
+    fail(format!("{state}: no state directory"));
+    return;
+  };
+  let mut last_runs = load(&path);
+  let now = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .unwrap_or_default()
+    .as_secs();
+  let cooldown = settings
+    .on_high_cooldown
+    .as_ref()
+    .map_or(DEFAULT_COOLDOWN, |cooldown| cooldown.0);
+  let due = due(&mut last_runs, &above, now, cooldown);
+  if !due.is_empty() && action(&due) {
+    for record in due {
+      last_runs.insert(record.id.clone(), now);
+    }
+  }
+  let written = path
+    .parent()
+    .map_or(Ok(()), fs::create_dir_all)
+    .and_then(|()| fs::write(&path, serde_json::to_string(&last_runs)?));
+  if let Err(error) = written {
+    fail(format!("{}: {error}", path.display()));
+  }
+}
+
+/// Run the actions of the settings, and the notifications when `notify`, for the filesystems of
+/// `records`.
+pub(crate) fn run(settings: &Settings, records: &[Record], size_format: &SizeFormat, notify: bool) {
+  if let Some(template) = &settings.on_high {
+    on_high(settings, template, records, size_format);
+  }
+  if notify {
+    self::notify(settings, records, size_format);
+  }
+  if let Some(webhook) = settings
+    .alert
+    .as_ref()
+    .and_then(|alert| alert.webhook.as_ref())
+  {
+    self::webhook(settings, webhook, records, size_format);
+  }
+}
+
+/// Run the `on_high` command for the filesystems of `records` above their high threshold,
+/// waiting for each one.
+fn on_high(settings: &Settings, template: &Template, records: &[Record], size_format: &SizeFormat) {
+  run_due(settings, records, ON_HIGH_STATE, Severity::High, |due| {
+    for record in due {
+      // Every field, `?` of an unknown value and human sizes included, is a single word.
+      let command = template.render_quoted(record, size_format, shell_quote);
+      match Command::new("sh").arg("-c").arg(&command).status() {
+        Ok(status) if status.success() => {}
+        Ok(status) => fail(format!("on_high: `{command}`: {status}")),
+        Err(error) => fail(format!("on_high: `{command}`: {error}")),
+      }
+    }
+    // Not retried at each run when it fails, it could be slow or have side effects.
+    true
+  });
+}
+
+/// Title of the alerts of the filesystems `due`.
+fn summary(due: &[&Record]) -> String {
+  match due {
+    [record] => format!("{} is almost full", record.target),
+    _ => format!("{} filesystems are almost full", due.len()),
+  }
+}
+
+/// Body of the alerts of the filesystems `due`, one line each, like `/home: 3.1GiB free, 96% used`.
+fn notification_body(due: &[&Record], size_format: &SizeFormat) -> String {
+  due
+    .iter()
+    .map(|record| {
+      let pcent = record
+        .pcent
+        .map_or(String::new(), |pcent| format!(", {}% used", pcent.round()));
+      format!(
+        "{}: {} free{pcent}",
+        record.target,
+        size_format.format_opt(record.avail)
+      )
+    })
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+/// `--notify`: send a desktop notification, through the freedesktop notification service, for
+/// the filesystems of `records` crossing their high threshold.
+fn notify(settings: &Settings, records: &[Record], size_format: &SizeFormat) {
+  run_due(settings, records, NOTIFY_STATE, Severity::High, |due| {
+    let sent = Command::new("notify-send")
+      .args([
+        "--app-name=ddf",
+        "--urgency=critical",
+        "--icon=drive-harddisk",
+      ])
+      .arg(summary(due))
+      .arg(notification_body(due, size_format))
+      .status();
+    match sent {
+      Ok(status) if status.success() => return true,
+      Ok(status) => fail(format!("notify-send: {status}")),
+      Err(error) => fail(format!("notify-send: {error}")),
+    }
+    false
+  });
+}
+
+/// JSON document POSTed by `[alert.webhook]`: the report of the filesystems, with a `text`
+/// summary for the receivers showing a message, like Slack and Mattermost.
+#[derive(Serialize)]
+struct Payload<'a> {
+  text: String,
+  sample: Sample,
+  filesystems: &'a [&'a Record],
+}
+
+/// POST with `curl` the filesystems of `records` crossing the threshold of `webhook`, retrying on
+/// the network errors and the transient HTTP ones.
+fn webhook(settings: &Settings, webhook: &Webhook, records: &[Record], size_format: &SizeFormat) {
+  let severity = webhook.threshold.unwrap_or(Severity::High);
+  run_due(settings, records, WEBHOOK_STATE, severity, |due| {
+    let sample = Sample::now(settings);
+    let payload = Payload {
+      text: format!(
+        "{}: {}\n{}",
+        sample.hostname,
+        summary(due),
+        notification_body(due, size_format)
+      ),
+      sample,
+      filesystems: due,
+    };
+    let retries = webhook.retries.unwrap_or(DEFAULT_RETRIES).to_string();
+    let sent = Command::new("curl")
+      .args(["--silent", "--show-error", "--fail", "--max-time", "10"])
+      .args(["--retry", &retries, "--retry-connrefused"])
+      .args(["--header", "Content-Type: application/json"])
+      .args(["--data-binary", "@-"])
+      .arg(&webhook.url)
+      .stdin(Stdio::piped())
+      .stdout(Stdio::null())
+      .spawn()
+      .and_then(|mut child| {
+        let body = serde_json::to_vec(&payload)?;
+        if let Some(mut stdin) = child.stdin.take() {
+          stdin.write_all(&body)?;
+        }
+        child.wait()
+      });
+    match sent {
+      Ok(status) if status.success() => return true,
+      Ok(status) => fail(format!("alert.webhook: {}: curl {status}", webhook.url)),
+      Err(error) => fail(format!("alert.webhook: curl: {error}")),
+    }
+    false
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_due() {
+    let record = |id: &str| Record {
+      id: id.to_string(),
+      ..Default::default()
+    };
+    let (root, home) = (record("root"), record("home"));
+    let cooldown = Duration::from_secs(60);
+    let mut last_runs = HashMap::from([
+      ("root".to_string(), 1000),
+      ("home".to_string(), 900),
+      ("gone".to_string(), 1000),
+    ]);
+    let ids = |records: Vec<&Record>| records.iter().map(|r| r.id.clone()).collect::<Vec<_>>();
+    assert_eq!(
+      ids(due(&mut last_runs, &[&root, &home], 1030, cooldown)),
+      ["home"]
+    );
+    assert!(!last_runs.contains_key("gone"));
+    assert_eq!(ids(due(&mut last_runs, &[&root], 1060, cooldown)), ["root"]);
+    assert_eq!(
+      ids(due(&mut last_runs, &[], 1060, cooldown)),
+      Vec::<String>::new()
+    );
+    assert!(last_runs.is_empty());
+    assert_eq!(shell_quote("/mnt/it's"), r"'/mnt/it'\''s'");
+    let odd = Record {
+      target: "/mnt/$(reboot)".to_string(),
+      ..Default::default()
+    };
+    assert_eq!(
+      crate::template::parse_template("echo {mount} {pcent} {avail:h}")
+        .unwrap()
+        .render_quoted(&odd, &SizeFormat::Human, shell_quote),
+      "echo '/mnt/$(reboot)' '?' '?'"
+    );
+
+    let full = Record {
+      target: "/".to_string(),
+      avail: Some(3 << 30),
+      pcent: Some(95.6),
+      ..Default::default()
+    };
+    assert_eq!(
+      notification_body(&[&full, &home], &SizeFormat::Human),
+      "/: 3GiB free, 96% used\n: ? free"
+    );
+  }
+}
@@ -0,0 +1,107 @@
+//! Information about the machine ddf runs on.
+
+#[cfg(unix)]
+use std::ffi::CStr;
+use std::time::Duration;
+
+/// Name of the local host, or an empty string if it can't be read.
+#[cfg(unix)]
+pub(crate) fn hostname() -> String {
+  let mut buffer = [0 as libc::c_char; 256];
+  // SAFETY: the buffer is valid for its whole length and `gethostname` writes at most that much.
+  if unsafe { libc::gethostname(buffer.as_mut_ptr(), buffer.len()) } != 0 {
+    return String::new();
+  }
+  buffer[buffer.len() - 1] = 0;
+  // SAFETY: the buffer is nul terminated.
+  unsafe { CStr::from_ptr(buffer.as_ptr()) }
+    .to_string_lossy()
+    .into_owned()
+}
+
+/// Name of the local host, from the environment: `COMPUTERNAME` on Windows.
+#[cfg(not(unix))]
+pub(crate) fn hostname() -> String {
+  std::env::var("COMPUTERNAME")
+    .or_else(|_| std::env::var("HOSTNAME"))
+    .unwrap_or_default()
+}
+
+/// Kernel name and release, like `Linux 6.1.0`.
+#[cfg(unix)]
+pub(crate) fn kernel() -> String {
+  // SAFETY: `utsname` is plain old data, zeroed is a valid value.
+  let mut uts: libc::utsname = unsafe { std::mem::zeroed() };
+  // SAFETY: `uts` is a valid `utsname` to fill.
+  if unsafe { libc::uname(&mut uts) } != 0 {
+    return String::new();
+  }
+  // SAFETY: `uname` nul terminates the fields it fills.
+  let field = |f: &[libc::c_char]| unsafe { CStr::from_ptr(f.as_ptr()) }.to_string_lossy();
+  format!("{} {}", field(&uts.sysname), field(&uts.release))
+}
+
+/// Name of the operating system, like `windows`, where there is no `uname`.
+#[cfg(not(unix))]
+pub(crate) fn kernel() -> String {
+  std::env::consts::OS.to_string()
+}
+
+/// Time since boot.
+pub(crate) fn uptime() -> Option<Duration> {
+  let uptime = std::fs::read_to_string("/proc/uptime").ok()?;
+  let seconds: f64 = uptime.split_whitespace().next()?.parse().ok()?;
+  Some(Duration::from_secs_f64(seconds))
+}
+
+/// Random identifier of the current boot, changing at every reboot.
+pub(crate) fn boot_id() -> Option<String> {
+  std::fs::read_to_string("/proc/sys/kernel/random/boot_id")
+    .ok()
+    .map(|id| id.trim().to_string())
+}
+
+/// Compact duration like `3d 04:12`.
+pub(crate) fn format_uptime(uptime: Duration) -> String {
+  let minutes = uptime.as_secs() / 60;
+  let (days, hours, minutes) = (minutes / (24 * 60), minutes / 60 % 24, minutes % 60);
+  if days > 0 {
+    format!("{days}d {hours:02}:{minutes:02}")
+  } else {
+    format!("{hours:02}:{minutes:02}")
+  }
+}
+
+/// Login name of `uid`, or the uid itself when it has no passwd entry.
+#[cfg(unix)]
+pub(crate) fn user_name(uid: u32) -> String {
+  let mut buffer = vec![0 as libc::c_char; 4096];
+  // SAFETY: `passwd` is plain old data, zeroed is a valid value.
+  let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+  let mut result = std::ptr::null_mut();
+  // SAFETY: every pointer is valid, `buffer` outlives the use of `passwd`.
+  let found = unsafe {
+    libc::getpwuid_r(
+      uid,
+      &mut passwd,
+      buffer.as_mut_ptr(),
+      buffer.len(),
+      &mut result,
+    )
+  } == 0
+    && !result.is_null();
+  if found {
+    // SAFETY: `pw_name` points into `buffer`, nul terminated.
+    unsafe { CStr::from_ptr(passwd.pw_name) }
+      .to_string_lossy()
+      .into_owned()
+  } else {
+    uid.to_string()
+  }
+}
+
+/// The uid itself, there is no passwd database.
+#[cfg(not(unix))]
+pub(crate) fn user_name(uid: u32) -> String {
+  uid.to_string()
+}
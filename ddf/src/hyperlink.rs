@@ -0,0 +1,111 @@
+//! OSC 8 hyperlinks, so a click on a mount directory opens it in the file manager.
+
+use clap::ValueEnum;
+
+/// When to link the mount directories, chosen with `--hyperlinks`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub(crate) enum Hyperlinks {
+  Never,
+  /// When the output is a terminal known to support them.
+  #[default]
+  Auto,
+  Always,
+}
+
+impl Hyperlinks {
+  pub(crate) fn enabled(self, is_terminal: bool) -> bool {
+    match self {
+      Hyperlinks::Never => false,
+      Hyperlinks::Auto => is_terminal && supported(|name| std::env::var(name).ok()),
+      Hyperlinks::Always => true,
+    }
+  }
+}
+
+/// Whether the terminal described by the environment `var` renders OSC 8 hyperlinks. Terminals
+/// that don't usually print the text and drop the sequence, but a few print it verbatim, so only
+/// the known ones are trusted.
+fn supported(var: impl Fn(&str) -> Option<String>) -> bool {
+  if let Some(force) = var("FORCE_HYPERLINK") {
+    return force != "0";
+  }
+  if var("DOMTERM").is_some() || var("WT_SESSION").is_some() || var("KONSOLE_VERSION").is_some() {
+    return true;
+  }
+  if let Some(version) = var("VTE_VERSION") {
+    // VTE 0.50 (GNOME Terminal 3.26) and later.
+    return version.parse::<u32>().is_ok_and(|version| version >= 5000);
+  }
+  let term_program = var("TERM_PROGRAM").unwrap_or_default();
+  let term = var("TERM").unwrap_or_default();
+  TERM_PROGRAMS.contains(&term_program.as_str())
+    || TERMS.contains(&term.as_str())
+    || term.starts_with("foot")
+}
+
+/// `TERM_PROGRAM` of the terminals supporting hyperlinks.
+const TERM_PROGRAMS: &[&str] = &[
+  "Hyper",
+  "iTerm.app",
+  "terminology",
+  "WezTerm",
+  "vscode",
+  "ghostty",
+];
+/// `TERM` of the terminals supporting hyperlinks.
+const TERMS: &[&str] = &[
+  "xterm-kitty",
+  "alacritty",
+  "alacritty-direct",
+  "xterm-ghostty",
+];
+
+/// `file://` URL of a local `path`, with the host name so remote terminals don't open it locally.
+pub(crate) fn file_url(host: &str, path: &str) -> String {
+  let mut url = format!("file://{host}");
+  for byte in path.bytes() {
+    match byte {
+      b'/' | b'-' | b'.' | b'_' | b'~' => url.push(byte as char),
+      _ if byte.is_ascii_alphanumeric() => url.push(byte as char),
+      _ => url.push_str(&format!("%{byte:02X}")),
+    }
+  }
+  url
+}
+
+/// Escape sequence starting a link to `url`.
+pub(crate) fn open(url: &str) -> String {
+  format!("\x1b]8;;{url}\x1b\\")
+}
+
+/// Escape sequence ending a link.
+pub(crate) const CLOSE: &str = "\x1b]8;;\x1b\\";
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_file_url() {
+    assert_eq!(file_url("box", "/mnt/my disk"), "file://box/mnt/my%20disk");
+    assert_eq!(file_url("", "/média"), "file:///m%C3%A9dia");
+  }
+
+  #[test]
+  fn test_supported() {
+    let env = |vars: &'static [(&str, &str)]| {
+      move |name: &str| {
+        vars
+          .iter()
+          .find(|(key, _)| *key == name)
+          .map(|(_, value)| value.to_string())
+      }
+    };
+    assert!(supported(env(&[("TERM", "xterm-kitty")])));
+    assert!(supported(env(&[("VTE_VERSION", "7600")])));
+    assert!(!supported(env(&[("VTE_VERSION", "4205")])));
+    assert!(!supported(env(&[("TERM", "xterm-256color")])));
+    let forced_off = env(&[("TERM", "foot"), ("FORCE_HYPERLINK", "0")]);
+    assert!(!supported(forced_off));
+  }
+}
@@ -0,0 +1,120 @@
+//! `--icons`: a glyph before each row telling the kind of the filesystem, like `eza` does for
+//! files.
+
+use crate::{
+  disk,
+  fsext::{is_dummy_filesystem, MountInfo, DUMMY_FILESYSTEMS, IN_MEMORY_FILESYSTEMS},
+};
+use clap::ValueEnum;
+use serde::Deserialize;
+
+/// Glyphs of `--icons`, the `icons` setting by default.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum IconStyle {
+  /// Nerd Font symbols, for the terminals using a patched font.
+  Nerd,
+  Emoji,
+  /// Short words, for any terminal.
+  Text,
+}
+
+impl IconStyle {
+  /// Cells taken by the glyphs of the style.
+  pub(crate) fn width(self) -> usize {
+    match self {
+      Self::Nerd => 1,
+      Self::Emoji => 2,
+      Self::Text => 4,
+    }
+  }
+}
+
+/// Network filesystem types, besides the `host:/export` and `//server/share` devices.
+const NETWORK_FILESYSTEMS: &[&str] = &[
+  "nfs",
+  "nfs4",
+  "cifs",
+  "smb3",
+  "smbfs",
+  "9p",
+  "afs",
+  "ceph",
+  "fuse.sshfs",
+  "fuse.rclone",
+  "fuse.glusterfs",
+  "davfs",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+  Disk,
+  Network,
+  Usb,
+  Memory,
+  Virtual,
+}
+
+fn kind(mount_info: &MountInfo) -> Kind {
+  let fs_type = mount_info.fs_type.as_str();
+  let dev_name = &mount_info.dev_name;
+  if NETWORK_FILESYSTEMS.contains(&fs_type) || dev_name.starts_with("//") || dev_name.contains(":/") {
+    Kind::Network
+  } else if IN_MEMORY_FILESYSTEMS.contains(&fs_type) {
+    Kind::Memory
+  } else if is_dummy_filesystem(fs_type, &mount_info.mount_option, DUMMY_FILESYSTEMS) {
+    Kind::Virtual
+  } else if disk::is_usb(&mount_info.dev_id)
+    || ["/media/", "/run/media/"]
+      .iter()
+      .any(|dir| mount_info.mount_dir.starts_with(dir))
+  {
+    Kind::Usb
+  } else {
+    Kind::Disk
+  }
+}
+
+/// Glyph of the kind of the filesystem mounted by `mount_info`, [`IconStyle::width`] cells wide.
+pub(crate) fn icon(style: IconStyle, mount_info: &MountInfo) -> &'static str {
+  let [nerd, emoji, text] = match kind(mount_info) {
+    Kind::Disk => ["\u{f0a0}", "💽", "disk"],
+    Kind::Network => ["\u{f0ac}", "🌐", "net "],
+    Kind::Usb => ["\u{f287}", "🔌", "usb "],
+    Kind::Memory => ["\u{f2db}", "🧠", "mem "],
+    Kind::Virtual => ["\u{f013}", "🔧", "virt"],
+  };
+  match style {
+    IconStyle::Nerd => nerd,
+    IconStyle::Emoji => emoji,
+    IconStyle::Text => text,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_kind() {
+    let mount = |dev_name: &str, fs_type: &str, mount_dir: &str| MountInfo {
+      dev_name: dev_name.to_string(),
+      fs_type: fs_type.to_string(),
+      mount_dir: mount_dir.to_string(),
+      ..Default::default()
+    };
+    assert_eq!(kind(&mount("/dev/sda1", "ext4", "/")), Kind::Disk);
+    assert_eq!(kind(&mount("nas:/export", "nfs4", "/mnt")), Kind::Network);
+    assert_eq!(kind(&mount("//nas/share", "cifs", "/mnt")), Kind::Network);
+    assert_eq!(kind(&mount("tmpfs", "tmpfs", "/tmp")), Kind::Memory);
+    assert_eq!(kind(&mount("proc", "proc", "/proc")), Kind::Virtual);
+    assert_eq!(
+      kind(&mount("/dev/sdb1", "vfat", "/run/media/me/KEY")),
+      Kind::Usb
+    );
+    assert_eq!(
+      icon(IconStyle::Text, &mount("tmpfs", "tmpfs", "/tmp")),
+      "mem "
+    );
+  }
+}
@@ -0,0 +1,230 @@
+//! `ddf import-df FILE`: the output of GNU or POSIX `df` captured on a machine without ddf, like
+//! `df -P`, `df -hT` or `df --output`, rendered as if it was the local machine.
+
+use crate::{
+  filesystem::Filesystem,
+  fsext::{FsUsage, MountInfo},
+  units::parse_block_size,
+};
+use anyhow::{anyhow, Context, Result};
+use std::{fs, io::Read, path::Path};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Column {
+  Source,
+  Type,
+  /// `1K-blocks`, `1024-blocks`...: the total, in blocks of that many bytes.
+  Blocks,
+  /// `Size` of `df -h`, the total with a unit.
+  Size,
+  Used,
+  Avail,
+  Inodes,
+  IUsed,
+  IFree,
+  Target,
+  /// Recomputed (`Use%`) or not shown (`File`).
+  Ignored,
+}
+
+/// Columns of the header line of `df`, with the block size of the sizes, none when they have
+/// units.
+fn parse_header(header: &str) -> Result<(Vec<Column>, Option<u64>), String> {
+  let mut columns = vec![];
+  let mut block_size = None;
+  let mut words = header.split_whitespace().peekable();
+  while let Some(word) = words.next() {
+    columns.push(match word {
+      "Filesystem" => Column::Source,
+      "Type" => Column::Type,
+      "Size" => Column::Size,
+      "Used" => Column::Used,
+      "Avail" | "Available" => Column::Avail,
+      "Inodes" => Column::Inodes,
+      "IUsed" => Column::IUsed,
+      "IFree" => Column::IFree,
+      "Use%" | "Capacity" | "IUse%" | "File" => Column::Ignored,
+      "Mounted" if words.peek() == Some(&"on") => {
+        words.next();
+        Column::Target
+      }
+      _ => {
+        let size = word
+          .strip_suffix("-blocks")
+          .ok_or_else(|| format!("unknown column `{word}`"))?;
+        block_size = Some(parse_block_size(size)?.bytes);
+        Column::Blocks
+      }
+    });
+  }
+  if !columns.contains(&Column::Target) {
+    return Err("no `Mounted on` column".to_string());
+  }
+  Ok((columns, block_size))
+}
+
+/// A size of `df -h`, like `976M` or `1.5G`, in bytes.
+fn parse_human(value: &str) -> Option<u64> {
+  let split = value
+    .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+    .unwrap_or(value.len());
+  let (number, unit) = value.split_at(split);
+  let exponent = ["", "K", "M", "G", "T", "P", "E"]
+    .iter()
+    .position(|suffix| suffix.eq_ignore_ascii_case(unit))?;
+  Some((number.parse::<f64>().ok()? * 1024f64.powi(exponent as i32)) as u64)
+}
+
+/// Filesystems of the text printed by `df`.
+fn parse(text: &str) -> Result<Vec<Filesystem>, String> {
+  let mut lines = text
+    .lines()
+    .enumerate()
+    .filter(|(_, line)| !line.trim().is_empty());
+  let (columns, block_size) = match lines.next() {
+    Some((_, header)) => parse_header(header)?,
+    None => return Ok(vec![]),
+  };
+  let mut filesystems = vec![];
+  // Without `-P`, a long device name is alone on its line, the rest follows on the next one.
+  let mut wrapped: Option<&str> = None;
+  for (index, line) in lines {
+    let mut values: Vec<&str> = wrapped.take().into_iter().collect();
+    values.extend(line.split_whitespace());
+    if values.len() == 1 && columns.len() > 1 {
+      wrapped = values.pop();
+      continue;
+    }
+    // Mount directories may contain spaces: the extra words belong to the last column.
+    let target_extra = values.len().saturating_sub(columns.len());
+    if values.len() < columns.len() || (target_extra > 0 && columns.last() != Some(&Column::Target)) {
+      return Err(format!(
+        "line {}: expected {} columns, found {}",
+        index + 1,
+        columns.len(),
+        values.len()
+      ));
+    }
+    let (mut size, mut used, mut avail) = (0, 0, 0);
+    let mut mount_info = MountInfo::default();
+    let (mut files, mut ffree) = (0, 0);
+    let mut values = values.into_iter();
+    for column in &columns {
+      let value = if *column == Column::Target {
+        values
+          .by_ref()
+          .take(target_extra + 1)
+          .collect::<Vec<_>>()
+          .join(" ")
+      } else {
+        values.next().unwrap_or_default().to_string()
+      };
+      // `-` for the sizes a filesystem doesn't have.
+      let number = || -> Result<u64, String> {
+        let bytes = match (value.as_str(), block_size) {
+          ("-", _) => Some(0),
+          (value, Some(block_size)) if *column != Column::Size => value
+            .parse::<u64>()
+            .ok()
+            .and_then(|blocks| blocks.checked_mul(block_size)),
+          (value, _) => parse_human(value),
+        };
+        bytes.ok_or_else(|| format!("line {}: invalid size `{value}`", index + 1))
+      };
+      let count = || value.parse::<u64>().unwrap_or_default();
+      match column {
+        Column::Source => mount_info.dev_name = value.clone(),
+        Column::Type => mount_info.fs_type = value.clone(),
+        Column::Blocks | Column::Size => size = number()?,
+        Column::Used => used = number()?,
+        Column::Avail => avail = number()?,
+        Column::Inodes => files = count(),
+        Column::IFree => ffree = count(),
+        Column::Target => mount_info.mount_dir = value.clone(),
+        Column::IUsed | Column::Ignored => {}
+      }
+    }
+    // Without `-T`, the pseudo filesystems are named after their type (`tmpfs`, `overlay`), so
+    // the exclusions of the settings still apply.
+    if !columns.contains(&Column::Type) && !mount_info.dev_name.contains('/') {
+      mount_info.fs_type = mount_info.dev_name.clone();
+    }
+    let bfree = size.saturating_sub(used);
+    filesystems.push(Filesystem {
+      mount_info,
+      usage: FsUsage {
+        blocksize: 1,
+        blocks: size,
+        bfree,
+        bavail: avail.min(bfree),
+        files,
+        ffree,
+        raw: None,
+      },
+    });
+  }
+  Ok(filesystems)
+}
+
+/// Read the filesystems of the `df` output in the file at `path`, or the standard input for `-`.
+pub(crate) fn load(path: &Path) -> Result<Vec<Filesystem>> {
+  let text = if path == Path::new("-") {
+    let mut text = String::new();
+    std::io::stdin().read_to_string(&mut text)?;
+    text
+  } else {
+    fs::read_to_string(path).with_context(|| path.display().to_string())?
+  };
+  parse(&text).map_err(|error| anyhow!("{}: {error}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse() {
+    let posix = "Filesystem     1024-blocks      Used Available Capacity Mounted on
+/dev/sda1         41152736  20576368  18463068      53% /
+tmpfs              1632268         0   1632268       0% /run/user/1000
+//nas/share
+                   1000000    250000    750000      25% /mnt/My Share
+";
+    let filesystems = parse(posix).unwrap();
+    assert_eq!(filesystems.len(), 3);
+    let root = &filesystems[0];
+    assert_eq!(root.mount_info.dev_name, "/dev/sda1");
+    assert_eq!(root.size(), Some(41152736 * 1024));
+    assert_eq!(root.used(), Some(20576368 * 1024));
+    assert_eq!(root.avail(), Some(18463068 * 1024));
+    assert_eq!(filesystems[1].mount_info.fs_type, "tmpfs");
+    assert_eq!(filesystems[2].mount_info.fs_type, "");
+    assert_eq!(filesystems[2].mount_info.dev_name, "//nas/share");
+    assert_eq!(filesystems[2].mount_info.mount_dir, "/mnt/My Share");
+
+    let human = "Filesystem     Type   Size  Used Avail Use% Mounted on
+/dev/nvme0n1p2 ext4   468G  1.5G  443G   1% /
+";
+    let filesystems = parse(human).unwrap();
+    let root = &filesystems[0];
+    assert_eq!(root.mount_info.fs_type, "ext4");
+    assert_eq!(root.size(), Some(468 << 30));
+    assert_eq!(root.used(), Some(3 << 29));
+
+    let output = "Filesystem      Inodes  IUsed   IFree IUse% 1K-blocks Used Avail Mounted on
+/dev/sda1      2621440 262144 2359296   10%       100   50    40 /
+";
+    let root = &parse(output).unwrap()[0];
+    assert_eq!((root.usage.files, root.usage.ffree), (2621440, 2359296));
+    assert_eq!(root.reserved(), Some(10 * 1024));
+
+    assert_eq!(
+      parse("Filesystem Size Used Avail Use%").unwrap_err(),
+      "no `Mounted on` column"
+    );
+    assert_eq!(
+      parse("Filesystem Size Mounted on\n/dev/sda1 12X /\n").unwrap_err(),
+      "line 2: invalid size `12X`"
+    );
+  }
+}
@@ -0,0 +1,267 @@
+//! `--kubelet`: the mounts of a Kubernetes node grouped by pod, with the ephemeral storage of each
+//! pod. The pods are read from the kubelet directory and the container logs, not from the API.
+
+use crate::{
+  anonymize::stable_hash,
+  filesystem::Filesystem,
+  fsext::read_fs_list,
+  settings::Settings,
+  units::SizeFormat,
+  walk::{self, Cancel, DiskUsage, WalkOptions},
+};
+use anyhow::Result;
+use std::{
+  collections::HashMap,
+  fs,
+  io::Write,
+  path::{Path, PathBuf},
+};
+use tracing::debug;
+
+/// Root directory of the kubelet unless `--root-dir` says otherwise.
+pub(crate) const DEFAULT_ROOT: &str = "/var/lib/kubelet";
+/// Container logs, in `NAMESPACE_NAME_UID` directories.
+const LOGS: &str = "/var/log/pods";
+/// Volume plugin of the `emptyDir` volumes, stored on the node disk unless in memory.
+const EMPTY_DIR: &str = "kubernetes.io~empty-dir";
+
+#[derive(Debug, Default)]
+struct Pod {
+  uid: String,
+  /// `NAMESPACE/NAME`, or only the name when read from the hosts file.
+  name: Option<String>,
+  /// Mounted volumes, with their name and plugin.
+  volumes: Vec<(String, Filesystem)>,
+  /// Bytes of the `emptyDir` volumes on the node disk.
+  empty_dir: u64,
+  /// Bytes of the container logs.
+  logs: u64,
+}
+
+impl Pod {
+  fn ephemeral(&self) -> u64 {
+    self.empty_dir + self.logs
+  }
+}
+
+/// Pod uid and volume of a mount below the kubelet `root`: `ROOT/pods/UID/volumes/PLUGIN/NAME`,
+/// or `ROOT/pods/UID/volume-subpaths/NAME/...` for volumes mounted with a `subPath`.
+fn volume_of(root: &Path, mount_dir: &str) -> Option<(String, String)> {
+  let relative = Path::new(mount_dir).strip_prefix(root.join("pods")).ok()?;
+  let parts: Vec<_> = relative.iter().map(|part| part.to_string_lossy()).collect();
+  match parts.as_slice() {
+    // CSI volumes are mounted in a `mount` subdirectory.
+    [uid, kind, plugin, name, ..] if kind == "volumes" => {
+      Some((uid.to_string(), format!("{name} ({plugin})")))
+    }
+    [uid, kind, name, ..] if kind == "volume-subpaths" => {
+      Some((uid.to_string(), format!("{name} (subpath)")))
+    }
+    _ => None,
+  }
+}
+
+/// `NAMESPACE/NAME` and uid of a log directory name `NAMESPACE_NAME_UID`. Kubernetes names never
+/// contain `_`.
+fn parse_log_dir(name: &str) -> Option<(String, String)> {
+  let [namespace, name, uid] = name.split('_').collect::<Vec<_>>()[..] else {
+    return None;
+  };
+  Some((format!("{namespace}/{name}"), uid.to_string()))
+}
+
+/// Pod name from the hosts file written by the kubelet: its last entry maps the pod IP to it.
+fn hosts_name(hosts: &str) -> Option<String> {
+  let entry = hosts
+    .lines()
+    .map(str::trim)
+    .rfind(|line| !line.is_empty() && !line.starts_with('#'))?;
+  Some(entry.split_whitespace().nth(1)?.to_string())
+}
+
+/// Bytes allocated below `dir`, zero when it can't be walked.
+fn disk_usage(dir: &Path, options: &WalkOptions) -> u64 {
+  let usage = DiskUsage::default();
+  let total = std::sync::atomic::AtomicU64::default();
+  let walked = walk::walk(dir, options, &Cancel::default(), &|_, metadata| {
+    total.fetch_add(
+      usage.allocated(metadata),
+      std::sync::atomic::Ordering::Relaxed,
+    );
+  });
+  if let Err(error) = walked {
+    debug!("{}: {error}", dir.display());
+  }
+  total.into_inner()
+}
+
+/// Subdirectories of `dir`, none when it can't be read.
+fn subdirs(dir: &Path) -> Vec<PathBuf> {
+  fs::read_dir(dir)
+    .into_iter()
+    .flatten()
+    .filter_map(|entry| Some(entry.ok()?.path()))
+    .filter(|path| path.is_dir())
+    .collect()
+}
+
+/// Pods of the node with a kubelet in `root`, the largest ephemeral storage first.
+fn pods(root: &Path) -> Result<Vec<Pod>> {
+  let mounts = read_fs_list()?;
+  let options = WalkOptions::new(&mounts, vec![]);
+  let mut pods: HashMap<String, Pod> = HashMap::new();
+  for dir in subdirs(&root.join("pods")) {
+    let uid = dir
+      .file_name()
+      .unwrap_or_default()
+      .to_string_lossy()
+      .into_owned();
+    let name = fs::read_to_string(dir.join("etc-hosts"))
+      .ok()
+      .and_then(|hosts| hosts_name(&hosts));
+    // Memory backed ones are mounts, shown with the volumes.
+    let empty_dir = subdirs(&dir.join("volumes").join(EMPTY_DIR))
+      .iter()
+      .filter(|volume| !options.mount_points.contains(*volume))
+      .map(|volume| disk_usage(volume, &options))
+      .sum();
+    pods.insert(
+      uid.clone(),
+      Pod {
+        uid,
+        name,
+        empty_dir,
+        ..Default::default()
+      },
+    );
+  }
+  for dir in subdirs(Path::new(LOGS)) {
+    let Some((name, uid)) = parse_log_dir(&dir.file_name().unwrap_or_default().to_string_lossy())
+    else {
+      continue;
+    };
+    let pod = pods.entry(uid.clone()).or_insert_with(|| Pod {
+      uid,
+      ..Default::default()
+    });
+    pod.name = Some(name);
+    pod.logs = disk_usage(&dir, &options);
+  }
+  for mount in mounts {
+    let Some((uid, volume)) = volume_of(root, &mount.mount_dir) else {
+      continue;
+    };
+    if let (Some(pod), Some(fs)) = (pods.get_mut(&uid), Filesystem::new(mount, None)) {
+      pod.volumes.push((volume, fs));
+    }
+  }
+  let mut pods: Vec<Pod> = pods.into_values().collect();
+  pods.sort_by(|a, b| {
+    b.ephemeral()
+      .cmp(&a.ephemeral())
+      .then_with(|| a.uid.cmp(&b.uid))
+  });
+  Ok(pods)
+}
+
+/// Print the pods of the node with a kubelet in `root`, each followed by its mounted volumes.
+pub(crate) fn run(root: &Path, settings: &Settings, size_format: &SizeFormat) -> Result<()> {
+  let pods = pods(root)?;
+  let anonymize = settings.anonymize.unwrap_or_default();
+  let label = |pod: &Pod| {
+    let label = pod.name.as_deref().unwrap_or(&pod.uid);
+    if anonymize {
+      stable_hash(label)
+    } else {
+      label.to_string()
+    }
+  };
+  let width = pods
+    .iter()
+    .map(|pod| label(pod).len())
+    .max()
+    .unwrap_or_default()
+    .max(3);
+  let mut out = std::io::stdout().lock();
+  writeln!(
+    out,
+    "{:<width$} {:>10} {:>10} {:>10} Volumes",
+    "Pod", "Ephemeral", "EmptyDir", "Logs"
+  )?;
+  for pod in &pods {
+    writeln!(
+      out,
+      "{:<width$} {:>10} {:>10} {:>10} {}",
+      label(pod),
+      size_format.format(pod.ephemeral()),
+      size_format.format(pod.empty_dir),
+      size_format.format(pod.logs),
+      pod.volumes.len(),
+    )?;
+    for (volume, fs) in &pod.volumes {
+      let percent = fs
+        .used_ratio()
+        .map_or("?".to_string(), |ratio| format!("{:.0}%", 100.0 * ratio));
+      writeln!(
+        out,
+        "  {} {} {} {} {percent} {}",
+        settings.device_label(&fs.mount_info),
+        size_format.format_opt(fs.size()),
+        size_format.format_opt(fs.used()),
+        size_format.format_opt(fs.avail()),
+        if anonymize {
+          stable_hash(volume)
+        } else {
+          volume.clone()
+        },
+      )?;
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_pod_paths() {
+    let root = Path::new(DEFAULT_ROOT);
+    let uid = "0b8c4f9e-21c5-4bd4-a8a4-5d6b3c1f0e2a";
+    assert_eq!(
+      volume_of(
+        root,
+        &format!("/var/lib/kubelet/pods/{uid}/volumes/kubernetes.io~csi/pvc-1/mount")
+      ),
+      Some((uid.to_string(), "pvc-1 (kubernetes.io~csi)".to_string()))
+    );
+    assert_eq!(
+      volume_of(
+        root,
+        &format!("/var/lib/kubelet/pods/{uid}/volumes/kubernetes.io~projected/kube-api-access")
+      ),
+      Some((
+        uid.to_string(),
+        "kube-api-access (kubernetes.io~projected)".to_string()
+      ))
+    );
+    assert_eq!(
+      volume_of(
+        root,
+        &format!("/var/lib/kubelet/pods/{uid}/volume-subpaths/config/nginx/0")
+      ),
+      Some((uid.to_string(), "config (subpath)".to_string()))
+    );
+    assert_eq!(volume_of(root, "/var/lib/docker"), None);
+    assert_eq!(
+      parse_log_dir(&format!("kube-system_coredns-5d78c9869d-abcde_{uid}")),
+      Some((
+        "kube-system/coredns-5d78c9869d-abcde".to_string(),
+        uid.to_string()
+      ))
+    );
+    assert_eq!(parse_log_dir("lost+found"), None);
+    let hosts = "# Kubernetes-managed hosts file.\n127.0.0.1\tlocalhost\n10.244.0.7\tweb-0\n";
+    assert_eq!(hosts_name(hosts), Some("web-0".to_string()));
+  }
+}
@@ -1,140 +1,201 @@
+mod anonymize;
+mod app;
 mod args;
+mod build_info;
+mod bundle;
+mod compress;
+mod disk;
+mod expr;
 mod filesystem;
 mod fsext;
+mod generate;
+mod graphics;
+mod history;
+mod hook;
+mod host;
+mod hyperlink;
+mod icons;
+mod import;
+mod kubelet;
+mod memory;
+mod mountwatch;
+mod output;
+mod palette;
+mod partition;
+mod problems;
+mod progress;
+mod render;
+mod scan;
+mod selftest;
 mod settings;
+mod stats;
+mod template;
 mod trace;
+mod tui;
+mod units;
+mod walk;
 
-use crate::{
-  args::{gen_completions, App},
-  filesystem::Filesystem,
-  settings::Exclusion::{FsType, MountDirStartsWith},
-};
-use anyhow::Result;
+use crate::args::{gen_completions, App, Command, ConfigCommand};
+use anyhow::{bail, Result};
 use clap::{CommandFactory, Parser};
-use fsext::read_fs_list;
-use humansize::{make_format, FormatSizeOptions, BINARY};
-use ratatui::{prelude::Backend, Terminal, Viewport};
-use settings::{settings, Settings};
+use output::Format;
+use scan::ScanKind;
+use settings::{check_config, settings, Settings};
 use trace::init_tracing;
-use tracing::{debug, trace};
+use tracing::debug;
 
 fn main() -> Result<()> {
   init_tracing()?;
   let args = App::parse();
-  let config = settings(&App::command().get_matches())?;
+  let matches = App::command().get_matches();
+  if args.version {
+    print!("{}", build_info::version(args.verbose));
+    return Ok(());
+  }
+  if args.check_config {
+    std::process::exit(check_config(&matches));
+  }
+  if let Some(Command::Config {
+    action: ConfigCommand::Init { force },
+  }) = &args.command
+  {
+    return settings::init(&matches, *force);
+  }
+  if let Some(Command::BalanceHelper { mount_dirs }) = &args.command {
+    progress::print_balances(mount_dirs);
+    return Ok(());
+  }
+  if args.sudo {
+    progress::use_sudo();
+  }
+  let config = settings(&matches)?;
   gen_completions(&args);
+  if args.command.is_none() {
+    settings::first_run_hint(&matches);
+  }
+  if args.self_test {
+    std::process::exit(if selftest::run(&config) { 0 } else { 1 });
+  }
 
   debug!("{:#?}", args);
-
-  let mounts = read_fs_list()?;
-  let filesystems: Vec<Filesystem> = if let Some(files) = args.files {
-    files
-      .iter()
-      .filter_map(|file| Filesystem::from_path(&mounts, file))
-      .collect()
-  } else {
-    mounts
-      .into_iter()
-      .filter_map(|m| Filesystem::new(m, None))
-      .filter(|fs| fs.usage.blocks > 0)
-      .filter(|fs| {
-        !config.exclude.as_ref().unwrap_or(&vec![]).iter().any(
-          |exclusion_rule| match exclusion_rule {
-            MountDirStartsWith(name) => fs.mount_info.mount_dir.starts_with(name),
-            FsType(typ) => fs.mount_info.fs_type == *typ,
-          },
-        )
-      })
-      .collect()
-  };
-
-  debug!("{filesystems:#?}");
-  let column_config = filesystems
-    .iter()
-    .map(|f| (f.mount_info.dev_name.len(), f.mount_info.mount_dir.len()))
-    .reduce(|acc, e| (acc.0.max(e.0), acc.1.max(e.1)))
-    .unwrap_or((10, 10));
-
-  trace!("{column_config:?}");
-
-  render_table(filesystems, config, column_config)?;
-  Ok(())
+  let result = run(&args, config);
+  if result.as_ref().is_err_and(output::is_broken_pipe) {
+    std::process::exit(0);
+  }
+  if let (Format::Nagios, Err(error)) = (args.format, &result) {
+    println!("DISK UNKNOWN - {error}");
+    std::process::exit(3);
+  }
+  let problems = problems::take();
+  if args.strict && !problems.is_empty() {
+    for problem in &problems {
+      eprintln!("ddf: {problem}");
+    }
+    eprintln!(
+      "ddf: --strict: the data is incomplete, errors: {}",
+      problems.len()
+    );
+    std::process::exit(1);
+  }
+  result
 }
 
-fn render_table(
-  filesystems: Vec<Filesystem>,
-  config: Settings,
-  columns_width: (usize, usize),
-) -> Result<(), anyhow::Error> {
-  let mut terminal = ratatui::init_with_options(ratatui::TerminalOptions {
-    viewport: Viewport::Inline(1),
-  });
-  for filesystem in filesystems {
-    render_line(&filesystem, &mut terminal, &config, columns_width)?;
-  }
-  ratatui::restore();
-  Ok(())
-}
+/// Print what the arguments ask for.
+fn run(args: &App, config: Settings) -> Result<()> {
+  let options = app::Options::new(args, &config)?;
+  let local = app::Local { args };
+  if args.posix_block_size().is_some() && (args.tui || args.watch.is_some()) {
+    bail!("--portability doesn't apply to --tui and --watch");
+  }
+  if args.notify && (args.command.is_some() || args.repeat.is_some() || args.kubelet.is_some()) {
+    bail!("--notify only applies to the filesystem list");
+  }
+  // Reload of `--watch` and of the TUI, alerting on each new sample.
+  let watched = || {
+    let filesystems = app::load(&options, &local)?;
+    let report = output::Report::new(&filesystems, &config);
+    hook::run(
+      &config,
+      &report.filesystems,
+      &args.size_format(&config),
+      args.notify,
+    );
+    Ok(filesystems)
+  };
+  if let (Some(interval), false) = (args.watch, args.tui) {
+    if args.command.is_some() || args.format != Format::Table {
+      bail!("--watch only applies to the filesystem table");
+    }
+    return render::watch(
+      watched,
+      &config,
+      interval,
+      &args.size_format(&config),
+      args.table_options(&config),
+    );
+  }
 
-fn render_line<A: Backend>(
-  fs: &Filesystem,
-  terminal: &mut Terminal<A>,
-  settings: &Settings,
-  columns_width: (usize, usize),
-) -> Result<()> {
-  use ratatui::{prelude::*, widgets::*};
-  terminal.insert_before(1, |frame| {
-    let [a_fs, a_size, a_used, a_avail, a_dir, a_percent] = Layout::default()
-      .direction(Direction::Horizontal)
-      .constraints([
-        Constraint::Length(columns_width.0 as u16 + 1),
-        Constraint::Length(10),
-        Constraint::Length(10),
-        Constraint::Length(10),
-        Constraint::Length(columns_width.1 as u16 + 1),
-        Constraint::Fill(1),
-      ])
-      .areas(*frame.area());
+  if let Some(count) = args.repeat {
+    if args.command.is_some() || args.format != Format::Table {
+      bail!("--repeat only applies to the filesystem table");
+    }
+    return stats::run(
+      || app::load(&options, &local),
+      count as usize,
+      args.interval,
+      &config,
+      &args.size_format(&config),
+    );
+  }
 
-    let bused = fs.usage.blocks.saturating_sub(fs.usage.bfree);
-    let percent_used = bused as f64 / (bused + fs.usage.bavail) as f64;
+  if let Some(root) = &args.kubelet {
+    if args.command.is_some() || args.format != Format::Table {
+      bail!("--kubelet only applies to the filesystem table");
+    }
+    return kubelet::run(root, &config, &args.size_format(&config));
+  }
 
-    let formatter = make_format(
-      FormatSizeOptions::from(BINARY)
-        .space_after_value(false)
-        .decimal_places(1),
+  let bundle;
+  let imported;
+  let mut output = app::Output::new(args, &config);
+  let collector: &dyn app::Collector = match &args.command {
+    Some(Command::Doctor { collect }) => return bundle::collect(collect),
+    Some(Command::Generate { out_dir }) => return generate::run(out_dir),
+    Some(Command::Config { .. } | Command::BalanceHelper { .. }) => {
+      unreachable!("handled before loading the settings")
+    }
+    Some(Command::Top { scan }) => return scan::run(ScanKind::Top, scan, &args.size_format(&config)),
+    Some(Command::Users { scan }) => {
+      return scan::run(ScanKind::Users, scan, &args.size_format(&config))
+    }
+    Some(Command::Analyze { bundle: path }) => {
+      bundle = app::Bundle::new(path);
+      &bundle
+    }
+    Some(Command::ImportDf { file }) => {
+      imported = app::Imported { path: file };
+      &imported
+    }
+    None => {
+      output = output.reload_with(watched);
+      &local
+    }
+  };
+  let report = app::run(&options, collector, &mut output)?;
+  if args.command.is_none() {
+    hook::run(
+      &config,
+      &report.filesystems,
+      &args.size_format(&config),
+      args.notify,
     );
-
-    Paragraph::new(fs.mount_info.dev_name.clone()).render(a_fs, frame);
-    Paragraph::new(format!(
-      "{:>9}",
-      formatter(fs.usage.blocks * fs.usage.blocksize,)
-    ))
-    .render(a_size, frame);
-    Paragraph::new(format!("{:>9}", formatter(bused * fs.usage.blocksize,))).render(a_used, frame);
-    Paragraph::new(format!(
-      "{:>9}",
-      formatter(fs.usage.bavail * fs.usage.blocksize,)
-    ))
-    .render(a_avail, frame);
-    Paragraph::new(fs.mount_info.mount_dir.clone()).render(a_dir, frame);
-    LineGauge::default()
-      .filled_style(
-        Style::default()
-          .fg(if percent_used > settings.high_threshold() {
-            Color::Red
-          } else if percent_used > settings.medium_threshold() {
-            Color::Yellow
-          } else {
-            Color::Green
-          })
-          .add_modifier(Modifier::BOLD),
-      )
-      .line_set(symbols::line::DOUBLE)
-      .unfilled_style(Style::default().fg(Color::DarkGray))
-      .label(format!("{:>3}%", (100.0 * percent_used).round()))
-      .ratio(percent_used)
-      .render(a_percent, frame);
-  })?;
+    history::save(&report);
+  }
+  if args.format == Format::Nagios && report.filesystems.is_empty() {
+    std::process::exit(3);
+  }
+  if args.check || args.format == Format::Nagios {
+    std::process::exit(report.worst().exit_code());
+  }
   Ok(())
 }
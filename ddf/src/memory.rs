@@ -0,0 +1,90 @@
+//! RAM accounting relevant to tmpfs sizing.
+//!
+//! tmpfs lives in RAM, but on ZFS systems `MemAvailable` doesn't include the ARC although most of
+//! it is released under memory pressure, so the naive numbers understate what tmpfs can use.
+
+use crate::{filesystem::Filesystem, units::SizeFormat};
+
+const MEMINFO: &str = "/proc/meminfo";
+const ARCSTATS: &str = "/proc/spl/kstat/zfs/arcstats";
+
+/// Read a `/proc/meminfo` field, in bytes.
+fn meminfo_field(meminfo: &str, name: &str) -> Option<u64> {
+  meminfo.lines().find_map(|line| {
+    let (key, value) = line.split_once(':')?;
+    if key != name {
+      return None;
+    }
+    let kib: u64 = value.split_whitespace().next()?.parse().ok()?;
+    Some(kib * 1024)
+  })
+}
+
+/// Read a `arcstats` field. Lines are `name type data` after two header lines.
+fn arcstats_field(arcstats: &str, name: &str) -> Option<u64> {
+  arcstats.lines().find_map(|line| {
+    let mut fields = line.split_whitespace();
+    if fields.next()? != name {
+      return None;
+    }
+    fields.nth(1)?.parse().ok()
+  })
+}
+
+/// ARC bytes that can be reclaimed: the current size above its minimum target.
+fn evictable_arc(arcstats: &str) -> Option<u64> {
+  let size = arcstats_field(arcstats, "size")?;
+  let c_min = arcstats_field(arcstats, "c_min").unwrap_or_default();
+  Some(size.saturating_sub(c_min))
+}
+
+/// Footnote about the RAM realistically available to the tmpfs mounts, when ZFS is in use.
+pub(crate) fn tmpfs_note(filesystems: &[Filesystem], size_format: &SizeFormat) -> Option<String> {
+  if !filesystems
+    .iter()
+    .any(|fs| fs.mount_info.fs_type == "tmpfs")
+  {
+    return None;
+  }
+  let arcstats = std::fs::read_to_string(ARCSTATS).ok()?;
+  let meminfo = std::fs::read_to_string(MEMINFO).ok()?;
+  let available = meminfo_field(&meminfo, "MemAvailable")?;
+  let arc = arcstats_field(&arcstats, "size")?;
+  let evictable = evictable_arc(&arcstats)?;
+  if arc == 0 {
+    return None;
+  }
+  Some(format!(
+    "note: ZFS ARC holds {} ({} evictable): about {} of RAM is available to tmpfs, not the {} of MemAvailable",
+    size_format.format(arc),
+    size_format.format(evictable),
+    size_format.format(available + evictable),
+    size_format.format(available),
+  ))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const ARC: &str = "9 1 0x01 147 39984 5259603498 63797857781509
+name                            type data
+hits                            4    1029013
+c_min                           4    1053125120
+size                            4    4294967296
+";
+
+  #[test]
+  fn test_arcstats() {
+    assert_eq!(arcstats_field(ARC, "size"), Some(4294967296));
+    assert_eq!(arcstats_field(ARC, "misses"), None);
+    assert_eq!(evictable_arc(ARC), Some(4294967296 - 1053125120));
+  }
+
+  #[test]
+  fn test_meminfo() {
+    let meminfo = "MemTotal:       32768000 kB\nMemAvailable:    1024 kB\n";
+    assert_eq!(meminfo_field(meminfo, "MemAvailable"), Some(1024 * 1024));
+    assert_eq!(meminfo_field(meminfo, "MemFree"), None);
+  }
+}
@@ -0,0 +1,82 @@
+//! Notifications of mount table changes, so watch and TUI modes refresh as soon as a filesystem
+//! is mounted or unmounted.
+//!
+//! On Linux `/proc/self/mountinfo` reports `POLLPRI` on every change of the mount table. Elsewhere
+//! no change is ever reported and the periodic refresh does the work.
+
+use std::{
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+  thread,
+  time::{Duration, Instant},
+};
+use tracing::debug;
+
+/// How often sleeps check for a change.
+const SLICE: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Default)]
+pub(crate) struct MountWatcher {
+  changed: Arc<AtomicBool>,
+}
+
+impl MountWatcher {
+  /// Start watching the mount table in a background thread.
+  pub(crate) fn spawn() -> Self {
+    let watcher = Self::default();
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+      let changed = watcher.changed.clone();
+      thread::spawn(move || {
+        if let Err(error) = poll_mountinfo(&changed) {
+          debug!("Stop watching the mount table: {error}");
+        }
+      });
+    }
+    watcher
+  }
+
+  /// Whether the mount table changed since the last call.
+  pub(crate) fn take_change(&self) -> bool {
+    self.changed.swap(false, Ordering::Relaxed)
+  }
+
+  /// Sleep for `timeout`, or less when the mount table changes.
+  pub(crate) fn sleep(&self, timeout: Duration) {
+    let deadline = Instant::now() + timeout;
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+      if self.take_change() {
+        return;
+      }
+      thread::sleep(remaining.min(SLICE));
+    }
+  }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn poll_mountinfo(changed: &AtomicBool) -> std::io::Result<()> {
+  use crate::fsext::LINUX_MOUNTINFO;
+  use std::{fs::File, io, os::fd::AsRawFd};
+
+  let file = File::open(LINUX_MOUNTINFO)?;
+  loop {
+    let mut fd = libc::pollfd {
+      fd: file.as_raw_fd(),
+      events: libc::POLLPRI,
+      revents: 0,
+    };
+    if unsafe { libc::poll(&mut fd, 1, -1) } < 0 {
+      let error = io::Error::last_os_error();
+      if error.kind() == io::ErrorKind::Interrupted {
+        continue;
+      }
+      return Err(error);
+    }
+    if fd.revents & (libc::POLLPRI | libc::POLLERR) != 0 {
+      debug!("The mount table changed");
+      changed.store(true, Ordering::Relaxed);
+    }
+  }
+}
@@ -0,0 +1,653 @@
+//! Machine readable outputs.
+//!
+//! Every format is built from the same [`Report`] model, so they don't drift apart.
+
+use crate::{
+  filesystem::Filesystem,
+  fsext::RawStatfs,
+  host::hostname,
+  settings::{Settings, Severity, Thresholds},
+  units::SizeFormat,
+};
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::{
+  io::Write,
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Output format.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub(crate) enum Format {
+  /// Table with usage gauges
+  #[default]
+  Table,
+  /// JSON document
+  Json,
+  /// YAML document, with the same structure as the JSON one
+  Yaml,
+  /// MessagePack encoding of the JSON document, with named fields
+  Msgpack,
+  /// CBOR encoding of the JSON document
+  Cbor,
+  /// Comma separated values with a header line
+  Csv,
+  /// One JSON object per line and per filesystem, streamed as they are read
+  Ndjson,
+  /// Markdown table with human readable sizes, for issues and wikis
+  Markdown,
+  /// Prometheus text exposition format, for the node_exporter textfile collector
+  Prometheus,
+  /// Nagios and Icinga plugin status line with performance data, exiting with its status
+  Nagios,
+}
+
+/// When and where a report was collected.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct Sample {
+  /// RFC 3339 UTC timestamp.
+  pub(crate) timestamp: String,
+  /// Seconds since the Unix epoch.
+  pub(crate) epoch: u64,
+  pub(crate) hostname: String,
+  /// Version of ddf that produced the report.
+  pub(crate) version: &'static str,
+}
+
+impl Sample {
+  /// Sample taken now, with the host name anonymized as set in `settings`.
+  pub(crate) fn now(settings: &Settings) -> Self {
+    let now = SystemTime::now();
+    Self {
+      timestamp: humantime::format_rfc3339_seconds(now).to_string(),
+      epoch: now
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default(),
+      hostname: settings.hostname_label(hostname()),
+      version: env!("CARGO_PKG_VERSION"),
+    }
+  }
+}
+
+/// One filesystem of a report. Sizes are in bytes.
+#[derive(Debug, Clone, Default, Serialize)]
+pub(crate) struct Record {
+  /// Identifier stable across mount point changes, see [`Filesystem::id`].
+  pub(crate) id: String,
+  pub(crate) source: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) alias: Option<String>,
+  pub(crate) fstype: String,
+  pub(crate) size: Option<u64>,
+  pub(crate) used: Option<u64>,
+  pub(crate) avail: Option<u64>,
+  /// Used percentage of the space usable by unprivileged users.
+  pub(crate) pcent: Option<f64>,
+  /// Set when `statfs` returned degenerate values.
+  #[serde(skip_serializing_if = "std::ops::Not::not")]
+  pub(crate) suspicious: bool,
+  pub(crate) target: String,
+  /// Untouched `statvfs` fields, with `--raw`.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) raw: Option<RawStatfs>,
+  /// Usage level relative to the thresholds of the filesystem, none when the usage is unknown.
+  #[serde(skip)]
+  pub(crate) severity: Option<Severity>,
+  #[serde(skip)]
+  pub(crate) thresholds: Option<Thresholds>,
+}
+
+impl Record {
+  pub(crate) fn new(fs: &Filesystem, settings: &Settings) -> Self {
+    Self {
+      id: settings.id_label(fs.id()),
+      source: settings.source_label(&fs.mount_info).into_owned(),
+      alias: settings.alias(&fs.mount_info).map(str::to_string),
+      fstype: fs.mount_info.fs_type.clone(),
+      size: fs.size(),
+      used: fs.used(),
+      avail: fs.avail(),
+      pcent: fs.used_ratio().map(|ratio| 100.0 * ratio),
+      suspicious: fs.usage.is_suspicious(),
+      target: settings.mount_label(&fs.mount_info).into_owned(),
+      raw: fs.usage.raw.clone(),
+      severity: fs.used_ratio().map(|ratio| settings.severity(fs, ratio)),
+      thresholds: Some(settings.thresholds(&fs.mount_info.mount_dir, &fs.mount_info.fs_type)),
+    }
+  }
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Report {
+  pub(crate) sample: Sample,
+  pub(crate) filesystems: Vec<Record>,
+}
+
+impl Report {
+  pub(crate) fn new(filesystems: &[Filesystem], settings: &Settings) -> Self {
+    Self {
+      sample: Sample::now(settings),
+      filesystems: filesystems
+        .iter()
+        .map(|fs| Record::new(fs, settings))
+        .collect(),
+    }
+  }
+
+  /// Highest usage level of the filesystems, `Low` when there are none.
+  pub(crate) fn worst(&self) -> Severity {
+    self
+      .filesystems
+      .iter()
+      .filter_map(|record| record.severity)
+      .max()
+      .unwrap_or(Severity::Low)
+  }
+}
+
+fn csv_field(value: &str) -> String {
+  if value.contains([',', '"', '\n', '\r']) {
+    format!("\"{}\"", value.replace('"', "\"\""))
+  } else {
+    value.to_string()
+  }
+}
+
+fn csv_option<T: ToString>(value: Option<T>) -> String {
+  value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+fn write_csv<W: Write>(report: &Report, out: &mut W) -> Result<()> {
+  writeln!(
+    out,
+    "timestamp,epoch,hostname,version,id,source,alias,fstype,size,used,avail,pcent,target"
+  )?;
+  let sample = &report.sample;
+  for record in &report.filesystems {
+    writeln!(
+      out,
+      "{},{},{},{},{},{},{},{},{},{},{},{},{}",
+      sample.timestamp,
+      sample.epoch,
+      csv_field(&sample.hostname),
+      sample.version,
+      csv_field(&record.id),
+      csv_field(&record.source),
+      csv_field(record.alias.as_deref().unwrap_or_default()),
+      csv_field(&record.fstype),
+      csv_option(record.size),
+      csv_option(record.used),
+      csv_option(record.avail),
+      csv_option(record.pcent.map(|pcent| format!("{pcent:.1}"))),
+      csv_field(&record.target),
+    )?;
+  }
+  Ok(())
+}
+
+/// NDJSON line: the sample fields followed by the record fields.
+#[derive(Serialize)]
+struct Line<'a> {
+  #[serde(flatten)]
+  sample: &'a Sample,
+  #[serde(flatten)]
+  record: &'a Record,
+}
+
+/// Write one NDJSON line and flush it, to stream the filesystems as they are read.
+pub(crate) fn write_ndjson_line<W: Write>(
+  sample: &Sample,
+  record: &Record,
+  out: &mut W,
+) -> Result<()> {
+  serde_json::to_writer(&mut *out, &Line { sample, record })?;
+  writeln!(out)?;
+  out.flush()?;
+  Ok(())
+}
+
+/// Label value with the backslashes, quotes and newlines escaped.
+fn prometheus_label(value: &str) -> String {
+  value
+    .replace('\\', "\\\\")
+    .replace('"', "\\\"")
+    .replace('\n', "\\n")
+}
+
+fn write_prometheus<W: Write>(report: &Report, out: &mut W) -> Result<()> {
+  type Metric = fn(&Record) -> Option<f64>;
+  let metrics: [(&str, &str, Metric); 3] = [
+    (
+      "ddf_filesystem_size_bytes",
+      "Filesystem size in bytes.",
+      |r| r.size.map(|v| v as f64),
+    ),
+    (
+      "ddf_filesystem_avail_bytes",
+      "Filesystem space available to unprivileged users in bytes.",
+      |r| r.avail.map(|v| v as f64),
+    ),
+    (
+      "ddf_filesystem_used_ratio",
+      "Ratio of the space usable by unprivileged users that is used.",
+      |r| r.pcent.map(|pcent| pcent / 100.0),
+    ),
+  ];
+  writeln!(
+    out,
+    "# HELP ddf_info Host and version of ddf that collected the sample."
+  )?;
+  writeln!(out, "# TYPE ddf_info gauge")?;
+  writeln!(
+    out,
+    "ddf_info{{hostname=\"{}\",version=\"{}\"}} 1",
+    prometheus_label(&report.sample.hostname),
+    prometheus_label(report.sample.version),
+  )?;
+  writeln!(
+    out,
+    "# HELP ddf_sample_timestamp_seconds Time the sample was collected, in seconds since the Unix epoch."
+  )?;
+  writeln!(out, "# TYPE ddf_sample_timestamp_seconds gauge")?;
+  writeln!(out, "ddf_sample_timestamp_seconds {}", report.sample.epoch)?;
+  for (name, help, value) in metrics {
+    writeln!(out, "# HELP {name} {help}")?;
+    writeln!(out, "# TYPE {name} gauge")?;
+    for record in &report.filesystems {
+      if let Some(value) = value(record) {
+        let alias = record.alias.as_deref().map_or(String::new(), |alias| {
+          format!(",alias=\"{}\"", prometheus_label(alias))
+        });
+        writeln!(
+          out,
+          "{name}{{device=\"{}\",mountpoint=\"{}\",fstype=\"{}\"{alias}}} {value}",
+          prometheus_label(&record.source),
+          prometheus_label(&record.target),
+          prometheus_label(&record.fstype),
+        )?;
+      }
+    }
+  }
+  Ok(())
+}
+
+/// Write `report` to `out` as the output of a Nagios plugin like `check_disk`: the status and the
+/// free space of each filesystem, then their used space with the thresholds as performance data,
+/// labelled by the alias of the filesystem or its mount directory.
+pub(crate) fn write_nagios<W: Write>(
+  report: &Report,
+  size_format: &SizeFormat,
+  out: &mut W,
+) -> Result<()> {
+  if report.filesystems.is_empty() {
+    writeln!(out, "DISK UNKNOWN - no filesystems")?;
+    return Ok(());
+  }
+  let status = match report.worst() {
+    Severity::Low => "OK",
+    Severity::Medium => "WARNING",
+    Severity::High => "CRITICAL",
+  };
+  let free: Vec<String> = report
+    .filesystems
+    .iter()
+    .map(|record| {
+      let pcent = record
+        .pcent
+        .map_or("?".to_string(), |pcent| format!("{:.0}%", 100.0 - pcent));
+      format!(
+        "{} {} ({pcent})",
+        record.target,
+        size_format.format_opt(record.avail)
+      )
+    })
+    .collect();
+  let perfdata: Vec<String> = report
+    .filesystems
+    .iter()
+    .filter_map(|record| {
+      let used = record.used?;
+      let usable = used + record.avail.unwrap_or_default();
+      let [warn, crit] = record
+        .thresholds
+        .map_or([String::new(), String::new()], |thresholds| {
+          [thresholds.medium, thresholds.high]
+            .map(|threshold| threshold.used_bytes(usable).to_string())
+        });
+      let label = record
+        .alias
+        .as_deref()
+        .unwrap_or(&record.target)
+        .replace('\'', "''");
+      Some(format!(
+        "'{label}'={used}B;{warn};{crit};0;{}",
+        record.size.unwrap_or_default()
+      ))
+    })
+    .collect();
+  write!(out, "DISK {status} - free space: {}", free.join("; "))?;
+  if !perfdata.is_empty() {
+    write!(out, " | {}", perfdata.join(" "))?;
+  }
+  writeln!(out)?;
+  Ok(())
+}
+
+fn markdown_cell(value: &str) -> String {
+  value.replace('|', "\\|")
+}
+
+/// Write `report` to `out` as a Markdown table, with an emoji per row for the severity of the
+/// usage with `emoji`.
+pub(crate) fn write_markdown<W: Write>(
+  report: &Report,
+  size_format: &SizeFormat,
+  emoji: bool,
+  out: &mut W,
+) -> Result<()> {
+  let header = ["Filesystem", "Size", "Used", "Avail", "Use%", "Mounted on"];
+  let right_aligned = [false, true, true, true, true, false];
+  let rows: Vec<[String; 6]> = report
+    .filesystems
+    .iter()
+    .map(|record| {
+      let mut pcent = record
+        .pcent
+        .map(|pcent| format!("{pcent:.0}%"))
+        .unwrap_or_else(|| "?".to_string());
+      if emoji {
+        // From the record: its target may be anonymized, which the mount rules don't match.
+        let emoji = match record.severity {
+          Some(Severity::High) => "🔴",
+          Some(Severity::Medium) => "🟡",
+          Some(Severity::Low) => "🟢",
+          None => "⚪",
+        };
+        pcent = format!("{emoji} {pcent}");
+      }
+      [
+        markdown_cell(record.alias.as_deref().unwrap_or(&record.source)),
+        size_format.format_opt(record.size),
+        size_format.format_opt(record.used),
+        size_format.format_opt(record.avail),
+        pcent,
+        markdown_cell(&record.target),
+      ]
+    })
+    .collect();
+  let mut widths = header.map(|title| title.chars().count().max(3));
+  for row in &rows {
+    for (width, cell) in widths.iter_mut().zip(row) {
+      *width = (*width).max(cell.chars().count());
+    }
+  }
+  let line = |cells: &[String]| {
+    let cells: Vec<String> = cells
+      .iter()
+      .zip(widths.iter().zip(right_aligned))
+      .map(|(cell, (&width, right))| {
+        if right {
+          format!("{cell:>width$}")
+        } else {
+          format!("{cell:<width$}")
+        }
+      })
+      .collect();
+    format!("| {} |", cells.join(" | "))
+  };
+  writeln!(out, "{}", line(&header.map(str::to_string)))?;
+  let separator: Vec<String> = widths
+    .iter()
+    .zip(right_aligned)
+    .map(|(&width, right)| {
+      if right {
+        format!("{}:", "-".repeat(width - 1))
+      } else {
+        format!(":{}", "-".repeat(width - 1))
+      }
+    })
+    .collect();
+  writeln!(out, "{}", line(&separator))?;
+  for row in &rows {
+    writeln!(out, "{}", line(row))?;
+  }
+  Ok(())
+}
+
+/// Write `report` to `out` in the POSIX `df -P` format, with sizes in `block_size` blocks rounded
+/// up. Unknown values print as `-`. Aliases are left out: the format has the device name only.
+pub(crate) fn write_posix<W: Write>(report: &Report, block_size: u64, out: &mut W) -> Result<()> {
+  let blocks = |bytes: Option<u64>| {
+    bytes.map_or_else(
+      || "-".to_string(),
+      |bytes| bytes.div_ceil(block_size).to_string(),
+    )
+  };
+  writeln!(
+    out,
+    "Filesystem {block_size}-blocks Used Available Capacity Mounted on"
+  )?;
+  for record in &report.filesystems {
+    writeln!(
+      out,
+      "{} {} {} {} {} {}",
+      record.source,
+      blocks(record.size),
+      blocks(record.used),
+      blocks(record.avail),
+      record
+        .pcent
+        .map_or_else(|| "-".to_string(), |pcent| format!("{}%", pcent.ceil())),
+      record.target,
+    )?;
+  }
+  Ok(())
+}
+
+/// Whether `error` comes from writing to a pipe closed by its reader, like `ddf | head -1`: not an
+/// error for a command line tool.
+pub(crate) fn is_broken_pipe(error: &anyhow::Error) -> bool {
+  error.chain().any(|cause| {
+    // `serde_json` hides the I/O error from the chain, only giving its kind.
+    cause
+      .downcast_ref::<std::io::Error>()
+      .map(std::io::Error::kind)
+      .or_else(|| cause.downcast_ref::<serde_json::Error>()?.io_error_kind())
+      == Some(std::io::ErrorKind::BrokenPipe)
+  })
+}
+
+/// Write `report` to `out` in a machine readable `format`.
+pub(crate) fn write<W: Write>(format: Format, report: &Report, out: &mut W) -> Result<()> {
+  match format {
+    Format::Table => unreachable!("tables are rendered by the terminal"),
+    Format::Markdown => unreachable!("markdown tables need the size format"),
+    Format::Ndjson => {
+      for record in &report.filesystems {
+        write_ndjson_line(&report.sample, record, out)?;
+      }
+    }
+    Format::Json => {
+      serde_json::to_writer_pretty(&mut *out, report)?;
+      writeln!(out)?;
+    }
+    Format::Yaml => serde_yaml::to_writer(&mut *out, report)?,
+    // Named, since optional fields are skipped and positions wouldn't match.
+    Format::Msgpack => rmp_serde::encode::write_named(out, report)?,
+    Format::Cbor => ciborium::into_writer(report, out)?,
+    Format::Csv => write_csv(report, out)?,
+    Format::Prometheus => write_prometheus(report, out)?,
+    Format::Nagios => unreachable!("the Nagios output needs the size format"),
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{settings::Threshold, units::ByteSize};
+
+  #[test]
+  fn test_binary_formats() {
+    let report = Report {
+      sample: Sample::now(&Settings::default()),
+      filesystems: vec![Record {
+        source: "/dev/sda1".to_string(),
+        size: Some(1000),
+        ..Default::default()
+      }],
+    };
+    for format in [Format::Msgpack, Format::Cbor] {
+      let mut out = vec![];
+      write(format, &report, &mut out).unwrap();
+      let value: serde_json::Value = match format {
+        Format::Msgpack => rmp_serde::from_slice(&out).unwrap(),
+        _ => ciborium::from_reader(out.as_slice()).unwrap(),
+      };
+      assert_eq!(value["filesystems"][0]["source"], "/dev/sda1");
+      assert_eq!(value["filesystems"][0]["size"], 1000);
+    }
+  }
+
+  #[test]
+  fn test_markdown() {
+    let record = |source: &str, pcent: Option<f64>| Record {
+      source: source.to_string(),
+      severity: pcent.map(|_| Severity::High),
+      fstype: "ext4".to_string(),
+      size: Some(2048),
+      used: Some(1024),
+      avail: Some(1024),
+      pcent,
+      target: "/mnt/a|b".to_string(),
+      ..Default::default()
+    };
+    let report = Report {
+      sample: Sample::now(&Settings::default()),
+      filesystems: vec![record("/dev/sda1", Some(95.0)), record("/dev/sdb", None)],
+    };
+    let mut out = vec![];
+    write_markdown(&report, &SizeFormat::Human, true, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[1].starts_with("| :---------"));
+    assert!(lines[1].contains("---: |"));
+    assert!(lines[2].contains("🔴 95%"));
+    assert!(lines[2].contains(r"| /mnt/a\|b "));
+    assert!(lines[3].contains("⚪ ?"));
+  }
+
+  #[test]
+  fn test_nagios() {
+    let record = |target: &str, used: u64, severity| Record {
+      size: Some(1000),
+      used: Some(used),
+      avail: Some(1000 - used),
+      pcent: Some(used as f64 / 10.0),
+      target: target.to_string(),
+      severity: Some(severity),
+      thresholds: Some(Thresholds {
+        medium: Threshold::Ratio(0.75),
+        high: Threshold::Free(ByteSize(100)),
+      }),
+      ..Default::default()
+    };
+    let report = Report {
+      sample: Sample::now(&Settings::default()),
+      filesystems: vec![
+        record("/", 500, Severity::Low),
+        Record {
+          alias: Some("Bob's data".to_string()),
+          ..record("/my data", 800, Severity::Medium)
+        },
+      ],
+    };
+    let mut out = vec![];
+    write_nagios(&report, &SizeFormat::Human, &mut out).unwrap();
+    assert_eq!(
+      String::from_utf8(out).unwrap(),
+      "DISK WARNING - free space: / 500B (50%); /my data 200B (20%) | \
+       '/'=500B;750;900;0;1000 'Bob''s data'=800B;750;900;0;1000\n"
+    );
+  }
+
+  #[test]
+  fn test_posix() {
+    let report = Report {
+      sample: Sample::now(&Settings::default()),
+      filesystems: vec![Record {
+        source: "/dev/sda1".to_string(),
+        fstype: "ext4".to_string(),
+        size: Some(10240),
+        used: Some(3000),
+        avail: None,
+        pcent: Some(29.3),
+        target: "/".to_string(),
+        ..Default::default()
+      }],
+    };
+    let mut out = vec![];
+    write_posix(&report, 512, &mut out).unwrap();
+    assert_eq!(
+      String::from_utf8(out).unwrap(),
+      "Filesystem 512-blocks Used Available Capacity Mounted on\n/dev/sda1 20 6 - 30% /\n"
+    );
+  }
+
+  #[test]
+  fn test_broken_pipe() {
+    let report = Report {
+      sample: Sample::now(&Settings::default()),
+      filesystems: vec![Record::default(); 200],
+    };
+    for format in [Format::Json, Format::Csv, Format::Ndjson] {
+      let (reader, mut writer) = std::io::pipe().unwrap();
+      drop(reader);
+      let error = write(format, &report, &mut writer).unwrap_err();
+      assert!(is_broken_pipe(&error), "{format:?}: {error}");
+    }
+    assert!(!is_broken_pipe(&anyhow::anyhow!("other")));
+  }
+
+  #[test]
+  fn test_prometheus() {
+    let report = Report {
+      sample: Sample::now(&Settings::default()),
+      filesystems: vec![Record {
+        source: "/dev/sda1".to_string(),
+        fstype: "ext4".to_string(),
+        size: Some(1000),
+        pcent: Some(25.0),
+        target: "/mnt/\"odd\"".to_string(),
+        ..Default::default()
+      }],
+    };
+    let mut out = vec![];
+    write_prometheus(&report, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let labels = r#"{device="/dev/sda1",mountpoint="/mnt/\"odd\"",fstype="ext4"}"#;
+    assert!(out.contains(&format!("ddf_filesystem_size_bytes{labels} 1000\n")));
+    assert!(out.contains(&format!("ddf_filesystem_used_ratio{labels} 0.25\n")));
+    assert!(!out.contains("ddf_filesystem_avail_bytes{"));
+    assert!(out.contains("# TYPE ddf_filesystem_avail_bytes gauge\n"));
+    assert!(out.contains(&format!(
+      "ddf_info{{hostname=\"{}\",version=\"{}\"}} 1\n",
+      report.sample.hostname, report.sample.version
+    )));
+    assert!(out.contains(&format!(
+      "ddf_sample_timestamp_seconds {}\n",
+      report.sample.epoch
+    )));
+
+    let mut report = report;
+    report.filesystems[0].alias = Some("Backup".to_string());
+    let mut out = vec![];
+    write_prometheus(&report, &mut out).unwrap();
+    assert!(String::from_utf8(out).unwrap().contains(
+      r#"ddf_filesystem_size_bytes{device="/dev/sda1",mountpoint="/mnt/\"odd\"",fstype="ext4",alias="Backup"} 1000"#
+    ));
+  }
+}
@@ -0,0 +1,180 @@
+//! Styles of the severities, degraded on terminals with few or no colors so the thresholds stay
+//! distinguishable on serial consoles and old terminals.
+
+use crate::settings::Severity;
+use ratatui::style::{Color, Modifier, Style};
+use std::{path::PathBuf, sync::OnceLock};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Palette {
+  /// 16 colors or more.
+  Full,
+  /// The 8 ANSI colors, without the bright ones.
+  Basic,
+  /// No colors: bold, underline and reverse video only.
+  Mono,
+}
+
+impl Palette {
+  /// Palette of the terminal ddf runs in, detected once.
+  pub(crate) fn current() -> Self {
+    static PALETTE: OnceLock<Palette> = OnceLock::new();
+    *PALETTE.get_or_init(|| Self::detect(|name| std::env::var(name).ok(), terminfo_colors))
+  }
+
+  /// Palette for the environment `var`, `colors` giving the number of colors of a `TERM` in the
+  /// terminfo database.
+  fn detect(var: impl Fn(&str) -> Option<String>, colors: impl Fn(&str) -> Option<i32>) -> Self {
+    if var("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+      return Self::Mono;
+    }
+    if var("COLORTERM").is_some_and(|value| ["truecolor", "24bit"].contains(&value.as_str())) {
+      return Self::Full;
+    }
+    let term = var("TERM").unwrap_or_default();
+    if term.is_empty() || term == "dumb" {
+      return Self::Mono;
+    }
+    match colors(&term) {
+      Some(colors) if colors >= 16 => Self::Full,
+      Some(colors) if colors >= 8 => Self::Basic,
+      Some(_) => Self::Mono,
+      // Unknown to terminfo: modern emulators with their own `TERM` don't always install one.
+      None => Self::Full,
+    }
+  }
+
+  /// Style of a used percentage or gauge.
+  pub(crate) fn severity(self, severity: Option<Severity>) -> Style {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    match (self, severity) {
+      (Self::Mono, Some(Severity::High)) => bold.add_modifier(Modifier::REVERSED),
+      (Self::Mono, Some(Severity::Medium)) => bold.add_modifier(Modifier::UNDERLINED),
+      (Self::Mono, _) => Style::default(),
+      (_, Some(Severity::High)) => bold.fg(Color::Red),
+      (_, Some(Severity::Medium)) => bold.fg(Color::Yellow),
+      (_, Some(Severity::Low)) => bold.fg(Color::Green),
+      (_, None) => self.dim(),
+    }
+  }
+
+  /// Style of the available space in the stacked bars.
+  pub(crate) fn available(self) -> Style {
+    match self {
+      Self::Mono => Style::default(),
+      _ => Style::default().fg(Color::Green),
+    }
+  }
+
+  /// Style of the values that changed since the previous refresh.
+  pub(crate) fn changed(self) -> Style {
+    match self {
+      Self::Mono => Style::default().add_modifier(Modifier::ITALIC),
+      _ => Style::default()
+        .fg(Color::Cyan)
+        .add_modifier(Modifier::BOLD),
+    }
+  }
+
+  /// Style of secondary text and of the unfilled part of the gauges.
+  pub(crate) fn dim(self) -> Style {
+    match self {
+      Self::Full => Style::default().fg(Color::DarkGray),
+      Self::Basic => Style::default().add_modifier(Modifier::DIM),
+      Self::Mono => Style::default(),
+    }
+  }
+}
+
+/// `colors` capability of `term` in the terminfo database.
+fn terminfo_colors(term: &str) -> Option<i32> {
+  let first = term.chars().next()?;
+  let mut dirs: Vec<PathBuf> = vec![];
+  dirs.extend(std::env::var_os("TERMINFO").map(PathBuf::from));
+  dirs.extend(std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".terminfo")));
+  if let Some(list) = std::env::var_os("TERMINFO_DIRS") {
+    dirs.extend(std::env::split_paths(&list));
+  }
+  dirs.extend(["/etc/terminfo", "/lib/terminfo", "/usr/share/terminfo"].map(PathBuf::from));
+  dirs.iter().find_map(|dir| {
+    // macOS names the subdirectories by the hexadecimal code of the first letter.
+    [first.to_string(), format!("{:x}", first as u32)]
+      .iter()
+      .find_map(|sub| std::fs::read(dir.join(sub).join(term)).ok())
+      .and_then(|entry| parse_colors(&entry))
+  })
+}
+
+/// Index of `colors` among the numeric capabilities.
+const COLORS: usize = 13;
+
+/// `colors` capability of a compiled terminfo entry, `-1` when absent.
+fn parse_colors(entry: &[u8]) -> Option<i32> {
+  let short = |at: usize| Some(i16::from_le_bytes(entry.get(at..at + 2)?.try_into().ok()?));
+  // Numbers are 16 bits in the legacy format, 32 bits in the extended one.
+  let number_size = match short(0)? {
+    0o432 => 2,
+    0o1036 => 4,
+    _ => return None,
+  };
+  let names_size = short(2)? as usize;
+  let booleans = short(4)? as usize;
+  let numbers = short(6)? as usize;
+  if COLORS >= numbers {
+    return Some(-1);
+  }
+  // The numbers are aligned on an even offset.
+  let start = (12 + names_size + booleans).next_multiple_of(2) + COLORS * number_size;
+  let bytes = entry.get(start..start + number_size)?;
+  Some(match number_size {
+    2 => i16::from_le_bytes(bytes.try_into().ok()?).into(),
+    _ => i32::from_le_bytes(bytes.try_into().ok()?),
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_colors() {
+    let mut entry = vec![];
+    for value in [0o432, 4, 1, 14, 0, 0] {
+      entry.extend(i16::to_le_bytes(value));
+    }
+    entry.extend(b"vt\0\0");
+    entry.push(1);
+    entry.push(0);
+    for value in 0..14 {
+      entry.extend(i16::to_le_bytes(if value == 13 { 8 } else { -1 }));
+    }
+    assert_eq!(parse_colors(&entry), Some(8));
+    entry[6] = 13;
+    assert_eq!(parse_colors(&entry), Some(-1));
+    assert_eq!(parse_colors(b"garbage"), None);
+  }
+
+  #[test]
+  fn test_detect() {
+    let detect = |vars: &'static [(&str, &str)], colors: Option<i32>| {
+      let var = |name: &str| {
+        vars
+          .iter()
+          .find(|(key, _)| *key == name)
+          .map(|(_, value)| value.to_string())
+      };
+      Palette::detect(var, |_| colors)
+    };
+    assert_eq!(
+      detect(&[("TERM", "xterm-256color")], Some(256)),
+      Palette::Full
+    );
+    assert_eq!(detect(&[("TERM", "vt220")], Some(-1)), Palette::Mono);
+    assert_eq!(detect(&[("TERM", "linux")], Some(8)), Palette::Basic);
+    assert_eq!(detect(&[("TERM", "dumb")], None), Palette::Mono);
+    let no_color = &[("TERM", "xterm-256color"), ("NO_COLOR", "1")];
+    assert_eq!(detect(no_color, Some(256)), Palette::Mono);
+    let truecolor = &[("TERM", "linux"), ("COLORTERM", "truecolor")];
+    assert_eq!(detect(truecolor, Some(8)), Palette::Full);
+  }
+}
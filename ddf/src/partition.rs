@@ -0,0 +1,138 @@
+//! Position of a partition on its disk, read from sysfs, to tell whether it can grow in place.
+
+use std::{fs, path::Path};
+
+/// Unit of the sysfs `start` and `size` attributes, whatever the sector size of the disk.
+const SECTOR: u64 = 512;
+/// Gaps smaller than this are partition alignment, not space to grow into.
+const ALIGNMENT: u64 = 1 << 20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Partition {
+  pub(crate) name: String,
+  pub(crate) number: u32,
+  /// Offset and length in bytes.
+  pub(crate) start: u64,
+  pub(crate) size: u64,
+}
+
+impl Partition {
+  fn end(&self) -> u64 {
+    self.start + self.size
+  }
+}
+
+/// A partition among the others of its disk.
+#[derive(Debug, PartialEq)]
+pub(crate) struct DiskLayout {
+  pub(crate) disk: String,
+  /// Size of the disk in bytes.
+  pub(crate) disk_size: u64,
+  /// Partitions of the disk, sorted by offset.
+  pub(crate) partitions: Vec<Partition>,
+  /// Index of the partition of interest in `partitions`.
+  pub(crate) index: usize,
+}
+
+impl DiskLayout {
+  fn new(disk: String, disk_size: u64, mut partitions: Vec<Partition>, name: &str) -> Option<Self> {
+    partitions.sort_by_key(|partition| partition.start);
+    let index = partitions
+      .iter()
+      .position(|partition| partition.name == name)?;
+    Some(Self {
+      disk,
+      disk_size,
+      partitions,
+      index,
+    })
+  }
+
+  pub(crate) fn partition(&self) -> &Partition {
+    &self.partitions[self.index]
+  }
+
+  pub(crate) fn previous(&self) -> Option<&Partition> {
+    self.partitions[..self.index].last()
+  }
+
+  pub(crate) fn next(&self) -> Option<&Partition> {
+    self.partitions.get(self.index + 1)
+  }
+
+  /// Unallocated bytes right after the partition, up to the next one or the end of the disk.
+  pub(crate) fn free_after(&self) -> u64 {
+    let limit = self.next().map_or(self.disk_size, |next| next.start);
+    limit.saturating_sub(self.partition().end())
+  }
+
+  /// Whether the partition can be grown without moving it or its neighbors.
+  pub(crate) fn growable(&self) -> bool {
+    self.free_after() >= ALIGNMENT
+  }
+}
+
+/// Read a sysfs number attribute of the block device directory `dir`.
+fn attribute(dir: &Path, name: &str) -> Option<u64> {
+  fs::read_to_string(dir.join(name)).ok()?.trim().parse().ok()
+}
+
+fn read_partition(dir: &Path) -> Option<Partition> {
+  Some(Partition {
+    name: dir.file_name()?.to_string_lossy().into_owned(),
+    number: attribute(dir, "partition")? as u32,
+    start: attribute(dir, "start")? * SECTOR,
+    size: attribute(dir, "size")? * SECTOR,
+  })
+}
+
+/// Layout of the disk of the partition with the `major:minor` number `dev_id`, none when it isn't
+/// a partition (whole disks, device mapper, network filesystems...).
+pub(crate) fn layout(dev_id: &str) -> Option<DiskLayout> {
+  if dev_id.is_empty() {
+    return None;
+  }
+  let dir = fs::canonicalize(Path::new("/sys/dev/block").join(dev_id)).ok()?;
+  let partition = read_partition(&dir)?;
+  let disk_dir = dir.parent()?;
+  let partitions = fs::read_dir(disk_dir)
+    .ok()?
+    .filter_map(|entry| read_partition(&entry.ok()?.path()))
+    .collect();
+  DiskLayout::new(
+    disk_dir.file_name()?.to_string_lossy().into_owned(),
+    attribute(disk_dir, "size")? * SECTOR,
+    partitions,
+    &partition.name,
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_free_after() {
+    const MIB: u64 = 1 << 20;
+    let partition = |name: &str, number, start, size| Partition {
+      name: name.to_string(),
+      number,
+      start: start * MIB,
+      size: size * MIB,
+    };
+    let partitions = vec![
+      partition("sda3", 3, 600, 100),
+      partition("sda1", 1, 1, 512),
+      partition("sda2", 2, 513, 50),
+    ];
+    let layout = |name| DiskLayout::new("sda".to_string(), 1000 * MIB, partitions.clone(), name);
+    let sda2 = layout("sda2").unwrap();
+    assert_eq!(sda2.previous().map(|p| p.number), Some(1));
+    assert_eq!(sda2.next().map(|p| p.number), Some(3));
+    assert_eq!(sda2.free_after(), 37 * MIB);
+    assert!(sda2.growable());
+    assert!(!layout("sda1").unwrap().growable());
+    assert_eq!(layout("sda3").unwrap().free_after(), 300 * MIB);
+    assert_eq!(layout("sdb1"), None);
+  }
+}
@@ -0,0 +1,15 @@
+//! Errors skipped while collecting the filesystems, turned into a failure by `--strict`.
+
+use std::sync::Mutex;
+
+static PROBLEMS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Record an error that was skipped to go on with partial data.
+pub(crate) fn report(problem: String) {
+  PROBLEMS.lock().unwrap().push(problem);
+}
+
+/// Errors recorded so far, oldest first.
+pub(crate) fn take() -> Vec<String> {
+  std::mem::take(&mut PROBLEMS.lock().unwrap())
+}
@@ -0,0 +1,302 @@
+//! Maintenance operations that move the numbers of a filesystem while they run: md resyncs and
+//! reshapes, btrfs balances, zfs scrubs and resilvers. Shown under the row of the filesystem.
+
+use crate::filesystem::Filesystem;
+use std::{
+  collections::HashMap,
+  env, fs,
+  path::Path,
+  process::Command,
+  sync::atomic::{AtomicBool, Ordering},
+};
+use tracing::debug;
+
+const MDSTAT: &str = "/proc/mdstat";
+
+/// Read the progress needing root through `sudo` when not root, set by `--sudo`.
+static SUDO: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn use_sudo() {
+  SUDO.store(true, Ordering::Relaxed);
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Progress {
+  /// `resync`, `balance`, `scrub`...
+  pub(crate) operation: String,
+  pub(crate) ratio: f64,
+}
+
+/// Progress of the md arrays from the content of `/proc/mdstat`, by array name (`md0`).
+fn parse_mdstat(mdstat: &str) -> HashMap<String, Progress> {
+  let mut progress = HashMap::new();
+  let mut array = None;
+  for line in mdstat.lines() {
+    if let Some((name, _)) = line.split_once(" : ") {
+      array = Some(name.trim().to_string());
+      continue;
+    }
+    // `[=>......]  resync =  8.6% (168230528/1953382464) finish=150.2min speed=198012K/sec`
+    let Some((before, after)) = line.split_once(" = ").filter(|_| line.contains('%')) else {
+      continue;
+    };
+    let Some(operation) = before.split_whitespace().last() else {
+      continue;
+    };
+    let percent = after.split('%').next().unwrap_or_default().trim();
+    if let (Some(array), Ok(percent)) = (&array, percent.parse::<f64>()) {
+      progress.insert(
+        array.clone(),
+        Progress {
+          operation: operation.to_string(),
+          ratio: percent / 100.0,
+        },
+      );
+    }
+  }
+  progress
+}
+
+/// Progress of the scrubs and resilvers from the output of `zpool status`, by pool name.
+fn parse_zpool_status(status: &str) -> HashMap<String, Progress> {
+  let mut progress = HashMap::new();
+  let mut pool = None;
+  let mut operation = None;
+  for line in status.lines().map(str::trim) {
+    if let Some(name) = line.strip_prefix("pool:") {
+      pool = Some(name.trim().to_string());
+      operation = None;
+    } else if let Some(scan) = line.strip_prefix("scan:") {
+      operation = scan
+        .contains("in progress")
+        .then(|| scan.split_whitespace().next().unwrap_or("scan").to_string());
+    } else if let (Some(pool), Some(operation)) = (&pool, &operation) {
+      // `0B repaired, 39.06% done, 00:23:00 to go`
+      let Some((before, _)) = line.split_once("% done") else {
+        continue;
+      };
+      let percent = before.rsplit([' ', ',']).next().unwrap_or_default();
+      if let Ok(percent) = percent.parse::<f64>() {
+        progress.insert(
+          pool.clone(),
+          Progress {
+            operation: operation.clone(),
+            ratio: percent / 100.0,
+          },
+        );
+      }
+    }
+  }
+  progress
+}
+
+/// Progress of the balance running on the btrfs mounted at `mount_dir`. Needs `CAP_SYS_ADMIN`.
+#[cfg(target_os = "linux")]
+fn btrfs_balance(mount_dir: &str) -> Option<Progress> {
+  use std::os::fd::AsRawFd;
+
+  /// `struct btrfs_ioctl_balance_args`, of which only the progress is read.
+  #[repr(C)]
+  struct BalanceArgs {
+    flags: u64,
+    state: u64,
+    /// The data, metadata and system `struct btrfs_balance_args` filters.
+    filters: [[u64; 17]; 3],
+    expected: u64,
+    considered: u64,
+    completed: u64,
+    unused: [u64; 72],
+  }
+  /// `_IOR(BTRFS_IOCTL_MAGIC, 34, struct btrfs_ioctl_balance_args)`
+  const BTRFS_IOC_BALANCE_PROGRESS: u64 = 0x8400_9422;
+
+  let dir = fs::File::open(mount_dir).ok()?;
+  let mut args = BalanceArgs {
+    flags: 0,
+    state: 0,
+    filters: [[0; 17]; 3],
+    expected: 0,
+    considered: 0,
+    completed: 0,
+    unused: [0; 72],
+  };
+  // SAFETY: the kernel writes at most the size of `BalanceArgs` encoded in the request.
+  let result = unsafe {
+    libc::ioctl(
+      dir.as_raw_fd(),
+      BTRFS_IOC_BALANCE_PROGRESS as _,
+      &mut args as *mut BalanceArgs,
+    )
+  };
+  // ENOTCONN when no balance is running.
+  if result != 0 || args.expected == 0 {
+    return None;
+  }
+  Some(Progress {
+    operation: "balance".to_string(),
+    ratio: args.completed as f64 / args.expected as f64,
+  })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn btrfs_balance(_mount_dir: &str) -> Option<Progress> {
+  None
+}
+
+/// Balances running on the btrfs mounted at `mount_dirs`, read by `ddf balance-helper` under
+/// `sudo` with `--sudo` when not root.
+fn btrfs_balances(mount_dirs: &[&str]) -> HashMap<String, Progress> {
+  #[cfg(unix)]
+  // SAFETY: `geteuid` has no preconditions.
+  let root = unsafe { libc::geteuid() } == 0;
+  #[cfg(not(unix))]
+  let root = true;
+  if root || !SUDO.load(Ordering::Relaxed) {
+    return mount_dirs
+      .iter()
+      .filter_map(|dir| Some((dir.to_string(), btrfs_balance(dir)?)))
+      .collect();
+  }
+  let output = env::current_exe().and_then(|exe| {
+    Command::new("sudo")
+      .arg("--")
+      .arg(exe)
+      .arg("balance-helper")
+      .args(mount_dirs)
+      .output()
+  });
+  match output {
+    Ok(output) if output.status.success() => parse_balances(&String::from_utf8_lossy(&output.stdout)),
+    Ok(output) => {
+      debug!("sudo ddf balance-helper: {}", output.status);
+      HashMap::new()
+    }
+    Err(error) => {
+      debug!("sudo ddf balance-helper: {error}");
+      HashMap::new()
+    }
+  }
+}
+
+/// Print the balances running on the btrfs mounted at `mount_dirs`, one `RATIO\tDIR` line each,
+/// for the unprivileged `ddf --sudo`.
+pub(crate) fn print_balances(mount_dirs: &[String]) {
+  for dir in mount_dirs {
+    if let Some(progress) = btrfs_balance(dir) {
+      println!("{}\t{dir}", progress.ratio);
+    }
+  }
+}
+
+/// Balances from the output of `ddf balance-helper`, by mount directory.
+fn parse_balances(output: &str) -> HashMap<String, Progress> {
+  output
+    .lines()
+    .filter_map(|line| {
+      let (ratio, dir) = line.split_once('\t')?;
+      let progress = Progress {
+        operation: "balance".to_string(),
+        ratio: ratio.parse().ok()?,
+      };
+      Some((dir.to_string(), progress))
+    })
+    .collect()
+}
+
+/// Name of the md array of a device name, `/dev/md/NAME` links resolved.
+fn md_array(dev_name: &str) -> Option<String> {
+  if !dev_name.starts_with("/dev/md") {
+    return None;
+  }
+  let path = fs::canonicalize(dev_name).unwrap_or_else(|_| dev_name.into());
+  Some(path.file_name()?.to_string_lossy().into_owned())
+}
+
+/// Operations running on `filesystems`, by mount directory. The sources are only read when one
+/// of the filesystems can have such an operation.
+pub(crate) fn running(filesystems: &[Filesystem]) -> HashMap<String, Progress> {
+  let mut running = HashMap::new();
+  let mut md = HashMap::new();
+  if filesystems
+    .iter()
+    .any(|fs| md_array(&fs.mount_info.dev_name).is_some())
+  {
+    md = parse_mdstat(&fs::read_to_string(Path::new(MDSTAT)).unwrap_or_default());
+  }
+  let mut zfs = HashMap::new();
+  if filesystems.iter().any(|fs| fs.mount_info.fs_type == "zfs") {
+    match Command::new("zpool").arg("status").output() {
+      Ok(output) => zfs = parse_zpool_status(&String::from_utf8_lossy(&output.stdout)),
+      Err(error) => debug!("zpool status: {error}"),
+    }
+  }
+  let btrfs: Vec<&str> = filesystems
+    .iter()
+    .filter(|fs| fs.mount_info.fs_type == "btrfs")
+    .map(|fs| fs.mount_info.mount_dir.as_str())
+    .collect();
+  let balances = if btrfs.is_empty() {
+    HashMap::new()
+  } else {
+    btrfs_balances(&btrfs)
+  };
+  for fs in filesystems {
+    let info = &fs.mount_info;
+    let progress = match info.fs_type.as_str() {
+      "btrfs" => balances.get(&info.mount_dir).cloned(),
+      "zfs" => info
+        .dev_name
+        .split('/')
+        .next()
+        .and_then(|pool| zfs.get(pool).cloned()),
+      _ => md_array(&info.dev_name).and_then(|array| md.get(&array).cloned()),
+    };
+    if let Some(progress) = progress {
+      running.insert(info.mount_dir.clone(), progress);
+    }
+  }
+  running
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_progress() {
+    let mdstat = "Personalities : [raid1]
+md0 : active raid1 sdb1[1] sda1[0]
+      1953382464 blocks super 1.2 [2/2] [UU]
+      [=>...................]  resync =  8.6% (168230528/1953382464) finish=150.2min speed=198012K/sec
+      bitmap: 15/15 pages [60KB], 65536KB chunk
+
+md1 : active raid1 sdd1[1] sdc1[0]
+      976630464 blocks super 1.2 [2/2] [UU]
+
+unused devices: <none>
+";
+    let md = parse_mdstat(mdstat);
+    assert_eq!(md.len(), 1);
+    assert_eq!(md["md0"].operation, "resync");
+    assert!((md["md0"].ratio - 0.086).abs() < 1e-9);
+
+    let status = "  pool: tank
+ state: ONLINE
+  scan: scrub in progress since Sun Jul 25 10:00:00 2021
+\t1.23T scanned at 1.2G/s, 800G issued at 900M/s, 2.00T total
+\t0B repaired, 39.06% done, 00:23:00 to go
+config:
+
+  pool: rpool
+ state: ONLINE
+  scan: scrub repaired 0B in 00:10:12 with 0 errors on Sun Jul 11 00:34:13 2021
+";
+    let zfs = parse_zpool_status(status);
+    assert_eq!(zfs.len(), 1);
+    assert_eq!(zfs["tank"].operation, "scrub");
+    assert!((zfs["tank"].ratio - 0.3906).abs() < 1e-9);
+
+    let balances = parse_balances("0.25\t/mnt/pool\n");
+    assert_eq!(balances["/mnt/pool"].ratio, 0.25);
+  }
+}
@@ -0,0 +1,1397 @@
+//! Table rendering: inline ratatui rows on terminals, aligned plain text otherwise.
+
+use crate::{
+  filesystem::Filesystem,
+  graphics,
+  history::{self, Trend},
+  host,
+  hyperlink::{self, Hyperlinks},
+  icons::{self, IconStyle},
+  memory,
+  mountwatch::MountWatcher,
+  output::Sample,
+  palette::Palette,
+  progress::{self, Progress},
+  settings::{Settings, Severity},
+  stats,
+  units::SizeFormat,
+};
+use anyhow::Result;
+use clap::ValueEnum;
+use crossterm::terminal::SetTitle;
+use ratatui::{buffer::Buffer, layout::Rect, prelude::Backend, Terminal, Viewport};
+use serde::Deserialize;
+use std::{
+  borrow::Cow,
+  collections::{HashMap, VecDeque},
+  io::{stdout, IsTerminal, Write},
+  time::{Duration, Instant},
+};
+use tracing::trace;
+use unicode_width::UnicodeWidthStr;
+
+/// Column widths given with `--columns-width`: `DEVICE,MOUNT`, either one may be left empty to
+/// keep the width of the longest value.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct ColumnsWidth {
+  device: Option<usize>,
+  mount: Option<usize>,
+}
+
+pub(crate) fn parse_columns_width(value: &str) -> Result<ColumnsWidth, String> {
+  let (device, mount) = value
+    .split_once(',')
+    .ok_or_else(|| format!("invalid columns width `{value}`: expected DEVICE,MOUNT"))?;
+  let width = |width: &str| {
+    let width = width.trim();
+    (!width.is_empty())
+      .then(|| width.parse::<usize>())
+      .transpose()
+      .map_err(|e| format!("invalid columns width `{value}`: {e}"))
+  };
+  Ok(ColumnsWidth {
+    device: width(device)?,
+    mount: width(mount)?,
+  })
+}
+
+/// How the table is laid out.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct TableOptions {
+  pub(crate) columns_width: ColumnsWidth,
+  /// Wrap the mount directories longer than their column instead of truncating them.
+  pub(crate) wrap: bool,
+  /// Print the machine description above the table.
+  pub(crate) header: bool,
+  /// Link the mount directories to their location.
+  pub(crate) hyperlinks: Hyperlinks,
+  /// Draw usage pies below the table on terminals supporting the kitty graphics protocol.
+  pub(crate) graphics: bool,
+  /// Draw the used, reserved and available space as a stacked bar instead of the usage gauge.
+  pub(crate) stacked: bool,
+  /// Show the change of the used space since the previous refresh in watch mode.
+  pub(crate) delta: bool,
+  /// Show the time before the filesystems are full at their growth rate in watch mode.
+  pub(crate) eta: bool,
+  /// Show the most used filesystem in the terminal title in watch mode.
+  pub(crate) set_title: bool,
+  /// Optional columns shown before the gauge.
+  pub(crate) show: Shown,
+  /// Icons before the rows.
+  pub(crate) icons: Option<IconStyle>,
+  /// Trend arrows after the percentages.
+  pub(crate) trend: bool,
+}
+
+/// Columns of the table, in the order of the `columns` setting.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Column {
+  /// Kind of the filesystem
+  Icon,
+  /// Device name or alias
+  Source,
+  Size,
+  Used,
+  Avail,
+  /// Mount directory
+  Target,
+  /// Usage gauge
+  Pcent,
+  /// Mount options
+  Options,
+  /// Filesystem UUID
+  Uuid,
+  /// Model and kind of the disk
+  Disk,
+  /// Free space only root can use
+  Reserved,
+  /// Time since the mount
+  Mounted,
+  /// `major:minor` device number
+  Devno,
+  /// Change of the used space since a previous run
+  Change,
+  /// `f_bsize` of `statvfs`
+  Bsize,
+  /// `f_blocks` of `statvfs`
+  Blocks,
+  /// `f_bfree` of `statvfs`
+  Bfree,
+  /// `f_bavail` of `statvfs`
+  Bavail,
+  /// `f_files` of `statvfs`
+  Files,
+  /// `f_ffree` of `statvfs`
+  Ffree,
+}
+
+impl Column {
+  const ALL: [Self; 20] = [
+    Self::Icon,
+    Self::Source,
+    Self::Size,
+    Self::Used,
+    Self::Avail,
+    Self::Target,
+    Self::Pcent,
+    Self::Options,
+    Self::Uuid,
+    Self::Disk,
+    Self::Reserved,
+    Self::Mounted,
+    Self::Devno,
+    Self::Change,
+    Self::Bsize,
+    Self::Blocks,
+    Self::Bfree,
+    Self::Bavail,
+    Self::Files,
+    Self::Ffree,
+  ];
+  /// Untouched `statvfs` fields, added by `--raw`.
+  pub(crate) const RAW: [Self; 6] = [
+    Self::Bsize,
+    Self::Blocks,
+    Self::Bfree,
+    Self::Bavail,
+    Self::Files,
+    Self::Ffree,
+  ];
+  /// Columns without a `columns` setting, the ones of `--show` going before the gauge.
+  const DEFAULT: [Self; 6] = [
+    Self::Source,
+    Self::Size,
+    Self::Used,
+    Self::Avail,
+    Self::Target,
+    Self::Pcent,
+  ];
+
+  /// Text of the column for `fs`, `-` when unknown. The target, the gauge and the icon are drawn
+  /// apart.
+  fn value<'a>(
+    self,
+    fs: &'a Filesystem,
+    config: &'a Settings,
+    size_format: &SizeFormat,
+  ) -> Cow<'a, str> {
+    let value = match self {
+      Self::Icon => return "".into(),
+      Self::Source => return config.device_label(&fs.mount_info),
+      Self::Size => return size_format.format_opt(fs.size()).into(),
+      Self::Used => return size_format.format_opt(fs.used()).into(),
+      Self::Avail => return size_format.format_opt(fs.avail()).into(),
+      Self::Reserved => return size_format.format_opt(fs.reserved()).into(),
+      Self::Target => return config.mount_label(&fs.mount_info),
+      Self::Pcent => return percent_label(fs.used_ratio(), config).into(),
+      Self::Options => &fs.mount_info.mount_option,
+      Self::Uuid => &fs.mount_info.uuid,
+      Self::Disk => &fs.mount_info.disk,
+      Self::Devno => &fs.mount_info.dev_id,
+      Self::Mounted => {
+        let since = fs.mount_info.mounted.and_then(|time| time.elapsed().ok());
+        return since.map_or("-".into(), |since| host::format_uptime(since).into());
+      }
+      Self::Change => {
+        let change = fs
+          .used()
+          .and_then(|used| history::change(&config.id_label(fs.id()), used, size_format));
+        return change.map_or("-".into(), Cow::Owned);
+      }
+      Self::Bsize | Self::Blocks | Self::Bfree | Self::Bavail | Self::Files | Self::Ffree => {
+        let raw = fs.usage.raw.as_ref().map(|raw| match self {
+          Self::Bsize => raw.f_bsize,
+          Self::Blocks => raw.f_blocks,
+          Self::Bfree => raw.f_bfree,
+          Self::Bavail => raw.f_bavail,
+          Self::Files => raw.f_files,
+          _ => raw.f_ffree,
+        });
+        return raw.map_or("-".into(), |value| value.to_string().into());
+      }
+    };
+    if value.is_empty() {
+      "-".into()
+    } else {
+      value.into()
+    }
+  }
+
+  /// Whether the values are sizes, as wide as the size columns.
+  fn is_size(self) -> bool {
+    matches!(self, Self::Size | Self::Used | Self::Avail | Self::Reserved)
+  }
+
+  /// Whether the values are aligned to the right.
+  fn is_numeric(self) -> bool {
+    self.is_size() || self == Self::Change || Self::RAW.contains(&self)
+  }
+
+  /// Whether the column is only shown when asked, dimmed.
+  fn is_extra(self) -> bool {
+    matches!(
+      self,
+      Self::Options | Self::Uuid | Self::Disk | Self::Mounted
+    )
+  }
+}
+
+/// Columns added with `--show`, by [`Column`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct Shown([bool; Column::ALL.len()]);
+
+impl Shown {
+  pub(crate) fn new(columns: &[Column]) -> Self {
+    Self(Column::ALL.map(|column| columns.contains(&column)))
+  }
+
+  pub(crate) fn contains(self, column: Column) -> bool {
+    self.0[column as usize]
+  }
+}
+
+impl TableOptions {
+  /// Whether to link the mount directories on stdout.
+  fn links(&self, config: &Settings) -> bool {
+    // Links would reveal the paths that anonymizing hides.
+    self.hyperlinks.enabled(stdout().is_terminal()) && !config.anonymize.unwrap_or_default()
+  }
+}
+
+/// Width of the usage gauge when other columns follow it.
+const GAUGE_WIDTH: usize = 20;
+
+/// Widths of the table columns.
+#[derive(Debug, Clone)]
+pub(crate) struct Widths {
+  pub(crate) mount: usize,
+  pub(crate) size: usize,
+  /// Columns to draw, in order, with their widths.
+  pub(crate) columns: Vec<(Column, usize)>,
+  pub(crate) icons: IconStyle,
+  pub(crate) wrap: bool,
+  pub(crate) stacked: bool,
+  /// Window of the trend arrows, none without them.
+  pub(crate) trend: Option<Duration>,
+}
+
+impl Widths {
+  /// Widths fitting the longest values in terminal cells, unless set in `options`.
+  pub(crate) fn new(
+    filesystems: &[Filesystem],
+    config: &Settings,
+    size_format: &SizeFormat,
+    options: &TableOptions,
+  ) -> Self {
+    let (device, mount) = filesystems
+      .iter()
+      .map(|f| {
+        (
+          config.device_label(&f.mount_info).width(),
+          config.mount_label(&f.mount_info).width(),
+        )
+      })
+      .reduce(|acc, e| (acc.0.max(e.0), acc.1.max(e.1)))
+      .unwrap_or((10, 10));
+    let size = filesystems
+      .iter()
+      .map(|fs| size_format.format_opt(fs.size()).width())
+      .max()
+      .unwrap_or_default()
+      .max(9);
+    let device = options.columns_width.device.unwrap_or(device);
+    let mount = options.columns_width.mount.unwrap_or(mount);
+    let icons = options.icons.unwrap_or(IconStyle::Nerd);
+    let columns = shown_columns(
+      config.columns.as_deref(),
+      options.show,
+      options.icons.is_some(),
+    );
+    let last = columns.last().copied();
+    let columns = columns
+      .into_iter()
+      .map(|column| {
+        let width = match column {
+          Column::Icon => icons.width(),
+          Column::Source => device,
+          Column::Target => mount,
+          // The last gauge takes the rest of the line.
+          Column::Pcent if last == Some(column) => 0,
+          Column::Pcent => GAUGE_WIDTH,
+          column if column.is_size() => size,
+          column => filesystems
+            .iter()
+            .map(|fs| column.value(fs, config, size_format).width())
+            .max()
+            .unwrap_or_default(),
+        };
+        (column, width)
+      })
+      .collect();
+    Self {
+      mount,
+      size,
+      columns,
+      icons,
+      wrap: options.wrap,
+      stacked: options.stacked,
+      trend: options.trend.then(|| {
+        config
+          .trend_window
+          .as_ref()
+          .map_or(Duration::ZERO, |window| window.0)
+      }),
+    }
+  }
+
+  /// Offset of `column` from the start of a row, none when it isn't shown.
+  fn offset(&self, column: Column) -> Option<usize> {
+    let index = self
+      .columns
+      .iter()
+      .position(|(shown, _)| *shown == column)?;
+    Some(
+      self.columns[..index]
+        .iter()
+        .map(|(_, width)| width + 1)
+        .sum(),
+    )
+  }
+
+  /// Lines of the mount directory column: one, or several when wrapping.
+  fn mount_lines(&self, label: &str) -> Vec<String> {
+    if self.wrap {
+      wrap_path(label, self.mount)
+    } else {
+      vec![label.to_string()]
+    }
+  }
+}
+
+/// Columns of the `columns` setting, or the default ones, with the ones of `--show` they miss
+/// before the gauge and the icon first with `icons`.
+fn shown_columns(setting: Option<&[Column]>, show: Shown, icons: bool) -> Vec<Column> {
+  let mut columns = setting.unwrap_or(&Column::DEFAULT).to_vec();
+  if icons && !columns.contains(&Column::Icon) {
+    columns.insert(0, Column::Icon);
+  }
+  let at = columns
+    .iter()
+    .position(|column| *column == Column::Pcent)
+    .unwrap_or(columns.len());
+  let missing: Vec<Column> = Column::ALL
+    .into_iter()
+    .filter(|column| show.contains(*column) && !columns.contains(column))
+    .collect();
+  columns.splice(at..at, missing);
+  columns
+}
+
+/// `value` followed by the spaces filling `width` terminal cells, `format!` pads by characters.
+fn pad_end(value: &str, width: usize) -> String {
+  format!("{value}{:1$}", "", width.saturating_sub(value.width()))
+}
+
+/// Split `path` in lines of at most `width` characters, preferably after a `/`.
+fn wrap_path(path: &str, width: usize) -> Vec<String> {
+  let mut chars: Vec<char> = path.chars().collect();
+  if width == 0 {
+    return vec![path.to_string()];
+  }
+  let mut lines = vec![];
+  while chars.len() > width {
+    let split = chars[..width]
+      .iter()
+      .rposition(|&c| c == '/')
+      .filter(|&slash| slash > 0)
+      .map_or(width, |slash| slash + 1);
+    lines.push(chars.drain(..split).collect());
+  }
+  lines.push(chars.into_iter().collect());
+  lines
+}
+
+pub(crate) fn render_table(
+  filesystems: Vec<Filesystem>,
+  config: &Settings,
+  size_format: &SizeFormat,
+  options: TableOptions,
+) -> Result<(), anyhow::Error> {
+  let widths = Widths::new(&filesystems, config, size_format, &options);
+  trace!("{widths:?}");
+  let links = options.links(config);
+  let running = progress::running(&filesystems);
+  if !stdout().is_terminal() {
+    return write_plain(
+      &filesystems,
+      config,
+      &widths,
+      size_format,
+      options.header,
+      links,
+      &running,
+    );
+  }
+  let mut terminal = ratatui::init_with_options(ratatui::TerminalOptions {
+    viewport: Viewport::Inline(1),
+  });
+  if options.header {
+    render_header(&mut terminal, config)?;
+  }
+  for filesystem in &filesystems {
+    render_line(
+      filesystem,
+      &mut terminal,
+      config,
+      &widths,
+      size_format,
+      links,
+    )?;
+    if let Some(progress) = running.get(&filesystem.mount_info.mount_dir) {
+      render_note(&mut terminal, progress_line(progress, &widths))?;
+    }
+  }
+  if let Some(note) = memory::tmpfs_note(&filesystems, size_format) {
+    render_note(&mut terminal, note)?;
+  }
+  ratatui::restore();
+  // The gauges of the table remain the fallback on the other terminals.
+  if options.graphics && graphics::supported(|name| std::env::var(name).ok()) {
+    graphics::write_pies(&filesystems, config, &mut stdout().lock())?;
+  }
+  Ok(())
+}
+
+/// Redraw the table every `interval`, reloading the filesystems with `load`, until `q`, `Esc` or
+/// Ctrl-C. Without a terminal, a new plain table is printed at each interval instead.
+pub(crate) fn watch(
+  mut load: impl FnMut() -> Result<Vec<Filesystem>>,
+  config: &Settings,
+  interval: Duration,
+  size_format: &SizeFormat,
+  options: TableOptions,
+) -> Result<()> {
+  let watcher = MountWatcher::spawn();
+  if !stdout().is_terminal() {
+    loop {
+      let filesystems = load()?;
+      let widths = Widths::new(&filesystems, config, size_format, &options);
+      let links = options.links(config);
+      write_plain(
+        &filesystems,
+        config,
+        &widths,
+        size_format,
+        options.header,
+        links,
+        &progress::running(&filesystems),
+      )?;
+      println!();
+      watcher.sleep(interval);
+    }
+  }
+  let mut terminal = ratatui::init();
+  if options.set_title {
+    // Saved on the title stack of the terminal, to restore it on exit.
+    print!("{PUSH_TITLE}");
+  }
+  let mut history = History::default();
+  let result = (|| loop {
+    let filesystems = load()?;
+    history.record(&filesystems);
+    if options.set_title {
+      crossterm::execute!(stdout(), SetTitle(title(&filesystems, config)))?;
+    }
+    terminal.draw(|frame| {
+      draw_watch(
+        frame,
+        &filesystems,
+        &history,
+        config,
+        interval,
+        size_format,
+        &options,
+      )
+    })?;
+    if wait_for_quit(interval, &watcher)? {
+      return Ok(());
+    }
+  })();
+  ratatui::restore();
+  if options.set_title {
+    print!("{POP_TITLE}");
+    stdout().flush()?;
+  }
+  result
+}
+
+/// XTWINOPS sequences saving and restoring the window title.
+const PUSH_TITLE: &str = "\x1b[22;0t";
+const POP_TITLE: &str = "\x1b[23;0t";
+
+/// Title of `--set-title`: the most used filesystem, which tmux shows as the pane title, with its
+/// percentage as the table shows it.
+fn title(filesystems: &[Filesystem], config: &Settings) -> String {
+  let worst = filesystems
+    .iter()
+    .filter_map(|fs| Some((fs, fs.used_ratio()?)))
+    .max_by(|a, b| a.1.total_cmp(&b.1));
+  match worst {
+    Some((fs, ratio)) => format!(
+      "ddf: worst {} {}",
+      config.mount_label(&fs.mount_info),
+      percent_label(Some(ratio), config).trim_start()
+    ),
+    None => "ddf".to_string(),
+  }
+}
+
+/// Wait for `timeout`, returning true when the user asked to quit. A resize or a change of the
+/// mount table ends the wait early, so the table is redrawn at once.
+fn wait_for_quit(timeout: Duration, watcher: &MountWatcher) -> Result<bool> {
+  use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+  let deadline = Instant::now() + timeout;
+  while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+    if watcher.take_change() {
+      break;
+    }
+    if !event::poll(remaining.min(Duration::from_millis(100)))? {
+      continue;
+    }
+    match event::read()? {
+      Event::Key(key) if key.kind == KeyEventKind::Press => match key.code {
+        KeyCode::Char('q') | KeyCode::Esc => return Ok(true),
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(true),
+        _ => {}
+      },
+      Event::Resize(_, _) => break,
+      _ => {}
+    }
+  }
+  Ok(false)
+}
+
+/// Samples kept for the sparklines and the ETA of the watch mode.
+const TREND_LEN: usize = 20;
+
+/// Recent used space of every mount with the time of the sample, oldest first, for the
+/// sparklines and the ETA of the watch mode.
+#[derive(Debug, Default)]
+struct History {
+  used: HashMap<String, VecDeque<(Instant, u64)>>,
+}
+
+impl History {
+  /// Add a sample of `filesystems`, forgetting the mounts that are gone.
+  fn record(&mut self, filesystems: &[Filesystem]) {
+    self.used.retain(|mount, _| {
+      filesystems
+        .iter()
+        .any(|fs| fs.mount_info.mount_dir == *mount)
+    });
+    for fs in filesystems {
+      let Some(used) = fs.used() else { continue };
+      let samples = self
+        .used
+        .entry(fs.mount_info.mount_dir.clone())
+        .or_default();
+      if samples.len() == TREND_LEN {
+        samples.pop_front();
+      }
+      samples.push_back((Instant::now(), used));
+    }
+  }
+
+  /// Time before `mount` is full at the growth rate of the samples, with `avail` bytes left.
+  fn time_to_full(&self, mount: &str, avail: u64) -> Option<Duration> {
+    let samples = self.used.get(mount)?;
+    let (start, _) = samples.front()?;
+    let samples: Vec<_> = samples
+      .iter()
+      .map(|(time, used)| (time.duration_since(*start).as_secs_f64(), *used))
+      .collect();
+    stats::time_to_full(avail, stats::growth_rate(&samples)?)
+  }
+
+  /// Change of the used space of `mount` between the last two samples.
+  fn delta(&self, mount: &str) -> Option<i128> {
+    let samples = self.used.get(mount)?;
+    let mut recent = samples.iter().rev();
+    let ((_, last), (_, previous)) = (recent.next()?, recent.next()?);
+    Some(i128::from(*last) - i128::from(*previous))
+  }
+
+  /// Used space of `mount` above its minimum over the samples, and the range of the values, so
+  /// small variations of a large filesystem still show.
+  fn trend(&self, mount: &str) -> (Vec<u64>, u64) {
+    let Some(samples) = self.used.get(mount) else {
+      return (vec![], 1);
+    };
+    let used = samples.iter().map(|(_, used)| *used);
+    let min = used.clone().min().unwrap_or_default();
+    let max = used.clone().max().unwrap_or_default();
+    (used.map(|used| used - min).collect(), (max - min).max(1))
+  }
+}
+
+/// Cells of the ETA column: a space and `~100d`.
+const ETA_WIDTH: u16 = 6;
+/// Times left shown as high and medium in the ETA column.
+const DAY: Duration = Duration::from_secs(86400);
+const WEEK: Duration = Duration::from_secs(7 * 86400);
+
+fn draw_watch(
+  frame: &mut ratatui::Frame,
+  filesystems: &[Filesystem],
+  history: &History,
+  config: &Settings,
+  interval: Duration,
+  size_format: &SizeFormat,
+  options: &TableOptions,
+) {
+  use ratatui::{prelude::*, widgets::*};
+  let area = frame.area();
+  let title = format!(
+    "Every {} · {}",
+    humantime::format_duration(interval),
+    if options.header {
+      header_line(config)
+    } else {
+      Sample::now(config).timestamp
+    }
+  );
+  frame.render_widget(
+    Paragraph::new(title).style(Style::default().add_modifier(Modifier::BOLD)),
+    Rect { height: 1, ..area },
+  );
+  let widths = Widths::new(filesystems, config, size_format, options);
+  let mut y = area.y + 1;
+  for fs in filesystems {
+    let height = widths
+      .mount_lines(&config.mount_label(&fs.mount_info))
+      .len() as u16;
+    if y + height > area.bottom() {
+      return;
+    }
+    let row = Rect { y, height, ..area };
+    let [row, delta_area] = if options.delta {
+      Layout::horizontal([
+        Constraint::Fill(1),
+        Constraint::Length(widths.size as u16 + 2),
+      ])
+      .areas(row)
+    } else {
+      [row, Rect::default()]
+    };
+    let [row, eta_area] = if options.eta {
+      Layout::horizontal([Constraint::Fill(1), Constraint::Length(ETA_WIDTH)]).areas(row)
+    } else {
+      [row, Rect::default()]
+    };
+    // The trend goes right of the gauge when there is room for both.
+    let [row, trend] = if area.width >= 100 {
+      Layout::horizontal([
+        Constraint::Fill(1),
+        Constraint::Length(TREND_LEN as u16 + 1),
+      ])
+      .areas(row)
+    } else {
+      [row, Rect::default()]
+    };
+    render_row(fs, row, frame.buffer_mut(), config, &widths, size_format);
+    let delta = history.delta(&fs.mount_info.mount_dir);
+    let used_x = widths.offset(Column::Used);
+    if let Some(used_x) = used_x.filter(|_| delta.is_some_and(|delta| delta != 0)) {
+      let used = Rect {
+        x: row.x + used_x as u16,
+        width: widths.size as u16,
+        height: 1,
+        ..row
+      };
+      frame
+        .buffer_mut()
+        .set_style(used.intersection(row), Palette::current().changed());
+    }
+    if let Some(delta) = delta.filter(|delta| *delta != 0) {
+      frame.render_widget(
+        Paragraph::new(size_format.format_signed(delta))
+          .right_aligned()
+          .style(Palette::current().changed()),
+        Rect {
+          height: 1,
+          ..delta_area
+        },
+      );
+    }
+    if options.eta {
+      let left = fs
+        .avail()
+        .and_then(|avail| history.time_to_full(&fs.mount_info.mount_dir, avail));
+      let severity = left.filter(|left| *left < WEEK).map(|left| {
+        if left < DAY {
+          Severity::High
+        } else {
+          Severity::Medium
+        }
+      });
+      frame.render_widget(
+        Paragraph::new(stats::format_eta(left))
+          .right_aligned()
+          .style(Palette::current().severity(severity)),
+        Rect {
+          height: 1,
+          ..eta_area
+        },
+      );
+    }
+    let (data, max) = history.trend(&fs.mount_info.mount_dir);
+    frame.render_widget(
+      Sparkline::default()
+        .data(&data)
+        .max(max)
+        .style(Palette::current().severity(fs.used_ratio().map(|ratio| config.severity(fs, ratio)))),
+      Rect {
+        x: trend.x + 1,
+        width: trend.width.saturating_sub(1),
+        height: trend.height.min(1),
+        ..trend
+      },
+    );
+    y += height;
+  }
+  if let Some(note) = memory::tmpfs_note(filesystems, size_format) {
+    if y < area.bottom() {
+      frame.render_widget(
+        Paragraph::new(note).style(Palette::current().dim()),
+        Rect {
+          y,
+          height: 1,
+          ..area
+        },
+      );
+    }
+  }
+}
+
+/// Describe the machine and the time of the sample, so screenshots are self-describing. The boot
+/// id is left out when anonymizing.
+fn header_line(settings: &Settings) -> String {
+  let sample = Sample::now(settings);
+  let mut fields = vec![sample.hostname, host::kernel()];
+  if let Some(uptime) = host::uptime() {
+    fields.push(format!("up {}", host::format_uptime(uptime)));
+  }
+  if let Some(boot_id) = host::boot_id().filter(|_| !settings.anonymize.unwrap_or_default()) {
+    fields.push(format!(
+      "boot {}",
+      boot_id.split('-').next().unwrap_or_default()
+    ));
+  }
+  fields.push(sample.timestamp);
+  fields.join(" · ")
+}
+
+fn render_header<A: Backend>(terminal: &mut Terminal<A>, settings: &Settings) -> Result<()> {
+  use ratatui::{prelude::*, widgets::*};
+  let header = header_line(settings);
+  terminal.insert_before(1, |frame| {
+    Paragraph::new(header.as_str())
+      .style(Style::default().add_modifier(Modifier::BOLD))
+      .render(*frame.area(), frame);
+  })?;
+  Ok(())
+}
+
+fn render_note<A: Backend>(terminal: &mut Terminal<A>, note: String) -> Result<()> {
+  use ratatui::{prelude::*, widgets::*};
+  terminal.insert_before(1, |frame| {
+    Paragraph::new(note)
+      .style(Palette::current().dim())
+      .render(*frame.area(), frame);
+  })?;
+  Ok(())
+}
+
+pub(crate) fn render_line<A: Backend>(
+  fs: &Filesystem,
+  terminal: &mut Terminal<A>,
+  settings: &Settings,
+  widths: &Widths,
+  size_format: &SizeFormat,
+  links: bool,
+) -> Result<()> {
+  let lines = widths.mount_lines(&settings.mount_label(&fs.mount_info));
+  terminal.insert_before(lines.len() as u16, |buf| {
+    let area = buf.area;
+    render_row(fs, area, buf, settings, widths, size_format);
+    if links {
+      let url = hyperlink::file_url(&host::hostname(), &fs.mount_info.mount_dir);
+      link_mount(buf, widths, &lines, &url);
+    }
+  })?;
+  Ok(())
+}
+
+/// Wrap the mount directory `lines` drawn in `buf` in links to `url`. The sequences are added to
+/// the first and last cells of each line: this only works when every cell is printed in order, as
+/// `insert_before` does, not with the diffs of a full-screen frame. Nothing is linked when the
+/// mount directory isn't shown.
+fn link_mount(buf: &mut Buffer, widths: &Widths, lines: &[String], url: &str) {
+  let Some(offset) = widths.offset(Column::Target) else {
+    return;
+  };
+  let x = buf.area.x + offset as u16;
+  let available = buf.area.width.saturating_sub(x);
+  for (y, line) in lines.iter().enumerate() {
+    let width = (line.chars().count().min(widths.mount) as u16).min(available);
+    if width == 0 {
+      continue;
+    }
+    let (first, last) = ((x, y as u16), (x + width - 1, y as u16));
+    let symbol = format!("{}{}", hyperlink::open(url), buf[first].symbol());
+    buf[first].set_symbol(&symbol);
+    let symbol = format!("{}{}", buf[last].symbol(), hyperlink::CLOSE);
+    buf[last].set_symbol(&symbol);
+  }
+}
+
+/// Draw the row of `fs` in `area`: labels, sizes and usage gauge on the first line, the rest of a
+/// wrapped mount directory below.
+fn render_row(
+  fs: &Filesystem,
+  area: Rect,
+  buf: &mut Buffer,
+  settings: &Settings,
+  widths: &Widths,
+  size_format: &SizeFormat,
+) {
+  use ratatui::{prelude::*, widgets::*};
+  let constraints = widths.columns.iter().map(|(column, width)| match column {
+    Column::Pcent if *width == 0 => Constraint::Fill(1),
+    _ => Constraint::Length(*width as u16 + 1),
+  });
+  let areas = Layout::horizontal(constraints).split(area);
+  let palette = Palette::current();
+  for ((column, width), area) in widths.columns.iter().zip(areas.iter()) {
+    let first_line = Rect { height: 1, ..*area };
+    match column {
+      Column::Icon => {
+        Paragraph::new(icons::icon(widths.icons, &fs.mount_info)).render(first_line, buf);
+      }
+      Column::Target => {
+        let mount_lines = widths.mount_lines(&settings.mount_label(&fs.mount_info));
+        Paragraph::new(mount_lines.into_iter().map(Line::from).collect::<Vec<_>>())
+          .render(*area, buf);
+      }
+      Column::Pcent if widths.stacked => render_stack(fs, first_line, buf, settings, widths.trend),
+      Column::Pcent => {
+        let percent_used = fs.used_ratio().unwrap_or_default();
+        LineGauge::default()
+          .filled_style(palette.severity(Some(settings.severity(fs, percent_used))))
+          .line_set(symbols::line::DOUBLE)
+          .unfilled_style(palette.dim())
+          .label(percent_line(fs, settings, widths.trend))
+          .ratio(gauge_ratio(settings.shown_ratio(percent_used)))
+          .render(first_line, buf);
+      }
+      column => {
+        let value = column.value(fs, settings, size_format);
+        if column.is_numeric() {
+          Paragraph::new(format!("{value:>width$}")).render(first_line, buf);
+        } else if column.is_extra() {
+          Paragraph::new(value)
+            .style(palette.dim())
+            .render(first_line, buf);
+        } else {
+          Paragraph::new(value).render(first_line, buf);
+        }
+      }
+    }
+  }
+}
+
+/// Draw the used space of `fs` in its severity style, the reserved space dimmed and the available
+/// space in green on the first line of `area`, after the used percentage. The three parts use
+/// different characters so they stay apart without colors.
+fn render_stack(
+  fs: &Filesystem,
+  area: Rect,
+  buf: &mut Buffer,
+  settings: &Settings,
+  trend: Option<Duration>,
+) {
+  let palette = Palette::current();
+  let label = percent_line(fs, settings, trend);
+  buf.set_line(area.x, area.y, &label, area.width);
+  let x = area.x + label.width() as u16;
+  let width = area.right().saturating_sub(x + 1);
+  let parts = [fs.used(), fs.reserved(), fs.avail()].map(Option::unwrap_or_default);
+  let severity = palette.severity(fs.used_ratio().map(|ratio| settings.severity(fs, ratio)));
+  let styles = [
+    ('█', severity),
+    ('▒', palette.dim()),
+    ('░', palette.available()),
+  ];
+  let mut x = x + 1;
+  for (cells, (symbol, style)) in stack_widths(width, parts).into_iter().zip(styles) {
+    buf.set_string(x, area.y, symbol.to_string().repeat(cells.into()), style);
+    x += cells;
+  }
+}
+
+/// Cells of a `width` cells bar taken by each of the `parts`, in proportion to their sizes. Every
+/// part that isn't empty gets at least one cell when there is room.
+fn stack_widths(width: u16, parts: [u64; 3]) -> [u16; 3] {
+  let total: u64 = parts.iter().sum();
+  if total == 0 {
+    return [0; 3];
+  }
+  let mut cells = parts.map(|part| {
+    let cells = (part as f64 / total as f64 * f64::from(width)).round() as u16;
+    if part > 0 {
+      cells.max(1)
+    } else {
+      cells
+    }
+  });
+  // Rounding may overflow the bar: take the excess from the largest part.
+  while cells.iter().sum::<u16>() > width {
+    let largest = (0..3).max_by_key(|&i| cells[i]).unwrap_or_default();
+    cells[largest] -= 1;
+  }
+  cells
+}
+
+/// Line under a row showing an operation running on its filesystem, aligned with the sizes.
+fn progress_line(progress: &Progress, widths: &Widths) -> String {
+  const WIDTH: usize = 20;
+  let done = ((progress.ratio.clamp(0.0, 1.0) * WIDTH as f64).round() as usize).min(WIDTH);
+  format!(
+    "{:indent$}{} [{}{}] {:.1}%",
+    "",
+    progress.operation,
+    "#".repeat(done),
+    "-".repeat(WIDTH - done),
+    100.0 * progress.ratio,
+    indent = widths.offset(Column::Size).unwrap_or_default(),
+  )
+}
+
+/// Trend arrow of `fs` over `window`, with its style: growing is what fills the disks.
+fn trend_arrow(
+  fs: &Filesystem,
+  settings: &Settings,
+  window: Duration,
+) -> Option<(char, ratatui::style::Style)> {
+  let trend = history::trend(&settings.id_label(fs.id()), fs.used()?, fs.size()?, window)?;
+  let palette = Palette::current();
+  let style = match trend {
+    Trend::Up => palette.severity(Some(Severity::High)),
+    Trend::Down => palette.available(),
+    Trend::Flat => palette.dim(),
+  };
+  Some((trend.arrow(), style))
+}
+
+/// Percentage of the gauge, followed by the trend arrow over `window` when given.
+fn percent_line(
+  fs: &Filesystem,
+  settings: &Settings,
+  window: Option<Duration>,
+) -> ratatui::text::Line<'static> {
+  use ratatui::text::{Line, Span};
+  let mut spans = vec![Span::raw(percent_label(fs.used_ratio(), settings))];
+  if let Some((arrow, style)) = window.and_then(|window| trend_arrow(fs, settings, window)) {
+    spans.push(Span::styled(arrow.to_string(), style));
+  }
+  Line::from(spans)
+}
+
+/// Used percentage, or free one with `free_percent`, of a used ratio, `?` when unknown. Usage
+/// above the capacity keeps its true value and is flagged with a `!`, nothing is free then.
+fn percent_label(ratio: Option<f64>, settings: &Settings) -> String {
+  match ratio {
+    Some(ratio) => {
+      let shown = (100.0 * settings.shown_ratio(ratio).max(0.0)).round();
+      if ratio > 1.0 {
+        format!("{shown:>3}%!")
+      } else {
+        format!("{shown:>3}%")
+      }
+    }
+    None => "  ?".to_string(),
+  }
+}
+
+/// Ratio the gauge can draw: `LineGauge` requires a value between 0 and 1.
+fn gauge_ratio(ratio: f64) -> f64 {
+  if ratio.is_nan() {
+    0.0
+  } else {
+    ratio.clamp(0.0, 1.0)
+  }
+}
+
+/// Aligned text without escape sequences, for pipes and files. Mount directories are never
+/// truncated, only wrapped when asked.
+fn write_plain(
+  filesystems: &[Filesystem],
+  settings: &Settings,
+  widths: &Widths,
+  size_format: &SizeFormat,
+  header: bool,
+  links: bool,
+  running: &HashMap<String, Progress>,
+) -> Result<()> {
+  let mut out = stdout().lock();
+  if header {
+    writeln!(out, "{}", header_line(settings))?;
+  }
+  for fs in filesystems {
+    let mut mount_lines = widths.mount_lines(&settings.mount_label(&fs.mount_info));
+    if links {
+      let url = hyperlink::file_url(&host::hostname(), &fs.mount_info.mount_dir);
+      for line in &mut mount_lines {
+        // Padded here, the escape sequences would count in the width.
+        let padding = widths.mount.saturating_sub(line.width());
+        *line = format!(
+          "{}{line}{}{:padding$}",
+          hyperlink::open(&url),
+          hyperlink::CLOSE,
+          ""
+        );
+      }
+    }
+    let mut line = String::new();
+    let mut indent = 0;
+    for (column, width) in &widths.columns {
+      let cell = match column {
+        // Padded by the glyphs, whose width may not be their count of characters.
+        Column::Icon => icons::icon(widths.icons, &fs.mount_info).to_string(),
+        Column::Target => {
+          indent = line.width();
+          pad_end(&mount_lines[0], widths.mount)
+        }
+        Column::Pcent => {
+          let arrow = widths
+            .trend
+            .map(|window| trend_arrow(fs, settings, window).map_or(' ', |(arrow, _)| arrow));
+          format!(
+            "{:>4}{}",
+            percent_label(fs.used_ratio(), settings),
+            arrow.map(String::from).unwrap_or_default()
+          )
+        }
+        column if column.is_numeric() => {
+          format!("{:>width$}", column.value(fs, settings, size_format))
+        }
+        column => pad_end(&column.value(fs, settings, size_format), *width),
+      };
+      line.push_str(&cell);
+      line.push(' ');
+    }
+    writeln!(out, "{}", line.trim_end())?;
+    for continuation in &mount_lines[1..] {
+      writeln!(out, "{:indent$}{continuation}", "")?;
+    }
+    if let Some(progress) = running.get(&fs.mount_info.mount_dir) {
+      writeln!(out, "{}", progress_line(progress, widths))?;
+    }
+  }
+  if let Some(note) = memory::tmpfs_note(filesystems, size_format) {
+    writeln!(out, "{note}")?;
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_wrap_path() {
+    assert_eq!(wrap_path("/home", 10), ["/home"]);
+    assert_eq!(
+      wrap_path("/var/lib/kubelet/pods/1234", 12),
+      ["/var/lib/", "kubelet/", "pods/1234"]
+    );
+    assert_eq!(wrap_path("/abcdefghij", 4), ["/abc", "defg", "hij"]);
+  }
+
+  #[test]
+  fn test_wide_widths() {
+    use crate::fsext::{FsUsage, MountInfo};
+    let fs = Filesystem {
+      mount_info: MountInfo {
+        dev_name: "/dev/sdé".to_string(),
+        mount_dir: "/データ".to_string(),
+        ..Default::default()
+      },
+      usage: FsUsage {
+        blocksize: 1,
+        blocks: 100,
+        bfree: 50,
+        bavail: 50,
+        files: 0,
+        ffree: 0,
+        raw: None,
+      },
+    };
+    let widths = Widths::new(
+      &[fs],
+      &Settings::default(),
+      &SizeFormat::Human,
+      &TableOptions::default(),
+    );
+    // Counted in terminal cells, not in bytes.
+    let device = widths
+      .columns
+      .iter()
+      .find(|(column, _)| *column == Column::Source)
+      .map(|(_, width)| *width);
+    assert_eq!((device, widths.mount), (Some(8), 7));
+    assert_eq!(pad_end("/データ", 9), "/データ  ");
+  }
+
+  #[test]
+  fn test_parse_columns_width() {
+    assert_eq!(
+      parse_columns_width("20,40"),
+      Ok(ColumnsWidth {
+        device: Some(20),
+        mount: Some(40)
+      })
+    );
+    assert_eq!(
+      parse_columns_width(",30"),
+      Ok(ColumnsWidth {
+        device: None,
+        mount: Some(30)
+      })
+    );
+    assert!(parse_columns_width("20").is_err());
+    assert!(parse_columns_width("a,b").is_err());
+  }
+
+  #[test]
+  fn test_history() {
+    use crate::fsext::{FsUsage, MountInfo};
+    let fs = |mount: &str, bfree| Filesystem {
+      mount_info: MountInfo {
+        mount_dir: mount.to_string(),
+        ..Default::default()
+      },
+      usage: FsUsage {
+        blocksize: 1,
+        blocks: 1000,
+        bfree,
+        bavail: bfree,
+        files: 0,
+        ffree: 0,
+        raw: None,
+      },
+    };
+    let mut history = History::default();
+    for bfree in [500, 490, 495] {
+      history.record(&[fs("/", bfree), fs("/tmp", 100)]);
+    }
+    assert_eq!(history.trend("/"), (vec![0, 10, 5], 10));
+    assert_eq!(history.trend("/tmp"), (vec![0, 0, 0], 1));
+    assert_eq!(history.delta("/"), Some(-5));
+    assert_eq!(history.delta("/tmp"), Some(0));
+    for _ in 0..TREND_LEN {
+      history.record(&[fs("/", 400)]);
+    }
+    assert_eq!(history.trend("/").0.len(), TREND_LEN);
+    assert_eq!(history.trend("/tmp"), (vec![], 1));
+  }
+
+  #[test]
+  fn test_title() {
+    use crate::fsext::{FsUsage, MountInfo};
+    let fs = |mount: &str, bfree| Filesystem {
+      mount_info: MountInfo {
+        mount_dir: mount.to_string(),
+        ..Default::default()
+      },
+      usage: FsUsage {
+        blocksize: 1,
+        blocks: 100,
+        bfree,
+        bavail: bfree,
+        files: 0,
+        ffree: 0,
+        raw: None,
+      },
+    };
+    let settings = Settings::default();
+    assert_eq!(
+      title(&[fs("/", 40), fs("/home", 9)], &settings),
+      "ddf: worst /home 91%"
+    );
+    assert_eq!(title(&[], &settings), "ddf");
+    let free = Settings {
+      free_percent: Some(true),
+      ..Default::default()
+    };
+    assert_eq!(
+      title(&[fs("/", 40), fs("/home", 9)], &free),
+      "ddf: worst /home 9%"
+    );
+  }
+
+  #[test]
+  fn test_anonymized_header() {
+    let settings = Settings {
+      anonymize: Some(true),
+      ..Default::default()
+    };
+    let header = header_line(&settings);
+    assert!(header.starts_with(&crate::anonymize::anonymize_host(&host::hostname())));
+    assert!(!header.contains("boot "));
+  }
+
+  #[test]
+  fn test_percent_overrun() {
+    use crate::fsext::{FsUsage, MountInfo};
+    // 8 reserved blocks, root left 4 of them: the users' 92 blocks hold 96.
+    let fs = Filesystem {
+      mount_info: MountInfo::default(),
+      usage: FsUsage {
+        blocksize: 1,
+        blocks: 100,
+        bfree: 4,
+        bavail: -4i64 as u64,
+        files: 0,
+        ffree: 0,
+        raw: None,
+      },
+    };
+    let settings = Settings::default();
+    assert_eq!(percent_label(fs.used_ratio(), &settings), "104%!");
+    let free = Settings {
+      free_percent: Some(true),
+      ..Default::default()
+    };
+    assert_eq!(percent_label(fs.used_ratio(), &free), "  0%!");
+    assert_eq!(fs.avail(), Some(0));
+    assert_eq!(gauge_ratio(fs.used_ratio().unwrap()), 1.0);
+  }
+
+  #[test]
+  fn test_raw_columns() {
+    use crate::fsext::{FsUsage, MountInfo, RawStatfs};
+    let mut fs = Filesystem {
+      mount_info: MountInfo::default(),
+      usage: FsUsage {
+        blocksize: 4096,
+        blocks: 100,
+        bfree: 50,
+        bavail: 40,
+        files: 0,
+        ffree: 0,
+        raw: None,
+      },
+    };
+    let (settings, size_format) = (Settings::default(), SizeFormat::Human);
+    assert_eq!(Column::Bavail.value(&fs, &settings, &size_format), "-");
+    fs.usage.raw = Some(RawStatfs {
+      f_bsize: 4096,
+      f_bavail: 40,
+      ..Default::default()
+    });
+    assert_eq!(Column::Bavail.value(&fs, &settings, &size_format), "40");
+    assert_eq!(Column::Bsize.value(&fs, &settings, &size_format), "4096");
+    // Next to the sanitized sizes, before the gauge.
+    let columns = shown_columns(None, Shown::new(&Column::RAW), false);
+    assert_eq!(columns[..5], Column::DEFAULT[..5]);
+    assert_eq!(columns[5..11], Column::RAW);
+    assert_eq!(columns.last(), Some(&Column::Pcent));
+  }
+
+  #[test]
+  fn test_stack_widths() {
+    assert_eq!(stack_widths(20, [50, 5, 45]), [10, 1, 9]);
+    // The thin reserved part keeps a cell, taken from the largest part.
+    assert_eq!(stack_widths(10, [90, 1, 9]), [8, 1, 1]);
+    assert_eq!(stack_widths(10, [0, 0, 100]), [0, 0, 10]);
+    assert_eq!(stack_widths(10, [0, 0, 0]), [0, 0, 0]);
+  }
+
+  #[test]
+  fn test_shown_columns() {
+    let show = Shown::new(&[Column::Uuid, Column::Size]);
+    assert_eq!(
+      shown_columns(None, show, false),
+      [
+        Column::Source,
+        Column::Size,
+        Column::Used,
+        Column::Avail,
+        Column::Target,
+        Column::Uuid,
+        Column::Pcent
+      ]
+    );
+    assert_eq!(
+      shown_columns(
+        Some(&[Column::Target, Column::Pcent, Column::Size]),
+        show,
+        false
+      ),
+      [Column::Target, Column::Uuid, Column::Pcent, Column::Size]
+    );
+    assert_eq!(
+      shown_columns(Some(&[Column::Target]), Shown::default(), true),
+      [Column::Icon, Column::Target]
+    );
+  }
+
+  #[test]
+  fn test_link_mount() {
+    let widths = Widths {
+      mount: 4,
+      size: 2,
+      columns: [(Column::Source, 4), (Column::Size, 2), (Column::Used, 2)]
+        .into_iter()
+        .chain([(Column::Avail, 2), (Column::Target, 4), (Column::Pcent, 0)])
+        .collect(),
+      icons: IconStyle::Nerd,
+      wrap: true,
+      stacked: false,
+      trend: None,
+    };
+    let mut buf = Buffer::empty(Rect::new(0, 0, 20, 2));
+    let lines = ["/mnt".to_string(), "/a".to_string()];
+    buf.set_string(14, 0, &lines[0], ratatui::style::Style::default());
+    buf.set_string(14, 1, &lines[1], ratatui::style::Style::default());
+    link_mount(&mut buf, &widths, &lines, "file:///mnt/a");
+    let open = hyperlink::open("file:///mnt/a");
+    assert_eq!(buf[(14, 0)].symbol(), format!("{open}/"));
+    assert_eq!(buf[(17, 0)].symbol(), format!("t{}", hyperlink::CLOSE));
+    assert_eq!(buf[(15, 1)].symbol(), format!("a{}", hyperlink::CLOSE));
+    assert_eq!(buf[(18, 0)].symbol(), " ");
+
+    // The link follows the mount directory when the columns are reordered.
+    let widths = Widths {
+      columns: vec![(Column::Target, 4), (Column::Source, 4), (Column::Pcent, 0)],
+      ..widths
+    };
+    let mut buf = Buffer::empty(Rect::new(0, 0, 20, 1));
+    buf.set_string(0, 0, &lines[0], ratatui::style::Style::default());
+    link_mount(&mut buf, &widths, &lines[..1], "file:///mnt/a");
+    assert_eq!(buf[(0, 0)].symbol(), format!("{open}/"));
+    assert_eq!(buf[(3, 0)].symbol(), format!("t{}", hyperlink::CLOSE));
+
+    // Hidden, nothing is linked.
+    let widths = Widths {
+      columns: vec![(Column::Source, 4), (Column::Pcent, 0)],
+      ..widths
+    };
+    let mut buf = Buffer::empty(Rect::new(0, 0, 20, 1));
+    buf.set_string(0, 0, "dev0", ratatui::style::Style::default());
+    link_mount(&mut buf, &widths, &lines[..1], "file:///mnt/a");
+    assert_eq!(buf[(0, 0)].symbol(), "d");
+  }
+}
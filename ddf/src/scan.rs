@@ -0,0 +1,108 @@
+//! `top` and `users` scans: walk a filesystem and rank what uses its space.
+//!
+//! The ranking is redrawn while the walk runs, so partial results show up immediately. Ctrl-C
+//! or the time budget stop the walk and print what was gathered so far.
+
+use crate::{
+  args::ScanArgs,
+  fsext::read_fs_list,
+  host::user_name,
+  units::SizeFormat,
+  walk::{self, Cancel, Totals, WalkOptions},
+};
+use anyhow::{bail, Result};
+use crossterm::{cursor::MoveUp, terminal, ExecutableCommand};
+use std::{
+  io::{stdout, IsTerminal, Write},
+  path::PathBuf,
+  thread,
+  time::Duration,
+};
+
+const REFRESH: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum ScanKind {
+  Top,
+  Users,
+}
+
+/// Print the ranking, replacing the `previous` lines when redrawing.
+fn draw(lines: &[String], previous: usize) -> Result<usize> {
+  let mut out = stdout().lock();
+  if previous > 0 {
+    out.execute(MoveUp(previous as u16))?;
+    out.execute(terminal::Clear(terminal::ClearType::FromCursorDown))?;
+  }
+  for line in lines {
+    writeln!(out, "{line}")?;
+  }
+  out.flush()?;
+  Ok(lines.len())
+}
+
+fn scan<K, F>(
+  args: &ScanArgs,
+  run: impl FnOnce(&Cancel, &Totals<K>) -> Result<()> + Send,
+  label: F,
+  size_format: &SizeFormat,
+) -> Result<()>
+where
+  K: Eq + std::hash::Hash + Ord + Clone + Send,
+  F: Fn(&K) -> String,
+{
+  let cancel = Cancel::with_budget(args.time_budget);
+  let on_interrupt = cancel.clone();
+  ctrlc::set_handler(move || on_interrupt.cancel())?;
+  let totals = Totals::default();
+  let lines = || -> Vec<String> {
+    totals
+      .largest(args.count)
+      .iter()
+      .map(|(key, bytes)| format!("{:>10} {}", size_format.format(*bytes), label(key)))
+      .collect()
+  };
+
+  let live = stdout().is_terminal();
+  let mut drawn = 0;
+  thread::scope(|scope| -> Result<()> {
+    let walker = scope.spawn(|| run(&cancel, &totals));
+    while !walker.is_finished() {
+      if live {
+        drawn = draw(&lines(), drawn)?;
+      }
+      thread::sleep(REFRESH);
+    }
+    walker.join().expect("the walker panicked")
+  })?;
+  draw(&lines(), if live { drawn } else { 0 })?;
+
+  if cancel.is_cancelled() {
+    eprintln!("ddf: scan interrupted, results are partial");
+  }
+  Ok(())
+}
+
+/// Run a scan of `args.path`.
+pub(crate) fn run(kind: ScanKind, args: &ScanArgs, size_format: &SizeFormat) -> Result<()> {
+  let mounts = read_fs_list()?;
+  let root = args.path.canonicalize()?;
+  if walk::on_virtual_fs(&mounts, &root) {
+    bail!("{} is on a virtual filesystem", root.display());
+  }
+  let options = WalkOptions::new(&mounts, args.exclude_dir.clone());
+  match kind {
+    ScanKind::Top => scan(
+      args,
+      |cancel, totals| walk::top(&root, &options, cancel, totals),
+      |path: &PathBuf| path.display().to_string(),
+      size_format,
+    ),
+    ScanKind::Users => scan(
+      args,
+      |cancel, totals| walk::users(&root, &options, cancel, totals),
+      |uid: &u32| user_name(*uid),
+      size_format,
+    ),
+  }
+}
@@ -0,0 +1,85 @@
+//! `--self-test`: exercise the platform backend stage by stage, for CI runners and for users
+//! checking an install.
+
+use crate::{
+  filesystem::Filesystem,
+  fsext::read_fs_list,
+  render::{self, TableOptions, Widths},
+  settings::Settings,
+  units::SizeFormat,
+};
+use anyhow::{bail, Result};
+use ratatui::{backend::TestBackend, Terminal, TerminalOptions, Viewport};
+use std::time::Instant;
+
+/// Run `stage`, print its outcome and timing, and return its value when it passed.
+fn stage<T>(name: &str, run: impl FnOnce() -> Result<(T, String)>) -> Option<T> {
+  let start = Instant::now();
+  let result = run();
+  let elapsed = start.elapsed();
+  match result {
+    Ok((value, detail)) => {
+      println!("PASS {name:<7} {elapsed:>10.1?}  {detail}");
+      Some(value)
+    }
+    Err(error) => {
+      println!("FAIL {name:<7} {elapsed:>10.1?}  {error:#}");
+      None
+    }
+  }
+}
+
+/// Enumerate the mounts, stat each of them and render them to an off-screen terminal. Returns
+/// whether every stage passed.
+pub(crate) fn run(settings: &Settings) -> bool {
+  let Some(mounts) = stage("mounts", || {
+    let mounts = read_fs_list()?;
+    if mounts.is_empty() {
+      bail!("the mount table is empty");
+    }
+    let count = mounts.len();
+    Ok((mounts, format!("{count} mounts")))
+  }) else {
+    return false;
+  };
+  let total = mounts.len();
+  let Some(filesystems) = stage("statfs", || {
+    let filesystems: Vec<Filesystem> = mounts
+      .into_iter()
+      .filter_map(|m| Filesystem::new(m, None))
+      .collect();
+    if filesystems.is_empty() {
+      bail!("no mount could be statted");
+    }
+    let count = filesystems.len();
+    Ok((filesystems, format!("{count}/{total} statted")))
+  }) else {
+    return false;
+  };
+  stage("render", || {
+    let mut terminal = Terminal::with_options(
+      TestBackend::new(120, 1),
+      TerminalOptions {
+        viewport: Viewport::Inline(1),
+      },
+    )?;
+    let widths = Widths::new(
+      &filesystems,
+      settings,
+      &SizeFormat::Human,
+      &TableOptions::default(),
+    );
+    for fs in &filesystems {
+      render::render_line(
+        fs,
+        &mut terminal,
+        settings,
+        &widths,
+        &SizeFormat::Human,
+        false,
+      )?;
+    }
+    Ok(((), format!("{} rows", filesystems.len())))
+  })
+  .is_some()
+}
@@ -1,47 +1,374 @@
-use anyhow::Result;
-use clap::ArgMatches;
-use config::{Config, Environment, File};
+use crate::{
+  anonymize::{anonymize_device, anonymize_host, anonymize_path, stable_hash},
+  expr::{self, Expr},
+  filesystem::Filesystem,
+  fsext::{is_dummy_filesystem, MountInfo, DUMMY_FILESYSTEMS},
+  icons::IconStyle,
+  render::Column,
+  template::Template,
+  units::{ByteSize, SizeFormat},
+};
+use anyhow::{bail, Context, Result};
+use clap::{ArgMatches, ValueEnum};
+use config::{Config, Environment, File, FileFormat, Map, Value, ValueKind};
 use directories::ProjectDirs;
+use regex::Regex;
 use serde::Deserialize;
-use std::path::Path;
+use std::{
+  borrow::Cow,
+  cmp::Ordering,
+  collections::HashMap,
+  fmt, fs,
+  io::IsTerminal,
+  mem,
+  path::{Path, PathBuf},
+  time::Duration,
+};
 use tracing::{debug, instrument};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 pub(crate) struct Settings {
+  /// Whitelist of mounts, checked before `exclude`
+  pub(crate) include: Option<Vec<MountRule>>,
   /// Exclusion list for mounts
-  pub(crate) exclude: Option<Vec<Exclusion>>,
+  pub(crate) exclude: Option<Vec<MountRule>>,
   /// Thredsholds for
   pub(crate) threshold: Option<ColorThreshold>,
+  /// Display names keyed by device name or mount directory
+  pub(crate) alias: Option<HashMap<String, String>>,
+  /// Show the device name after the alias instead of hiding it
+  pub(crate) alias_with_source: Option<bool>,
+  /// Hash device names, server names and UUIDs in the output
+  pub(crate) anonymize: Option<bool>,
+  /// Print human readable sizes with powers of 1000
+  pub(crate) si: Option<bool>,
+  /// Show the free percentage in the gauges instead of the used one
+  pub(crate) free_percent: Option<bool>,
+  /// Columns of the table, in order
+  pub(crate) columns: Option<Vec<Column>>,
+  /// Icons before the rows of the table
+  pub(crate) icons: Option<IconStyle>,
+  /// Arrows after the percentages telling whether the usage grew since a previous run
+  pub(crate) trend: Option<bool>,
+  /// Age of the run the trend arrows compare with, the previous one by default
+  pub(crate) trend_window: Option<Interval>,
+  /// Order of the filesystems, the mount table order when unset
+  pub(crate) sort: Option<SortKey>,
+  /// Filters of the listed filesystems
+  pub(crate) filter: Option<Filter>,
+  /// Command run for each filesystem above its high threshold
+  pub(crate) on_high: Option<Template>,
+  /// Time before the actions run again for a filesystem still above its threshold
+  pub(crate) on_high_cooldown: Option<Interval>,
+  /// Alerts sent for the filesystems above a threshold
+  pub(crate) alert: Option<Alert>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Alert {
+  pub(crate) webhook: Option<Webhook>,
 }
 
+/// `[alert.webhook]`: URL the filesystems above `threshold` are POSTed to, in JSON.
 #[derive(Debug, Deserialize)]
-pub(crate) enum Exclusion {
+pub(crate) struct Webhook {
+  pub(crate) url: String,
+  /// Lowest level alerted, `high` by default
+  pub(crate) threshold: Option<Severity>,
+  /// Attempts after a failed request
+  pub(crate) retries: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) enum MountRule {
   #[serde(rename = "mount_dir_starts_with")]
   MountDirStartsWith(String),
   #[serde(rename = "fstype")]
   FsType(String),
+  #[serde(rename = "mount_dir_matches")]
+  MountDirMatches(Pattern),
+  #[serde(rename = "dev_name_matches")]
+  DevNameMatches(Pattern),
+  #[serde(rename = "mount_dir_glob")]
+  MountDirGlob(Glob),
+}
+
+impl MountRule {
+  /// Whether the mount is the one described by the rule.
+  pub(crate) fn matches(&self, mount_info: &MountInfo) -> bool {
+    match self {
+      MountRule::MountDirStartsWith(name) => mount_info.mount_dir.starts_with(name),
+      MountRule::FsType(typ) => mount_info.fs_type == *typ,
+      MountRule::MountDirMatches(pattern) => pattern.0.is_match(&mount_info.mount_dir),
+      MountRule::DevNameMatches(pattern) => pattern.0.is_match(&mount_info.dev_name),
+      MountRule::MountDirGlob(glob) => glob.0.matches_with(&mount_info.mount_dir, Glob::OPTIONS),
+    }
+  }
+}
+
+impl fmt::Display for MountRule {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      MountRule::MountDirStartsWith(dir) => write!(f, "{dir}*"),
+      MountRule::FsType(fstype) => write!(f, "type {fstype}"),
+      MountRule::MountDirMatches(pattern) => write!(f, "~ {}", pattern.0),
+      MountRule::DevNameMatches(pattern) => write!(f, "device ~ {}", pattern.0),
+      MountRule::MountDirGlob(glob) => write!(f, "{}", glob.0),
+    }
+  }
+}
+
+/// A regex of the settings, compiled when they are loaded. Unanchored: it matches anywhere unless
+/// written with `^` and `$`.
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub(crate) struct Pattern(pub(crate) Regex);
+
+impl TryFrom<String> for Pattern {
+  type Error = regex::Error;
+
+  fn try_from(pattern: String) -> Result<Self, Self::Error> {
+    Regex::new(&pattern).map(Pattern)
+  }
+}
+
+/// A glob of mount directories: `*` stays within a directory, `**` crosses them.
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub(crate) struct Glob(pub(crate) glob::Pattern);
+
+impl Glob {
+  const OPTIONS: glob::MatchOptions = glob::MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+  };
+}
+
+impl TryFrom<String> for Glob {
+  type Error = glob::PatternError;
+
+  fn try_from(pattern: String) -> Result<Self, Self::Error> {
+    glob::Pattern::new(&pattern).map(Glob)
+  }
+}
+
+/// A duration like `30m` or `1h 30m`.
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub(crate) struct Interval(pub(crate) Duration);
+
+impl TryFrom<String> for Interval {
+  type Error = humantime::DurationError;
+
+  fn try_from(value: String) -> Result<Self, Self::Error> {
+    humantime::parse_duration(&value).map(Interval)
+  }
+}
+
+/// Rule of an `--exclude-mount` argument: a glob when it has glob characters, a prefix otherwise.
+fn mount_rule(value: String) -> Result<MountRule> {
+  if value.contains(['*', '?', '[']) {
+    let glob = Glob::try_from(value.clone()).with_context(|| format!("--exclude-mount {value}"))?;
+    Ok(MountRule::MountDirGlob(glob))
+  } else {
+    Ok(MountRule::MountDirStartsWith(value))
+  }
+}
+
+/// Field the filesystems are sorted by, smallest first.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum SortKey {
+  Size,
+  Used,
+  Avail,
+  Pcent,
+  Source,
+  Target,
+  Fstype,
+}
+
+impl SortKey {
+  /// Order of `a` and `b`, unknown sizes first. Names are compared as shown in the output.
+  pub(crate) fn compare(self, a: &Filesystem, b: &Filesystem, settings: &Settings) -> Ordering {
+    match self {
+      Self::Size => a.size().cmp(&b.size()),
+      Self::Used => a.used().cmp(&b.used()),
+      Self::Avail => a.avail().cmp(&b.avail()),
+      Self::Pcent => a
+        .used_ratio()
+        .partial_cmp(&b.used_ratio())
+        .unwrap_or(Ordering::Equal),
+      Self::Source => settings
+        .device_label(&a.mount_info)
+        .cmp(&settings.device_label(&b.mount_info)),
+      Self::Target => settings
+        .mount_label(&a.mount_info)
+        .cmp(&settings.mount_label(&b.mount_info)),
+      Self::Fstype => a.mount_info.fs_type.cmp(&b.mount_info.fs_type),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub(crate) struct Filter {
+  /// Filesystems smaller than this are hidden
+  pub(crate) min_size: Option<ByteSize>,
+  /// Types of the dummy filesystems hidden without `--all`, instead of the built-in list
+  pub(crate) dummy: Option<Vec<String>>,
+  /// `--where` expressions by name, usable in the other ones
+  #[serde(default, rename = "where")]
+  pub(crate) named: HashMap<String, String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub(crate) struct ColorThreshold {
-  pub(crate) medium: Option<f64>,
-  pub(crate) high: Option<f64>,
+  pub(crate) medium: Option<Threshold>,
+  pub(crate) high: Option<Threshold>,
+  /// Thresholds of some mounts or filesystem types, in place of these ones
+  #[serde(default, rename = "override")]
+  pub(crate) overrides: Vec<ThresholdOverride>,
+}
+
+/// `[[threshold.override]]`: thresholds of the filesystems mounted at `mount_dir` or of type
+/// `fstype`, both when both are given.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct ThresholdOverride {
+  pub(crate) mount_dir: Option<String>,
+  pub(crate) fstype: Option<String>,
+  pub(crate) medium: Option<Threshold>,
+  pub(crate) high: Option<Threshold>,
+}
+
+impl ThresholdOverride {
+  fn applies(&self, mount_dir: &str, fs_type: &str) -> bool {
+    self.mount_dir.as_deref().is_none_or(|dir| dir == mount_dir)
+      && self
+        .fstype
+        .as_deref()
+        .is_none_or(|fstype| fstype == fs_type)
+  }
 }
 
-const MEDIUM_DEFAULT: f64 = 0.75;
-const HIGH_DEFAULT: f64 = 0.90;
+/// A usage threshold: a used ratio (`0.9`), or an available space (`"5G"`) a filesystem with
+/// less free space is above of. A percentage means little on the largest filesystems.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum Threshold {
+  Ratio(f64),
+  Free(ByteSize),
+}
+
+impl Threshold {
+  /// Used bytes of a filesystem of `usable` bytes at the threshold.
+  pub(crate) fn used_bytes(self, usable: u64) -> u64 {
+    match self {
+      Self::Ratio(ratio) => (ratio * usable as f64).round() as u64,
+      Self::Free(free) => usable.saturating_sub(free.0),
+    }
+  }
+
+  /// Whether a filesystem with the used `ratio` and `avail` bytes available is above the
+  /// threshold. Free space thresholds don't apply when the available space is unknown.
+  fn exceeded(self, ratio: f64, avail: Option<u64>) -> bool {
+    match self {
+      Self::Ratio(threshold) => ratio > threshold,
+      Self::Free(free) => avail.is_some_and(|avail| avail < free.0),
+    }
+  }
+
+  fn check(self) -> Result<(), String> {
+    match self {
+      Self::Ratio(ratio) if !(0.0..=1.0).contains(&ratio) => Err(format!(
+        "thresholds must be between 0 and 1 or sizes, got {ratio}"
+      )),
+      _ => Ok(()),
+    }
+  }
+}
+
+impl fmt::Display for Threshold {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Ratio(ratio) => write!(f, "{:.0}%", 100.0 * ratio),
+      Self::Free(free) => write!(f, "{} free", SizeFormat::Human.format(free.0)),
+    }
+  }
+}
+
+/// Thresholds of a filesystem.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Thresholds {
+  pub(crate) medium: Threshold,
+  pub(crate) high: Threshold,
+}
+
+impl Thresholds {
+  /// Severity of a filesystem with the used `ratio` and `avail` bytes available: above the high
+  /// threshold, above the medium one, or below.
+  pub(crate) fn severity(self, ratio: f64, avail: Option<u64>) -> Severity {
+    if self.high.exceeded(ratio, avail) {
+      Severity::High
+    } else if self.medium.exceeded(ratio, avail) {
+      Severity::Medium
+    } else {
+      Severity::Low
+    }
+  }
+
+  /// Reject a medium threshold above the high one, when they are of the same kind.
+  fn check(self) -> Result<(), String> {
+    self.medium.check()?;
+    self.high.check()?;
+    match (self.medium, self.high) {
+      (Threshold::Ratio(medium), Threshold::Ratio(high)) if medium > high => {
+        Err(format!("medium ({medium}) is above high ({high})"))
+      }
+      (Threshold::Free(medium), Threshold::Free(high)) if medium.0 < high.0 => Err(format!(
+        "medium ({}) is below high ({})",
+        self.medium, self.high
+      )),
+      _ => Ok(()),
+    }
+  }
+}
+
+/// Usage level of a filesystem relative to the thresholds, from the lowest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Severity {
+  Low,
+  Medium,
+  High,
+}
+
+impl Severity {
+  /// Exit code of `--check` and of the Nagios plugins: 0 for OK, 1 for WARNING, 2 for CRITICAL.
+  pub(crate) fn exit_code(self) -> i32 {
+    match self {
+      Self::Low => 0,
+      Self::Medium => 1,
+      Self::High => 2,
+    }
+  }
+}
+
+const MEDIUM_DEFAULT: Threshold = Threshold::Ratio(0.75);
+const HIGH_DEFAULT: Threshold = Threshold::Ratio(0.90);
 
 impl Default for ColorThreshold {
   fn default() -> Self {
     Self {
       medium: Some(MEDIUM_DEFAULT),
       high: Some(HIGH_DEFAULT),
+      overrides: vec![],
     }
   }
 }
 
 impl Settings {
-  pub(crate) fn medium_threshold(&self) -> f64 {
+  pub(crate) fn medium_threshold(&self) -> Threshold {
     self
       .threshold
       .clone()
@@ -50,7 +377,86 @@ impl Settings {
       .unwrap_or(MEDIUM_DEFAULT)
   }
 
-  pub(crate) fn high_threshold(&self) -> f64 {
+  /// Display alias configured for a mount, looked up by device name first then by mount directory.
+  pub(crate) fn alias(&self, mount_info: &MountInfo) -> Option<&str> {
+    // `config` lowercases table keys, so they are matched case-insensitively.
+    let lookup = |key: &str| {
+      self
+        .alias
+        .as_ref()?
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(key))
+        .map(|(_, name)| name.as_str())
+    };
+    lookup(&mount_info.dev_name).or_else(|| lookup(&mount_info.mount_dir))
+  }
+
+  /// Device name as shown in the output.
+  pub(crate) fn source_label<'a>(&self, mount_info: &'a MountInfo) -> Cow<'a, str> {
+    if self.anonymize.unwrap_or_default() {
+      anonymize_device(&mount_info.dev_name).into()
+    } else {
+      mount_info.dev_name.as_str().into()
+    }
+  }
+
+  /// Name shown in the filesystem column: the alias if any, followed by the device name with
+  /// `alias_with_source`, the device name otherwise.
+  pub(crate) fn device_label<'a>(&'a self, mount_info: &'a MountInfo) -> Cow<'a, str> {
+    match self.alias(mount_info) {
+      Some(alias) if self.alias_with_source.unwrap_or_default() => {
+        format!("{alias} ({})", self.source_label(mount_info)).into()
+      }
+      Some(alias) => alias.into(),
+      None => self.source_label(mount_info),
+    }
+  }
+
+  /// Filesystem id as shown in the output, with the UUID hashed when anonymizing.
+  pub(crate) fn id_label(&self, id: String) -> String {
+    match id.strip_prefix("uuid:") {
+      Some(rest) if self.anonymize.unwrap_or_default() => {
+        let (uuid, root) = rest.split_once('/').unwrap_or((rest, ""));
+        let root = if root.is_empty() {
+          String::new()
+        } else {
+          format!("/{root}")
+        };
+        format!("uuid:{}{root}", stable_hash(uuid))
+      }
+      _ => id,
+    }
+  }
+
+  /// Host name as shown in the output.
+  pub(crate) fn hostname_label(&self, hostname: String) -> String {
+    if self.anonymize.unwrap_or_default() {
+      anonymize_host(&hostname)
+    } else {
+      hostname
+    }
+  }
+
+  /// Mount directory as shown in the output.
+  pub(crate) fn mount_label<'a>(&self, mount_info: &'a MountInfo) -> Cow<'a, str> {
+    if self.anonymize.unwrap_or_default() {
+      anonymize_path(&mount_info.mount_dir).into()
+    } else {
+      mount_info.mount_dir.as_str().into()
+    }
+  }
+
+  /// Ratio shown for a used ratio: the free one with `free_percent`. The thresholds still apply to
+  /// the used ratio.
+  pub(crate) fn shown_ratio(&self, used: f64) -> f64 {
+    if self.free_percent.unwrap_or_default() {
+      1.0 - used
+    } else {
+      used
+    }
+  }
+
+  pub(crate) fn high_threshold(&self) -> Threshold {
     self
       .threshold
       .clone()
@@ -58,30 +464,718 @@ impl Settings {
       .high
       .unwrap_or(HIGH_DEFAULT)
   }
+
+  /// Whether the mount is a dummy filesystem, hidden without `--all`.
+  pub(crate) fn is_dummy(&self, mount_info: &MountInfo) -> bool {
+    let (fs_type, options) = (&mount_info.fs_type, &mount_info.mount_option);
+    match self
+      .filter
+      .as_ref()
+      .and_then(|filter| filter.dummy.as_deref())
+    {
+      Some(dummies) => is_dummy_filesystem(fs_type, options, dummies),
+      None => is_dummy_filesystem(fs_type, options, DUMMY_FILESYSTEMS),
+    }
+  }
+
+  /// Size below which the filesystems are hidden, in bytes.
+  pub(crate) fn min_size(&self) -> u64 {
+    self
+      .filter
+      .as_ref()
+      .and_then(|filter| filter.min_size)
+      .map_or(0, |size| size.0)
+  }
+
+  /// Parse a `--where` expression, with the named filters of the settings.
+  pub(crate) fn parse_where(&self, source: &str) -> Result<Expr, String> {
+    let none = HashMap::new();
+    let named = self.filter.as_ref().map_or(&none, |filter| &filter.named);
+    expr::parse(source, named)
+  }
+
+  /// Thresholds of the filesystem of type `fs_type` mounted at `mount_dir`: the matching
+  /// overrides, the ones of a mount directory taking precedence over the ones of a type, then the
+  /// thresholds of every filesystem.
+  pub(crate) fn thresholds(&self, mount_dir: &str, fs_type: &str) -> Thresholds {
+    let mut thresholds = Thresholds {
+      medium: self.medium_threshold(),
+      high: self.high_threshold(),
+    };
+    let overrides = self
+      .threshold
+      .as_ref()
+      .map_or(&[][..], |threshold| &threshold.overrides);
+    let (by_mount, by_type): (Vec<_>, Vec<_>) = overrides
+      .iter()
+      .filter(|rule| rule.applies(mount_dir, fs_type))
+      .partition(|rule| rule.mount_dir.is_some());
+    for rule in by_type.into_iter().chain(by_mount) {
+      thresholds.medium = rule.medium.unwrap_or(thresholds.medium);
+      thresholds.high = rule.high.unwrap_or(thresholds.high);
+    }
+    thresholds
+  }
+
+  /// Severity of `fs` with the used `ratio`. The empty pseudo filesystems have no free space to
+  /// run out of.
+  pub(crate) fn severity(&self, fs: &Filesystem, ratio: f64) -> Severity {
+    let avail = fs
+      .avail()
+      .filter(|_| fs.size().is_some_and(|size| size > 0));
+    self
+      .thresholds(&fs.mount_info.mount_dir, &fs.mount_info.fs_type)
+      .severity(ratio, avail)
+  }
+}
+
+/// Version of the settings schema understood by this build.
+pub(crate) const SETTINGS_VERSION: u32 = 1;
+
+/// Keys renamed or moved between two schema versions, as `(old path, new path)`.
+struct Migration {
+  to: u32,
+  renames: &'static [(&'static str, &'static str)],
+}
+
+/// Migrations applied in order to settings older than [`SETTINGS_VERSION`].
+/// Files without a `version` key are considered to be version 0.
+const MIGRATIONS: &[Migration] = &[Migration {
+  to: 1,
+  renames: &[
+    ("exclusions", "exclude"),
+    ("excludes", "exclude"),
+    ("thresholds", "threshold"),
+    ("threshold.warning", "threshold.medium"),
+    ("threshold.critical", "threshold.high"),
+  ],
+}];
+
+fn get_path<'a>(table: &'a Map<String, Value>, path: &str) -> Option<&'a Value> {
+  match path.split_once('.') {
+    None => table.get(path),
+    Some((head, tail)) => match &table.get(head)?.kind {
+      ValueKind::Table(inner) => get_path(inner, tail),
+      _ => None,
+    },
+  }
+}
+
+fn take_path(table: &mut Map<String, Value>, path: &str) -> Option<Value> {
+  match path.split_once('.') {
+    None => table.remove(path),
+    Some((head, tail)) => match &mut table.get_mut(head)?.kind {
+      ValueKind::Table(inner) => take_path(inner, tail),
+      _ => None,
+    },
+  }
+}
+
+fn put_path(table: &mut Map<String, Value>, path: &str, value: Value) -> bool {
+  match path.split_once('.') {
+    None if table.contains_key(path) => false,
+    None => {
+      table.insert(path.to_string(), value);
+      true
+    }
+    Some((head, tail)) => {
+      let entry = table
+        .entry(head.to_string())
+        .or_insert_with(|| Value::new(None, ValueKind::Table(Map::new())));
+      match &mut entry.kind {
+        ValueKind::Table(inner) => put_path(inner, tail, value),
+        _ => false,
+      }
+    }
+  }
+}
+
+/// Upgrade a raw settings table to [`SETTINGS_VERSION`], warning about every
+/// deprecated key found on the way.
+fn migrate(mut table: Map<String, Value>) -> Result<Map<String, Value>> {
+  let version = match table.get("version") {
+    Some(value) => {
+      let version = value.clone().into_uint().context("version")?;
+      u32::try_from(version).with_context(|| format!("version: {version} is out of range"))?
+    }
+    None => 0,
+  };
+
+  if version > SETTINGS_VERSION {
+    eprintln!(
+      "ddf: warning: settings version {version} is newer than the supported version \
+       {SETTINGS_VERSION}, some options may be ignored"
+    );
+    return Ok(table);
+  }
+
+  for migration in MIGRATIONS.iter().filter(|m| m.to > version) {
+    for (old, new) in migration.renames {
+      if let Some(value) = take_path(&mut table, old) {
+        // The new key only counts as set when it comes from a source, a file or the environment,
+        // not from a command line override.
+        let set = !put_path(&mut table, new, value)
+          && get_path(&table, new).is_some_and(|value| value.origin().is_some());
+        if set {
+          eprintln!("ddf: warning: settings key `{old}` is deprecated and ignored, `{new}` is set");
+        } else {
+          eprintln!("ddf: warning: settings key `{old}` is deprecated, use `{new}` instead");
+        }
+      }
+    }
+  }
+  table.insert(
+    "version".to_string(),
+    Value::new(None, ValueKind::U64(SETTINGS_VERSION.into())),
+  );
+  Ok(table)
+}
+
+/// Exit code of `--check-config` when the settings file doesn't exist, `EX_NOINPUT` of
+/// `sysexits.h`: the low codes are taken by the usage errors and the checks.
+pub(crate) const EXIT_CONFIG_NOT_FOUND: i32 = 66;
+/// Exit code of `--check-config` when the settings file can't be loaded, `EX_CONFIG`.
+pub(crate) const EXIT_CONFIG_INVALID: i32 = 78;
+
+impl Settings {
+  /// Whether the mount passes the `include` whitelist. Rules of the same kind are alternatives and
+  /// every kind must match: `fstype` ext4 and xfs with `mount_dir_starts_with` /srv keeps the ext4
+  /// and xfs mounts under /srv.
+  pub(crate) fn includes(&self, mount_info: &MountInfo) -> bool {
+    let rules = self.include.as_deref().unwrap_or_default();
+    rules.iter().all(|rule| {
+      rules
+        .iter()
+        .filter(|other| mem::discriminant(*other) == mem::discriminant(rule))
+        .any(|other| other.matches(mount_info))
+    })
+  }
+
+  /// Whether the mount is hidden by the `exclude` rules.
+  pub(crate) fn excludes(&self, mount_info: &MountInfo) -> bool {
+    self
+      .exclude
+      .as_deref()
+      .unwrap_or_default()
+      .iter()
+      .any(|rule| rule.matches(mount_info))
+  }
+
+  /// Reject values that parse but make no sense.
+  fn validate(&self) -> Result<()> {
+    let thresholds = Thresholds {
+      medium: self.medium_threshold(),
+      high: self.high_threshold(),
+    };
+    if let Err(error) = thresholds.check() {
+      bail!("threshold: {error}");
+    }
+    let overrides = self
+      .threshold
+      .as_ref()
+      .map(|threshold| &threshold.overrides);
+    for (i, rule) in overrides.into_iter().flatten().enumerate() {
+      let name = format!("threshold.override[{i}]");
+      if rule.mount_dir.is_none() && rule.fstype.is_none() {
+        bail!("{name} needs a mount_dir or a fstype");
+      }
+      // Thresholds of a filesystem the rule applies to.
+      let thresholds = self.thresholds(
+        rule.mount_dir.as_deref().unwrap_or_default(),
+        rule.fstype.as_deref().unwrap_or_default(),
+      );
+      if let Err(error) = thresholds.check() {
+        bail!("{name}: {error}");
+      }
+    }
+    if let Some(webhook) = self.alert.as_ref().and_then(|alert| alert.webhook.as_ref()) {
+      if !webhook.url.starts_with("http://") && !webhook.url.starts_with("https://") {
+        bail!(
+          "alert.webhook.url: not an http or https URL: {}",
+          webhook.url
+        );
+      }
+    }
+    if let Some(columns) = &self.columns {
+      if columns.is_empty() {
+        bail!("columns must not be empty");
+      }
+      let mut seen = vec![];
+      for column in columns {
+        if seen.contains(column) {
+          bail!("columns lists {column:?} twice");
+        }
+        seen.push(*column);
+      }
+    }
+    if let Some(filter) = &self.filter {
+      for (name, source) in &filter.named {
+        if let Err(error) = expr::parse(source, &filter.named) {
+          bail!("filter.where.{name}: {error}");
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+/// Settings used when there is no settings file, and written by `ddf config init`.
+pub(crate) const DEFAULT_SETTINGS: &str = r#"version=1
+
+# Mounts hidden from the table, shown anyway by `ddf -a`.
+[[exclude]]
+fstype="tmpfs"
+[[exclude]]
+fstype="devtmpfs"
+
+# Filesystem types hidden without `ddf -a`, instead of the built-in list.
+# [filter]
+# dummy = ["autofs", "proc", "squashfs", "overlay"]
+
+# Named `--where` expressions, e.g. `ddf --where full`.
+# [filter.where]
+# full = "pcent >= 90"
+# local = 'fstype != "nfs" && fstype != "cifs"'
+
+# Columns of the table, in order, among source, size, used, avail, reserved, target, pcent,
+# options, uuid, disk, mounted and devno.
+# columns = ["source", "size", "used", "avail", "target", "pcent"]
+
+# Icon of the kind of filesystem before each row: "nerd" (Nerd Font), "emoji" or "text".
+# icons = "nerd"
+
+# Used ratios above which a mount is shown in yellow, then in red, or free spaces below which
+# (like "5G"), and the ones of some mounts or filesystem types.
+# [threshold]
+# medium=0.75
+# high=0.9
+# [[threshold.override]]
+# mount_dir = "/var/log"
+# high = 0.7
+# [[threshold.override]]
+# fstype = "tmpfs"
+# high = 1.0
+# [[threshold.override]]
+# mount_dir = "/"
+# high = "5G"
+
+# Arrows after the percentages, `↑` when the usage grew since a previous run, `↓` when it shrank
+# and `→` when it stayed flat, compared with the run at least `trend_window` old.
+# trend = true
+# trend_window = "24h"
+
+# Command run for each filesystem above its high threshold, with the `--format-string` fields
+# (quoted for `sh`), then again after the cooldown while it stays above.
+# on_high = "notify-send 'disk full' {mount}"
+# on_high_cooldown = "1h"
+
+# JSON POSTed to a URL (a Slack incoming webhook, a generic receiver...) for the filesystems above
+# the threshold, `high` or `medium`, retried on errors.
+# [alert.webhook]
+# url = "https://hooks.example.com/ddf"
+# threshold = "high"
+# retries = 3
+
+# Names shown instead of a device name or a mount directory.
+# `alias_with_source = true` keeps the device name after them.
+# [alias]
+# "/dev/mapper/luks-0b7c" = "laptop-root"
+# "/srv/backup" = "Backup RAID"
+"#;
+
+/// Marker of the data directory recording that the first run hint was shown.
+const FIRST_RUN_MARKER: &str = "first-run";
+
+fn project_dirs() -> Option<ProjectDirs> {
+  ProjectDirs::from("org", "djedi", "ddf")
+}
+
+/// Directory of the data that can be recomputed or lost, like the usage history.
+pub(crate) fn cache_dir() -> Option<PathBuf> {
+  project_dirs().map(|dirs| dirs.cache_dir().to_path_buf())
+}
+
+/// Directory of the state kept between runs, the data directory where there is no state one.
+pub(crate) fn state_dir() -> Option<PathBuf> {
+  let dirs = project_dirs()?;
+  Some(dirs.state_dir().unwrap_or(dirs.data_dir()).to_path_buf())
+}
+
+/// Path of the settings file: the `--config` argument, or `settings.toml` in the user's
+/// configuration directory.
+fn settings_path(matches: &ArgMatches) -> Option<PathBuf> {
+  matches.get_one::<PathBuf>("config").cloned().or_else(|| {
+    project_dirs().map(|proj_dirs| Path::new(proj_dirs.config_dir()).join("settings.toml"))
+  })
+}
+
+/// `ddf config init`: write [`DEFAULT_SETTINGS`] to the settings file.
+pub(crate) fn init(matches: &ArgMatches, force: bool) -> Result<()> {
+  let Some(path) = settings_path(matches) else {
+    bail!("no configuration directory");
+  };
+  if path.exists() && !force {
+    bail!(
+      "{}: already exists, use --force to replace it",
+      path.display()
+    );
+  }
+  if let Some(dir) = path.parent() {
+    fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+  }
+  fs::write(&path, DEFAULT_SETTINGS).with_context(|| format!("writing {}", path.display()))?;
+  println!("{}", path.display());
+  Ok(())
+}
+
+/// On the first run without a settings file, tell on stderr which mounts are hidden and how to
+/// change it. Not repeated once the marker is written in the data directory.
+pub(crate) fn first_run_hint(matches: &ArgMatches) {
+  if !std::io::stderr().is_terminal() || settings_path(matches).is_some_and(|path| path.exists()) {
+    return;
+  }
+  let Some(marker) = project_dirs().map(|dirs| dirs.data_dir().join(FIRST_RUN_MARKER)) else {
+    return;
+  };
+  if marker.exists() {
+    return;
+  }
+  eprintln!(
+    "ddf: no settings file, the tmpfs and devtmpfs mounts are hidden (`ddf -a` shows them). \
+     Run `ddf config init` to write the settings and choose what to hide. This hint is only \
+     shown once."
+  );
+  let written = marker
+    .parent()
+    .map_or(Ok(()), fs::create_dir_all)
+    .and_then(|()| fs::write(&marker, ""));
+  if let Err(error) = written {
+    debug!("{}: {error}", marker.display());
+  }
+}
+
+/// Load the settings file and report whether it is valid, returning the process exit code.
+pub(crate) fn check_config(matches: &ArgMatches) -> i32 {
+  let Some(path) = settings_path(matches) else {
+    eprintln!("ddf: no configuration directory");
+    return EXIT_CONFIG_NOT_FOUND;
+  };
+  if !path.is_file() {
+    eprintln!("ddf: {}: settings file not found", path.display());
+    return EXIT_CONFIG_NOT_FOUND;
+  }
+  match settings(matches) {
+    Ok(_) => {
+      println!("{}: ok", path.display());
+      0
+    }
+    Err(error) => {
+      eprintln!("ddf: {}: {error:#}", path.display());
+      EXIT_CONFIG_INVALID
+    }
+  }
 }
 
-#[instrument(skip(_matches))]
-pub(crate) fn settings(_matches: &ArgMatches) -> Result<Settings> {
-  let qualifier: &str = "org";
-  let organisation: &str = "djedi";
-  let application: &str = "ddf";
+#[instrument(skip(matches))]
+pub(crate) fn settings(matches: &ArgMatches) -> Result<Settings> {
   let env_prefix: &str = "DDF";
   let mut settings_builder = Config::builder();
   settings_builder = settings_builder.set_default("uri", "http://localhost:8080")?;
 
-  if let Some(proj_dirs) = ProjectDirs::from(qualifier, organisation, application) {
-    let path = Path::new(proj_dirs.config_dir()).join("settings.toml");
-    let path = path.to_str().unwrap();
-    settings_builder = settings_builder.add_source(File::with_name(path).required(false));
-    settings_builder = settings_builder.set_default("configuration_path", path)?;
+  match settings_path(matches) {
+    Some(path) if path.exists() || matches.get_one::<PathBuf>("config").is_some() => {
+      let path = path.to_str().context("configuration path is not UTF-8")?;
+      settings_builder = settings_builder.add_source(File::with_name(path));
+      settings_builder = settings_builder.set_default("configuration_path", path)?;
 
-    debug!("Try to load config file: {}", &path);
+      debug!("Try to load config file: {}", &path);
+    }
+    _ => {
+      settings_builder =
+        settings_builder.add_source(File::from_str(DEFAULT_SETTINGS, FileFormat::Toml));
+    }
   }
   settings_builder = settings_builder.add_source(Environment::with_prefix(env_prefix));
+  for flag in ["anonymize", "si", "free_percent"] {
+    if matches.get_flag(flag) {
+      settings_builder = settings_builder.set_override(flag, true)?;
+    }
+  }
+  for level in ["medium", "high"] {
+    let key = format!("threshold.{level}");
+    settings_builder = match matches.get_one::<Threshold>(&format!("{level}_threshold")) {
+      Some(Threshold::Ratio(ratio)) => settings_builder.set_override(key, *ratio)?,
+      Some(Threshold::Free(free)) => settings_builder.set_override(key, free.0.to_string())?,
+      None => settings_builder,
+    };
+  }
+  if let Some(min_size) = matches.get_one::<u64>("min_size") {
+    settings_builder = settings_builder.set_override("filter.min_size", min_size.to_string())?;
+  }
   let config = settings_builder.build()?;
-  let settings: Settings = config.clone().try_deserialize()?;
+  // The table of the cache keeps the origins of the values, for `migrate`.
+  let table = migrate(config.cache.into_table()?)?;
+  let mut settings: Settings = Value::new(None, ValueKind::Table(table)).try_deserialize()?;
+  settings.validate()?;
+  let values = |id: &str| {
+    matches
+      .get_many::<String>(id)
+      .into_iter()
+      .flatten()
+      .cloned()
+  };
+  let mut exclusions: Vec<MountRule> = values("exclude_type").map(MountRule::FsType).collect();
+  for value in values("exclude_mount") {
+    exclusions.push(mount_rule(value)?);
+  }
+  if !exclusions.is_empty() {
+    settings
+      .exclude
+      .get_or_insert_with(Vec::new)
+      .extend(exclusions);
+  }
 
   debug!("{:#?}", settings);
 
   Ok(settings)
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_exclusion_matches() {
+    let exclusions: Vec<MountRule> = Config::builder()
+      .add_source(File::from_str(
+        "[[exclude]]\nmount_dir_matches='^/run/user/[0-9]+/gvfs'\n\
+         [[exclude]]\ndev_name_matches='^/dev/loop'\n",
+        FileFormat::Toml,
+      ))
+      .build()
+      .unwrap()
+      .get("exclude")
+      .unwrap();
+    let mount = |dev_name: &str, mount_dir: &str| MountInfo {
+      dev_name: dev_name.to_string(),
+      mount_dir: mount_dir.to_string(),
+      ..Default::default()
+    };
+    let excluded = |info: MountInfo| exclusions.iter().any(|rule| rule.matches(&info));
+    assert!(excluded(mount("gvfsd-fuse", "/run/user/1000/gvfs")));
+    assert!(!excluded(mount("/dev/sdb1", "/run/media/usb")));
+    assert!(excluded(mount("/dev/loop3", "/snap/core/1")));
+    assert!(serde_json::from_str::<Pattern>("\"(\"").is_err());
+  }
+
+  #[test]
+  fn test_glob_rules() {
+    let rule = |value: &str| mount_rule(value.to_string()).unwrap();
+    let mount = |mount_dir: &str| MountInfo {
+      mount_dir: mount_dir.to_string(),
+      ..Default::default()
+    };
+    let cache = rule("/home/*/.cache");
+    assert!(cache.matches(&mount("/home/ada/.cache")));
+    assert!(!cache.matches(&mount("/home/ada/src/.cache")));
+    let docker = rule("/var/lib/docker/**");
+    assert!(docker.matches(&mount("/var/lib/docker/overlay2/0f3a/merged")));
+    assert!(!docker.matches(&mount("/var/lib/dockerd")));
+    assert!(matches!(rule("/snap/"), MountRule::MountDirStartsWith(_)));
+    assert!(mount_rule("/mnt/[".to_string()).is_err());
+  }
+
+  #[test]
+  fn test_default_settings() {
+    let settings: Settings = Config::builder()
+      .add_source(File::from_str(DEFAULT_SETTINGS, FileFormat::Toml))
+      .build()
+      .unwrap()
+      .try_deserialize()
+      .unwrap();
+    settings.validate().unwrap();
+    let tmpfs = MountInfo {
+      fs_type: "tmpfs".to_string(),
+      ..Default::default()
+    };
+    assert!(settings.excludes(&tmpfs));
+    assert_eq!(settings.exclude.map(|exclude| exclude.len()), Some(2));
+  }
+
+  #[test]
+  fn test_includes() {
+    let settings = Settings {
+      include: Some(vec![
+        MountRule::FsType("ext4".to_string()),
+        MountRule::FsType("xfs".to_string()),
+        MountRule::MountDirStartsWith("/srv".to_string()),
+      ]),
+      exclude: Some(vec![MountRule::MountDirStartsWith("/srv/tmp".to_string())]),
+      ..Default::default()
+    };
+    let mount = |fs_type: &str, mount_dir: &str| MountInfo {
+      fs_type: fs_type.to_string(),
+      mount_dir: mount_dir.to_string(),
+      ..Default::default()
+    };
+    assert!(settings.includes(&mount("xfs", "/srv/data")));
+    assert!(!settings.includes(&mount("tmpfs", "/srv/run")));
+    assert!(!settings.includes(&mount("ext4", "/home")));
+    assert!(settings.excludes(&mount("ext4", "/srv/tmp")));
+    assert!(Settings::default().includes(&mount("tmpfs", "/run")));
+  }
+
+  #[test]
+  fn test_thresholds() {
+    let rule = |mount_dir: Option<&str>, fstype: Option<&str>, high: f64| ThresholdOverride {
+      mount_dir: mount_dir.map(str::to_string),
+      fstype: fstype.map(str::to_string),
+      medium: None,
+      high: Some(Threshold::Ratio(high)),
+    };
+    let settings = Settings {
+      threshold: Some(ColorThreshold {
+        medium: Some(Threshold::Ratio(0.6)),
+        high: None,
+        overrides: vec![
+          rule(Some("/var/log"), None, 0.7),
+          rule(None, Some("ext4"), 0.95),
+          rule(None, Some("tmpfs"), 1.0),
+        ],
+      }),
+      ..Default::default()
+    };
+    settings.validate().unwrap();
+    let thresholds = |high| Thresholds {
+      medium: Threshold::Ratio(0.6),
+      high: Threshold::Ratio(high),
+    };
+    assert_eq!(settings.thresholds("/", "ext4"), thresholds(0.95));
+    assert_eq!(settings.thresholds("/var/log", "ext4"), thresholds(0.7));
+    assert_eq!(settings.thresholds("/home", "xfs").high, HIGH_DEFAULT);
+    assert_eq!(thresholds(1.0).severity(1.0, None), Severity::Medium);
+
+    let free = Thresholds {
+      medium: Threshold::Free(ByteSize(20 << 30)),
+      high: Threshold::Free(ByteSize(5 << 30)),
+    };
+    assert_eq!(free.severity(0.99, Some(6 << 40)), Severity::Low);
+    assert_eq!(free.severity(0.5, Some(10 << 30)), Severity::Medium);
+    assert_eq!(free.severity(0.5, Some(1 << 30)), Severity::High);
+    assert_eq!(free.severity(0.5, None), Severity::Low);
+    let swapped = Thresholds {
+      medium: free.high,
+      high: free.medium,
+    };
+    assert_eq!(
+      swapped.check().unwrap_err(),
+      "medium (5GiB free) is below high (20GiB free)"
+    );
+  }
+
+  #[test]
+  fn test_device_label() {
+    let mut settings = Settings {
+      alias: Some(HashMap::from([(
+        "/srv/backup".to_string(),
+        "Backup RAID".to_string(),
+      )])),
+      ..Default::default()
+    };
+    let backup = MountInfo {
+      dev_name: "/dev/md0".to_string(),
+      mount_dir: "/srv/backup".to_string(),
+      ..Default::default()
+    };
+    assert_eq!(settings.device_label(&backup), "Backup RAID");
+    settings.alias_with_source = Some(true);
+    assert_eq!(settings.device_label(&backup), "Backup RAID (/dev/md0)");
+    let home = MountInfo {
+      dev_name: "/dev/sda2".to_string(),
+      mount_dir: "/home".to_string(),
+      ..Default::default()
+    };
+    assert_eq!(settings.device_label(&home), "/dev/sda2");
+  }
+
+  #[test]
+  fn test_migrate() {
+    let migrated = |toml: &str| {
+      migrate(
+        Config::builder()
+          .add_source(File::from_str(toml, FileFormat::Toml))
+          .build()
+          .unwrap()
+          .cache
+          .into_table()
+          .unwrap(),
+      )
+    };
+    let take = |table: &mut Map<String, Value>, path: &str| {
+      take_path(table, path).map(|value| value.to_string())
+    };
+
+    // Old keys are moved, also the ones under a renamed table.
+    let mut table = migrated("exclusions = ['/boot']\n[thresholds]\nwarning = 0.7\n").unwrap();
+    assert_eq!(take(&mut table, "version").as_deref(), Some("1"));
+    assert_eq!(take(&mut table, "threshold.medium").as_deref(), Some("0.7"));
+    assert!(take(&mut table, "exclude").is_some());
+    assert_eq!(take(&mut table, "exclusions"), None);
+    assert_eq!(take(&mut table, "thresholds"), None);
+
+    // Already migrated, the same names are left alone.
+    let mut table =
+      migrated("version = 1\nexclusions = ['/boot']\n[threshold]\nwarning = 0.7\n").unwrap();
+    assert!(take(&mut table, "exclusions").is_some());
+    assert_eq!(
+      take(&mut table, "threshold.warning").as_deref(),
+      Some("0.7")
+    );
+    assert_eq!(take(&mut table, "threshold.medium"), None);
+
+    // Both set, the new key wins and the old one is dropped.
+    let mut table = migrated("[threshold]\ncritical = 0.9\nhigh = 0.95\n").unwrap();
+    assert_eq!(take(&mut table, "threshold.high").as_deref(), Some("0.95"));
+    assert_eq!(take(&mut table, "threshold.critical"), None);
+
+    // Versions that aren't a `u32` are refused, not truncated.
+    assert!(migrated("version = 4294967297\n").is_err());
+    assert!(migrated("version = -1\n").is_err());
+
+    // The command line overrides have no origin, unlike the keys of a file.
+    let tmp = tempfile::TempDir::new().expect("Failed to create temp dir");
+    let path = tmp.path().join("ddf.toml");
+    fs::write(&path, "[threshold]\ncritical = 0.9\nhigh = 0.95\n").unwrap();
+    let table = Config::builder()
+      .add_source(File::from(path.as_path()))
+      .set_override("threshold.medium", 0.5)
+      .unwrap()
+      .build()
+      .unwrap()
+      .cache
+      .into_table()
+      .unwrap();
+    assert!(get_path(&table, "threshold.high")
+      .unwrap()
+      .origin()
+      .is_some());
+    assert_eq!(get_path(&table, "threshold.medium").unwrap().origin(), None);
+  }
+
+  #[test]
+  fn test_put_path() {
+    let mut table = Map::new();
+    let value = |v: u64| Value::new(None, ValueKind::U64(v));
+    assert!(put_path(&mut table, "a.b", value(1)));
+    assert!(!put_path(&mut table, "a.b", value(2)));
+    // Not under a value that isn't a table.
+    assert!(!put_path(&mut table, "a.b.c", value(3)));
+    assert_eq!(
+      take_path(&mut table, "a.b")
+        .map(|v| v.to_string())
+        .as_deref(),
+      Some("1")
+    );
+    assert!(take_path(&mut table, "a.b").is_none());
+  }
+}
@@ -0,0 +1,330 @@
+//! `--repeat N`: sample the usage N times and print its min, average, max and growth, with the
+//! time left before the space and the inodes run out at that rate.
+
+use crate::{filesystem::Filesystem, settings::Settings, units::SizeFormat};
+use anyhow::Result;
+use std::{
+  io::{IsTerminal, Write},
+  thread,
+  time::{Duration, Instant},
+};
+
+/// Used inodes and free inodes of a sample, none when the filesystem has no inode count.
+fn inodes(fs: &Filesystem) -> Option<(u64, u64)> {
+  let usage = &fs.usage;
+  (usage.files > 0).then(|| (usage.files - usage.ffree.min(usage.files), usage.ffree))
+}
+
+/// Used space of one mount over the samples, with the labels of its first sample.
+#[derive(Debug, PartialEq)]
+struct Stats {
+  device: String,
+  mount_dir: String,
+  mount: String,
+  min: u64,
+  max: u64,
+  sum: u128,
+  count: u64,
+  first: u64,
+  last: u64,
+  /// Available space of the last sample.
+  avail: u64,
+  /// Used inodes of the first sample, used and free inodes of the last one.
+  first_inodes: Option<u64>,
+  last_inodes: Option<(u64, u64)>,
+}
+
+impl Stats {
+  fn new(device: String, mount_dir: String, mount: String, used: u64, fs: &Filesystem) -> Self {
+    let inodes = inodes(fs);
+    Self {
+      device,
+      mount_dir,
+      mount,
+      min: used,
+      max: used,
+      sum: used.into(),
+      count: 1,
+      first: used,
+      last: used,
+      avail: fs.avail().unwrap_or_default(),
+      first_inodes: inodes.map(|(used, _)| used),
+      last_inodes: inodes,
+    }
+  }
+
+  fn add(&mut self, used: u64, fs: &Filesystem) {
+    self.min = self.min.min(used);
+    self.max = self.max.max(used);
+    self.sum += u128::from(used);
+    self.count += 1;
+    self.last = used;
+    self.avail = fs.avail().unwrap_or_default();
+    self.last_inodes = inodes(fs);
+  }
+
+  fn avg(&self) -> u64 {
+    (self.sum / u128::from(self.count)) as u64
+  }
+
+  fn growth(&self) -> i128 {
+    i128::from(self.last) - i128::from(self.first)
+  }
+
+  /// Time before the available space runs out at the growth rate over `window` seconds.
+  fn space_left(&self, window: f64) -> Option<Duration> {
+    time_left(self.avail, self.growth(), window)
+  }
+
+  /// Time before the free inodes run out at the growth rate over `window` seconds.
+  fn inodes_left(&self, window: f64) -> Option<Duration> {
+    let (first, (last, free)) = (self.first_inodes?, self.last_inodes?);
+    time_left(free, i128::from(last) - i128::from(first), window)
+  }
+}
+
+/// Time before `free` runs out when `growth` is used every `window` seconds, none when not
+/// growing.
+fn time_left(free: u64, growth: i128, window: f64) -> Option<Duration> {
+  if window <= 0.0 {
+    return None;
+  }
+  time_to_full(free, growth as f64 / window)
+}
+
+/// Time before `free` runs out at `rate` bytes per second, none when not growing.
+pub(crate) fn time_to_full(free: u64, rate: f64) -> Option<Duration> {
+  if rate <= 0.0 {
+    return None;
+  }
+  Duration::try_from_secs_f64(free as f64 / rate).ok()
+}
+
+/// Growth of the used space in bytes per second, the slope of the linear regression of the
+/// `(seconds, used)` samples. None with less than two samples at different times.
+pub(crate) fn growth_rate(samples: &[(f64, u64)]) -> Option<f64> {
+  let count = samples.len() as f64;
+  if samples.len() < 2 {
+    return None;
+  }
+  let mean_time = samples.iter().map(|(time, _)| time).sum::<f64>() / count;
+  let mean_used = samples.iter().map(|(_, used)| *used as f64).sum::<f64>() / count;
+  let (covariance, variance) =
+    samples
+      .iter()
+      .fold((0.0, 0.0), |(covariance, variance), (time, used)| {
+        let dt = time - mean_time;
+        (
+          covariance + dt * (*used as f64 - mean_used),
+          variance + dt * dt,
+        )
+      });
+  (variance > 0.0).then(|| covariance / variance)
+}
+
+/// Short time left of the ETA column, like `~3d` or `~5h`, `-` when not filling up.
+pub(crate) fn format_eta(left: Option<Duration>) -> String {
+  let Some(left) = left else {
+    return "-".to_string();
+  };
+  match left.as_secs() {
+    0..60 => "<1m".to_string(),
+    secs @ 60..3600 => format!("~{}m", secs / 60),
+    secs @ 3600..86400 => format!("~{}h", secs / 3600),
+    secs @ 86400..31536000 => format!("~{}d", secs / 86400),
+    _ => ">1y".to_string(),
+  }
+}
+
+/// `duration` rounded to what matters at its scale: hours past a day, minutes past an hour.
+fn format_left(duration: Option<Duration>) -> String {
+  let Some(duration) = duration else {
+    return "-".to_string();
+  };
+  let secs = duration.as_secs();
+  let unit = match secs {
+    86400.. => 3600,
+    3600.. => 60,
+    _ => 1,
+  };
+  humantime::format_duration(Duration::from_secs(secs / unit * unit)).to_string()
+}
+
+/// Stats of every mount of `samples`, in the order of first appearance. Filesystems whose usage
+/// is unknown are left out.
+fn summarize(samples: &[Vec<Filesystem>], settings: &Settings) -> Vec<Stats> {
+  let mut stats: Vec<Stats> = vec![];
+  for fs in samples.iter().flatten() {
+    let Some(used) = fs.used() else { continue };
+    let mount = &fs.mount_info.mount_dir;
+    match stats.iter_mut().find(|stat| stat.mount_dir == *mount) {
+      Some(stat) => stat.add(used, fs),
+      None => stats.push(Stats::new(
+        settings.device_label(&fs.mount_info).into_owned(),
+        mount.clone(),
+        settings.mount_label(&fs.mount_info).into_owned(),
+        used,
+        fs,
+      )),
+    }
+  }
+  stats
+}
+
+/// Load the filesystems `count` times, `interval` apart, and print the statistics of the used
+/// space.
+pub(crate) fn run(
+  mut load: impl FnMut() -> Result<Vec<Filesystem>>,
+  count: usize,
+  interval: Duration,
+  settings: &Settings,
+  size_format: &SizeFormat,
+) -> Result<()> {
+  let progress = std::io::stderr().is_terminal();
+  let start = Instant::now();
+  let mut samples = vec![];
+  for i in 0..count {
+    if i > 0 {
+      thread::sleep(interval);
+    }
+    if progress {
+      eprint!("\rddf: sample {}/{count}", i + 1);
+    }
+    samples.push(load()?);
+  }
+  if progress {
+    eprint!("\r\x1b[K");
+  }
+  let window = start.elapsed().as_secs_f64();
+  let stats = summarize(&samples, settings);
+
+  let mut out = std::io::stdout().lock();
+  let device_width = stats
+    .iter()
+    .map(|s| s.device.len())
+    .max()
+    .unwrap_or_default()
+    .max(10);
+  writeln!(
+    out,
+    "{:<device_width$} {:>9} {:>9} {:>9} {:>10} {:>11} {:>10} {:>10} Mounted on",
+    "Filesystem", "Min", "Avg", "Max", "Growth", "Rate", "Full in", "Inodes in"
+  )?;
+  for stat in &stats {
+    let rate = if window > 0.0 {
+      format!(
+        "{}/s",
+        size_format.format_signed((stat.growth() as f64 / window) as i128)
+      )
+    } else {
+      "?".to_string()
+    };
+    writeln!(
+      out,
+      "{:<device_width$} {:>9} {:>9} {:>9} {:>10} {:>11} {:>10} {:>10} {}",
+      stat.device,
+      size_format.format(stat.min),
+      size_format.format(stat.avg()),
+      size_format.format(stat.max),
+      size_format.format_signed(stat.growth()),
+      rate,
+      format_left(stat.space_left(window)),
+      format_left(stat.inodes_left(window)),
+      stat.mount,
+    )?;
+  }
+  // Maildirs and spools die by inodes while the space looks fine.
+  for stat in &stats {
+    let Some(inodes) = stat.inodes_left(window) else {
+      continue;
+    };
+    if stat.space_left(window).is_none_or(|space| inodes < space) {
+      eprintln!(
+        "ddf: {}: the inodes run out in {}, before the space",
+        stat.mount,
+        format_left(Some(inodes))
+      );
+    }
+  }
+  writeln!(out, "{count} samples over {window:.1}s")?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::fsext::{FsUsage, MountInfo};
+
+  fn sample(mount: &str, bfree: u64) -> Filesystem {
+    sample_inodes(mount, bfree, 0)
+  }
+
+  fn sample_inodes(mount: &str, bfree: u64, ffree: u64) -> Filesystem {
+    Filesystem {
+      mount_info: MountInfo {
+        dev_name: "/dev/sda".to_string(),
+        mount_dir: mount.to_string(),
+        ..Default::default()
+      },
+      usage: FsUsage {
+        blocksize: 1,
+        blocks: 100,
+        bfree,
+        bavail: bfree,
+        files: if ffree > 0 { 100 } else { 0 },
+        ffree,
+        raw: None,
+      },
+    }
+  }
+
+  #[test]
+  fn test_summarize() {
+    let samples = [
+      vec![sample("/", 50), sample("/home", 10)],
+      vec![sample("/", 20)],
+      vec![sample("/", 40), sample("/home", 30)],
+    ];
+    let stats = summarize(&samples, &Settings::default());
+    assert_eq!(stats.len(), 2);
+    let root = &stats[0];
+    assert_eq!((root.min, root.avg(), root.max), (50, 63, 80));
+    assert_eq!(root.growth(), 10);
+    assert_eq!(stats[1].growth(), -20);
+    assert_eq!(SizeFormat::Human.format_signed(stats[1].growth()), "-20B");
+    assert_eq!(root.space_left(10.0), Some(Duration::from_secs(40)));
+    assert_eq!(stats[1].space_left(10.0), None);
+    assert_eq!(root.inodes_left(10.0), None);
+  }
+
+  #[test]
+  fn test_inodes_left() {
+    let samples = [
+      vec![sample_inodes("/var/spool", 50, 40)],
+      vec![sample_inodes("/var/spool", 50, 20)],
+    ];
+    let stats = summarize(&samples, &Settings::default());
+    // 20 inodes left, 20 used in 60 seconds.
+    assert_eq!(stats[0].inodes_left(60.0), Some(Duration::from_secs(60)));
+    assert_eq!(stats[0].space_left(60.0), None);
+    assert_eq!(format_left(Some(Duration::from_secs(90061))), "1day 1h");
+    assert_eq!(format_left(Some(Duration::from_secs(3725))), "1h 2m");
+    assert_eq!(format_left(None), "-");
+  }
+
+  #[test]
+  fn test_growth_rate() {
+    // 10 bytes a second, with some noise.
+    let samples = [(0.0, 1000), (2.0, 1025), (4.0, 1035), (6.0, 1060)];
+    let rate = growth_rate(&samples).unwrap();
+    assert!((rate - 9.5).abs() < 1e-9, "{rate}");
+    assert_eq!(growth_rate(&samples[..1]), None);
+    assert_eq!(growth_rate(&[(1.0, 10), (1.0, 20)]), None);
+    assert_eq!(time_to_full(3000, 10.0), Some(Duration::from_secs(300)));
+    assert_eq!(time_to_full(3000, -1.0), None);
+    assert_eq!(format_eta(time_to_full(3000, 10.0)), "~5m");
+    assert_eq!(format_eta(Some(Duration::from_secs(3 * 86400 + 10))), "~3d");
+    assert_eq!(format_eta(Some(Duration::from_secs(30))), "<1m");
+    assert_eq!(format_eta(None), "-");
+  }
+}
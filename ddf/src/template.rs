@@ -0,0 +1,198 @@
+//! `--format-string` templates: text with `{field}` placeholders replaced by the fields of each
+//! filesystem.
+//!
+//! Fields are the machine output names (`source`, `alias`, `label`, `fstype`, `size`, `used`,
+//! `avail`, `pcent`, `target`, also named `mount`). Sizes are in bytes, `{size:h}` prints them like the table does.
+//! `{{` and `}}` are literal braces, `\t`, `\n` and `\\` the usual escapes.
+
+use crate::{output::Record, units::SizeFormat};
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Field {
+  Source,
+  Alias,
+  Label,
+  FsType,
+  Size,
+  Used,
+  Avail,
+  Pcent,
+  Target,
+}
+
+impl Field {
+  pub(crate) fn parse(name: &str) -> Option<Self> {
+    Some(match name {
+      "source" => Self::Source,
+      "alias" => Self::Alias,
+      "label" => Self::Label,
+      "fstype" => Self::FsType,
+      "size" => Self::Size,
+      "used" => Self::Used,
+      "avail" => Self::Avail,
+      "pcent" => Self::Pcent,
+      "target" | "mount" => Self::Target,
+      _ => return None,
+    })
+  }
+
+  pub(crate) fn size(self, record: &Record) -> Option<Option<u64>> {
+    match self {
+      Self::Size => Some(record.size),
+      Self::Used => Some(record.used),
+      Self::Avail => Some(record.avail),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+  Text(String),
+  Field { field: Field, human: bool },
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub(crate) struct Template(Vec<Segment>);
+
+impl TryFrom<String> for Template {
+  type Error = String;
+
+  fn try_from(value: String) -> Result<Self, Self::Error> {
+    parse_template(&value)
+  }
+}
+
+/// Parse a `--format-string` argument.
+pub(crate) fn parse_template(value: &str) -> Result<Template, String> {
+  let mut segments = vec![];
+  let mut text = String::new();
+  let mut chars = value.chars();
+  while let Some(c) = chars.next() {
+    match c {
+      '\\' => match chars.next() {
+        Some('t') => text.push('\t'),
+        Some('n') => text.push('\n'),
+        Some('\\') | None => text.push('\\'),
+        Some(other) => {
+          text.push('\\');
+          text.push(other);
+        }
+      },
+      '}' => {
+        if chars.next() != Some('}') {
+          return Err("unmatched `}`, use `}}` for a literal brace".to_string());
+        }
+        text.push('}');
+      }
+      '{' => {
+        let rest = chars.as_str();
+        if let Some(rest) = rest.strip_prefix('{') {
+          text.push('{');
+          chars = rest.chars();
+          continue;
+        }
+        let Some(end) = rest.find('}') else {
+          return Err("unclosed `{`".to_string());
+        };
+        let placeholder = &rest[..end];
+        let (name, spec) = placeholder.split_once(':').unwrap_or((placeholder, ""));
+        let field = Field::parse(name).ok_or_else(|| format!("unknown field `{name}`"))?;
+        let human = match spec {
+          "" => false,
+          "h" if field.size(&Record::default()).is_some() => true,
+          _ => return Err(format!("invalid format `{spec}` for the field `{name}`")),
+        };
+        if !text.is_empty() {
+          segments.push(Segment::Text(std::mem::take(&mut text)));
+        }
+        segments.push(Segment::Field { field, human });
+        chars = rest[end + 1..].chars();
+      }
+      c => text.push(c),
+    }
+  }
+  if !text.is_empty() {
+    segments.push(Segment::Text(text));
+  }
+  Ok(Template(segments))
+}
+
+impl Template {
+  /// The template filled with the fields of `record`. Unknown values print as `?`.
+  pub(crate) fn render(&self, record: &Record, size_format: &SizeFormat) -> String {
+    self.render_quoted(record, size_format, str::to_string)
+  }
+
+  /// The template filled with the fields of `record`, each one passed through `quote`: the text
+  /// around them is left as written.
+  pub(crate) fn render_quoted(
+    &self,
+    record: &Record,
+    size_format: &SizeFormat,
+    quote: impl Fn(&str) -> String,
+  ) -> String {
+    let mut line = String::new();
+    for segment in &self.0 {
+      match segment {
+        Segment::Text(text) => line.push_str(text),
+        Segment::Field { field, human } => line.push_str(&quote(&match field {
+          Field::Source => record.source.clone(),
+          Field::Alias => record.alias.clone().unwrap_or_default(),
+          Field::Label => record.alias.clone().unwrap_or(record.source.clone()),
+          Field::FsType => record.fstype.clone(),
+          Field::Pcent => record
+            .pcent
+            .map(|pcent| format!("{}%", pcent.round()))
+            .unwrap_or_else(|| "?".to_string()),
+          Field::Target => record.target.clone(),
+          Field::Size | Field::Used | Field::Avail => {
+            let bytes = field.size(record).flatten();
+            if *human {
+              size_format.format_opt(bytes)
+            } else {
+              bytes.map_or_else(|| "?".to_string(), |bytes| bytes.to_string())
+            }
+          }
+        })),
+      }
+    }
+    line
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_template() {
+    let record = Record {
+      source: "/dev/sda1".to_string(),
+      size: Some(2048),
+      pcent: Some(42.3),
+      target: "/".to_string(),
+      ..Default::default()
+    };
+    let render = |template: &str| {
+      parse_template(template)
+        .unwrap()
+        .render(&record, &SizeFormat::Human)
+    };
+    assert_eq!(render(r"{source}\t{pcent}\t{target}"), "/dev/sda1\t42%\t/");
+    assert_eq!(render("{size} {size:h} {used}"), "2048 2KiB ?");
+    assert_eq!(render("{{{label}}}"), "{/dev/sda1}");
+    let quoted = parse_template("ls {target} {used}").unwrap().render_quoted(
+      &record,
+      &SizeFormat::Human,
+      |value| format!("<{value}>"),
+    );
+    assert_eq!(quoted, "ls </> <?>");
+    assert!(parse_template("{nope}").is_err());
+    assert!(parse_template("{source").is_err());
+    assert!(parse_template("{target:h}").is_err());
+    assert!(parse_template("a}b").is_err());
+  }
+}
@@ -0,0 +1,1213 @@
+//! `--tui`: full-screen interactive table.
+
+use crate::{
+  filesystem::Filesystem,
+  host,
+  mountwatch::MountWatcher,
+  palette::Palette,
+  partition::{self, Partition},
+  settings::{MountRule, Settings},
+  units::SizeFormat,
+};
+use anyhow::Result;
+use arboard::Clipboard;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use crossterm::{
+  event::{
+    self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
+    KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+  },
+  execute,
+};
+use ratatui::{
+  layout::{Constraint, Flex, Layout, Rect},
+  style::{Modifier, Style},
+  text::{Line, Span},
+  widgets::{Block, Cell, Clear, Padding, Paragraph, Row, Table, TableState, Wrap},
+  DefaultTerminal, Frame,
+};
+use regex::Regex;
+use std::{
+  cmp::Ordering,
+  collections::HashMap,
+  io::{stdout, Write},
+  process,
+  time::{Duration, Instant},
+};
+
+/// Columns of the table, in display order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Column {
+  Filesystem,
+  Size,
+  Used,
+  Avail,
+  Percent,
+  Mount,
+}
+
+impl Column {
+  const ALL: [Self; 6] = [
+    Self::Filesystem,
+    Self::Size,
+    Self::Used,
+    Self::Avail,
+    Self::Percent,
+    Self::Mount,
+  ];
+
+  fn title(self) -> &'static str {
+    match self {
+      Self::Filesystem => "Filesystem",
+      Self::Size => "Size",
+      Self::Used => "Used",
+      Self::Avail => "Avail",
+      Self::Percent => "Use%",
+      Self::Mount => "Mounted on",
+    }
+  }
+
+  /// Columns cycled through by `s`.
+  fn next_sort(current: Option<Self>) -> Self {
+    match current {
+      Some(Self::Size) => Self::Used,
+      Some(Self::Used) => Self::Avail,
+      Some(Self::Avail) => Self::Percent,
+      Some(Self::Percent) => Self::Mount,
+      _ => Self::Size,
+    }
+  }
+
+  /// Numbers are sorted largest first, names alphabetically.
+  fn descending_by_default(self) -> bool {
+    !matches!(self, Self::Filesystem | Self::Mount)
+  }
+
+  /// Order of `a` and `b`, the percentage being the inode usage when `inodes` is set.
+  fn compare(self, a: &Filesystem, b: &Filesystem, settings: &Settings, inodes: bool) -> Ordering {
+    match self {
+      Self::Filesystem => settings
+        .device_label(&a.mount_info)
+        .cmp(&settings.device_label(&b.mount_info)),
+      Self::Size => a.size().cmp(&b.size()),
+      Self::Used => a.used().cmp(&b.used()),
+      Self::Avail => a.avail().cmp(&b.avail()),
+      Self::Percent => gauge_ratio(a, inodes)
+        .partial_cmp(&gauge_ratio(b, inodes))
+        .unwrap_or(Ordering::Equal),
+      Self::Mount => settings
+        .mount_label(&a.mount_info)
+        .cmp(&settings.mount_label(&b.mount_info)),
+    }
+  }
+}
+
+/// Used ratio shown in the percentage column: of the inodes when `inodes` is set, of the blocks
+/// otherwise.
+fn gauge_ratio(fs: &Filesystem, inodes: bool) -> Option<f64> {
+  if inodes {
+    fs.usage.inode_ratio()
+  } else {
+    fs.used_ratio()
+  }
+}
+
+/// Active sort column and direction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Sort {
+  column: Column,
+  descending: bool,
+}
+
+/// Overlays of the table.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Popup {
+  /// Keys and settings in effect, opened with `?`.
+  Help,
+  /// Everything known about the selected filesystem, opened with Enter.
+  Detail,
+  /// Confirmation of an action on the selected filesystem.
+  Confirm(Action),
+}
+
+/// Disk actions run through udisks2.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Action {
+  Unmount,
+  /// Power off the drive, for removable devices.
+  PowerOff,
+}
+
+impl Action {
+  fn description(self) -> &'static str {
+    match self {
+      Self::Unmount => "Unmount",
+      Self::PowerOff => "Power off the drive of",
+    }
+  }
+
+  /// `udisksctl` command applying the action to the block `device`.
+  fn command(self, device: &str) -> process::Command {
+    let verb = match self {
+      Self::Unmount => "unmount",
+      Self::PowerOff => "power-off",
+    };
+    let mut command = process::Command::new("udisksctl");
+    // Polkit would otherwise ask for a password on the terminal the TUI is drawn on.
+    command.args([verb, "--no-user-interaction", "--block-device", device]);
+    command
+  }
+}
+
+/// Widths of the columns, in display order.
+const WIDTHS: [Constraint; 6] = [
+  Constraint::Max(30),
+  Constraint::Length(9),
+  Constraint::Length(9),
+  Constraint::Length(9),
+  Constraint::Length(6),
+  Constraint::Fill(1),
+];
+
+/// Keys listed by the help overlay.
+const KEYS: &[(&str, &str)] = &[
+  ("↑ ↓ j k", "move the selection"),
+  ("PgUp PgDn", "move by a page"),
+  ("Home g End G", "go to the first or last row"),
+  ("s", "cycle the sort column"),
+  ("1-6", "sort by a column, again to reverse"),
+  ("r", "reverse the sort"),
+  ("i", "switch Use% between blocks and inodes"),
+  ("/", "filter the rows, Enter keeps and Esc clears"),
+  ("Enter", "show the details of the selected filesystem"),
+  ("u", "unmount the selected filesystem"),
+  ("e", "power off the drive of the selected filesystem"),
+  ("o", "open the mount directory in the file manager"),
+  ("!", "start a shell in the mount directory"),
+  ("y Y c", "copy the mount directory, the device or the row"),
+  ("?", "show or hide this help"),
+  (
+    "mouse",
+    "click a header to sort, a row to select, wheel to scroll",
+  ),
+  ("q Esc", "quit"),
+];
+
+/// How long to wait for a key before checking for a refresh.
+const POLL: Duration = Duration::from_millis(100);
+/// How long a used space that changed at a refresh stays highlighted.
+const HIGHLIGHT: Duration = Duration::from_secs(2);
+
+type Reload<'a> = Box<dyn FnMut() -> Result<Vec<Filesystem>> + 'a>;
+
+/// State of the interactive table.
+pub(crate) struct Tui<'a> {
+  filesystems: Vec<Filesystem>,
+  settings: &'a Settings,
+  size_format: SizeFormat,
+  state: TableState,
+  /// Rows visible in the last frame, for page up and down.
+  page: usize,
+  /// Area of the table in the last frame, for the mouse.
+  table_area: Rect,
+  /// Reads the filesystems again when the mount table changes, and every `interval` if set.
+  reload: Option<Reload<'a>>,
+  interval: Option<Duration>,
+  /// Mount table order when unset.
+  sort: Option<Sort>,
+  /// Text typed after `/`, and whether it is being typed.
+  filter: String,
+  editing_filter: bool,
+  /// Matches the rows shown: the filter as a case-insensitive regex, or as plain text while it
+  /// isn't a valid regex.
+  matcher: Option<Regex>,
+  /// Show the inode usage in the percentage column instead of the block usage.
+  inodes: bool,
+  /// Overlay open above the table.
+  popup: Option<Popup>,
+  /// Result of the last action, shown in the status bar until the next key.
+  message: Option<String>,
+  /// `!` was pressed: the event loop suspends the TUI for a shell.
+  shell_requested: bool,
+  /// When the used space of each mount last changed at a refresh.
+  changed: HashMap<String, Instant>,
+  /// Opened on the first yank and kept: on X11 the copied text is served by its owner.
+  clipboard: Option<Clipboard>,
+}
+
+impl<'a> Tui<'a> {
+  pub(crate) fn new(
+    filesystems: Vec<Filesystem>,
+    settings: &'a Settings,
+    size_format: SizeFormat,
+  ) -> Self {
+    let state = TableState::default().with_selected((!filesystems.is_empty()).then_some(0));
+    Self {
+      filesystems,
+      settings,
+      size_format,
+      state,
+      page: 1,
+      table_area: Rect::default(),
+      reload: None,
+      interval: None,
+      sort: None,
+      filter: String::new(),
+      editing_filter: false,
+      matcher: None,
+      inodes: false,
+      popup: None,
+      message: None,
+      shell_requested: false,
+      changed: HashMap::new(),
+      clipboard: None,
+    }
+  }
+
+  /// Refresh the table with `reload` when the mount table changes, and every `interval` if set.
+  pub(crate) fn reload_with(
+    mut self,
+    reload: impl FnMut() -> Result<Vec<Filesystem>> + 'a,
+    interval: Option<Duration>,
+  ) -> Self {
+    self.reload = Some(Box::new(reload));
+    self.interval = interval;
+    self
+  }
+
+  fn refresh(&mut self) -> Result<()> {
+    let selected = self.selected_mount();
+    if let Some(reload) = &mut self.reload {
+      let filesystems = reload()?;
+      let now = Instant::now();
+      for fs in &filesystems {
+        let mount = &fs.mount_info.mount_dir;
+        let before = self
+          .filesystems
+          .iter()
+          .find(|old| old.mount_info.mount_dir == *mount);
+        if before.is_some_and(|before| before.used() != fs.used()) {
+          self.changed.insert(mount.clone(), now);
+        }
+      }
+      self.changed.retain(|_, at| now - *at < HIGHLIGHT);
+      self.filesystems = filesystems;
+      self.apply_sort(selected);
+    }
+    Ok(())
+  }
+
+  /// Whether `fs` matches the filter on its device, mount directory or type.
+  fn matches(&self, fs: &Filesystem) -> bool {
+    self.matcher.as_ref().is_none_or(|matcher| {
+      [
+        self.settings.device_label(&fs.mount_info),
+        self.settings.mount_label(&fs.mount_info),
+        fs.mount_info.fs_type.as_str().into(),
+      ]
+      .iter()
+      .any(|field| matcher.is_match(field))
+    })
+  }
+
+  /// Rows shown, in order.
+  fn visible(&self) -> Vec<&Filesystem> {
+    self
+      .filesystems
+      .iter()
+      .filter(|fs| self.matches(fs))
+      .collect()
+  }
+
+  fn selected(&self) -> Option<&Filesystem> {
+    self.visible().get(self.state.selected()?).copied()
+  }
+
+  fn selected_mount(&self) -> Option<String> {
+    Some(self.selected()?.mount_info.mount_dir.clone())
+  }
+
+  fn set_filter(&mut self, filter: String) {
+    let selected = self.selected_mount();
+    self.matcher = (!filter.is_empty()).then(|| {
+      Regex::new(&format!("(?i){filter}"))
+        .or_else(|_| Regex::new(&format!("(?i){}", regex::escape(&filter))))
+        .expect("an escaped pattern is a valid regex")
+    });
+    self.filter = filter;
+    self.apply_sort(selected);
+  }
+
+  /// Sort the rows, keeping the selection on the `selected` mount when it is still shown.
+  fn apply_sort(&mut self, selected: Option<String>) {
+    if let Some(Sort { column, descending }) = self.sort {
+      let (settings, inodes) = (self.settings, self.inodes);
+      self.filesystems.sort_by(|a, b| {
+        let order = column.compare(a, b, settings, inodes);
+        if descending {
+          order.reverse()
+        } else {
+          order
+        }
+      });
+    }
+    let visible = self.visible();
+    let index = selected
+      .and_then(|dir| visible.iter().position(|fs| fs.mount_info.mount_dir == dir))
+      .or(self.state.selected());
+    let last = visible.len().checked_sub(1);
+    self
+      .state
+      .select(last.map(|last| index.unwrap_or_default().min(last)));
+  }
+
+  /// Sort by `column`, reversing the direction when it is already the sort column.
+  fn sort_by(&mut self, column: Column) {
+    self.sort = Some(match self.sort {
+      Some(sort) if sort.column == column => Sort {
+        column,
+        descending: !sort.descending,
+      },
+      _ => Sort {
+        column,
+        descending: column.descending_by_default(),
+      },
+    });
+    self.apply_sort(self.selected_mount());
+  }
+
+  /// Take over the terminal until the user quits.
+  pub(crate) fn run(mut self) -> Result<()> {
+    let mut terminal = ratatui::init();
+    let result = execute!(stdout(), EnableMouseCapture)
+      .map_err(Into::into)
+      .and_then(|()| self.event_loop(&mut terminal));
+    let _ = execute!(stdout(), DisableMouseCapture);
+    ratatui::restore();
+    result
+  }
+
+  fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+    let watcher = self.reload.is_some().then(MountWatcher::spawn);
+    let mut refreshed = Instant::now();
+    loop {
+      terminal.draw(|frame| self.draw(frame))?;
+      if event::poll(POLL)? {
+        match event::read()? {
+          Event::Key(key) if key.kind == KeyEventKind::Press && !self.handle_key(key) => {
+            return Ok(());
+          }
+          Event::Mouse(mouse) => self.handle_mouse(mouse),
+          _ => {}
+        }
+        if std::mem::take(&mut self.shell_requested) {
+          self.open_shell(terminal)?;
+        }
+      }
+      let due = self
+        .interval
+        .is_some_and(|interval| refreshed.elapsed() >= interval);
+      if due || watcher.as_ref().is_some_and(MountWatcher::take_change) {
+        self.refresh()?;
+        refreshed = Instant::now();
+      }
+    }
+  }
+
+  /// Apply a key typed while editing the filter.
+  fn edit_filter(&mut self, key: KeyEvent) {
+    let mut filter = self.filter.clone();
+    match key.code {
+      KeyCode::Enter => self.editing_filter = false,
+      KeyCode::Esc => {
+        self.editing_filter = false;
+        filter.clear();
+      }
+      KeyCode::Backspace => {
+        filter.pop();
+      }
+      KeyCode::Char(c) => filter.push(c),
+      _ => {}
+    }
+    if filter != self.filter {
+      self.set_filter(filter);
+    }
+  }
+
+  /// Apply a key press, returning false when the user quits.
+  fn handle_key(&mut self, key: KeyEvent) -> bool {
+    if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+      return false;
+    }
+    if self.editing_filter {
+      self.edit_filter(key);
+      return true;
+    }
+    self.message = None;
+    if let Some(popup) = self.popup.take() {
+      if let (Popup::Confirm(action), KeyCode::Char('y')) = (popup, key.code) {
+        self.run_action(action);
+        return true;
+      }
+      // Any other key closes the popup, `q` still quits.
+      return key.code != KeyCode::Char('q');
+    }
+    let last = self.visible().len().saturating_sub(1);
+    let selected = self.state.selected().unwrap_or_default();
+    let select = match key.code {
+      KeyCode::Char('?') => {
+        self.popup = Some(Popup::Help);
+        return true;
+      }
+      KeyCode::Char(c @ ('u' | 'e')) => {
+        let action = if c == 'u' {
+          Action::Unmount
+        } else {
+          Action::PowerOff
+        };
+        if self.local() && self.state.selected().is_some() {
+          self.popup = Some(Popup::Confirm(action));
+        }
+        return true;
+      }
+      KeyCode::Char('o') => {
+        if self.local() {
+          self.open_file_manager();
+        }
+        return true;
+      }
+      KeyCode::Char(c @ ('y' | 'Y' | 'c')) => {
+        let text = self.selected().map(|fs| match c {
+          'y' => fs.mount_info.mount_dir.clone(),
+          'Y' => fs.mount_info.dev_name.clone(),
+          _ => self.row_text(fs),
+        });
+        if let Some(text) = text {
+          self.yank(text);
+        }
+        return true;
+      }
+      KeyCode::Char('!') => {
+        self.shell_requested = self.local() && self.state.selected().is_some();
+        return true;
+      }
+      KeyCode::Enter => {
+        if self.state.selected().is_some() {
+          self.popup = Some(Popup::Detail);
+        }
+        return true;
+      }
+      KeyCode::Char('/') => {
+        self.editing_filter = true;
+        return true;
+      }
+      KeyCode::Esc if !self.filter.is_empty() => {
+        self.set_filter(String::new());
+        return true;
+      }
+      KeyCode::Char('q') | KeyCode::Esc => return false,
+      KeyCode::Down | KeyCode::Char('j') => selected.saturating_add(1),
+      KeyCode::Up | KeyCode::Char('k') => selected.saturating_sub(1),
+      KeyCode::PageDown => selected.saturating_add(self.page),
+      KeyCode::PageUp => selected.saturating_sub(self.page),
+      KeyCode::Home | KeyCode::Char('g') => 0,
+      KeyCode::End | KeyCode::Char('G') => last,
+      KeyCode::Char('s') => {
+        let column = Column::next_sort(self.sort.map(|sort| sort.column));
+        self.sort = None;
+        self.sort_by(column);
+        return true;
+      }
+      KeyCode::Char('r') => {
+        if let Some(sort) = self.sort {
+          self.sort_by(sort.column);
+        }
+        return true;
+      }
+      KeyCode::Char('i') => {
+        self.inodes = !self.inodes;
+        let selected = self.selected_mount();
+        self.apply_sort(selected);
+        return true;
+      }
+      KeyCode::Char(digit @ '1'..='6') => {
+        self.sort_by(Column::ALL[digit as usize - '1' as usize]);
+        return true;
+      }
+      _ => return true,
+    };
+    if !self.visible().is_empty() {
+      self.state.select(Some(select.min(last)));
+    }
+    true
+  }
+
+  /// Whether the filesystems are the ones of this machine, not read from a bundle. Sets the
+  /// message when they aren't.
+  fn local(&mut self) -> bool {
+    if self.reload.is_none() {
+      self.message = Some("Only available for the local filesystems".to_string());
+    }
+    self.reload.is_some()
+  }
+
+  /// Open the selected mount directory with `$FILE_MANAGER`, or the desktop's default.
+  fn open_file_manager(&mut self) {
+    let Some(dir) = self.selected_mount() else {
+      return;
+    };
+    let program = std::env::var("FILE_MANAGER").unwrap_or_else(|_| {
+      if cfg!(target_os = "macos") {
+        "open"
+      } else {
+        "xdg-open"
+      }
+      .to_string()
+    });
+    let spawned = process::Command::new(&program)
+      .arg(&dir)
+      .stdin(process::Stdio::null())
+      .stdout(process::Stdio::null())
+      .stderr(process::Stdio::null())
+      .spawn();
+    self.message = Some(match spawned {
+      Ok(_) => format!("Opened {dir}"),
+      Err(error) => format!("{program}: {error}"),
+    });
+  }
+
+  /// Copy `text` to the system clipboard.
+  fn yank(&mut self, text: String) {
+    // Over SSH the clipboard that matters is the one of the terminal, reached with OSC 52.
+    let remote = std::env::var_os("SSH_TTY").is_some();
+    let copied = match &mut self.clipboard {
+      _ if remote => Err(arboard::Error::ClipboardNotSupported),
+      Some(clipboard) => clipboard.set_text(text.as_str()),
+      None => Clipboard::new()
+        .and_then(|clipboard| self.clipboard.insert(clipboard).set_text(text.as_str())),
+    };
+    let copied = copied.or_else(|error| {
+      let mut out = stdout();
+      write!(out, "{}", osc52(&text))
+        .and_then(|()| out.flush())
+        .map_err(|_| error)
+    });
+    self.message = Some(match copied {
+      Ok(()) => format!("Copied {text}"),
+      Err(error) => format!("Clipboard: {error}"),
+    });
+  }
+
+  /// Suspend the TUI for a `$SHELL` started in the selected mount directory.
+  fn open_shell(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+    let Some(dir) = self.selected_mount() else {
+      return Ok(());
+    };
+    let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
+    execute!(stdout(), DisableMouseCapture)?;
+    ratatui::restore();
+    println!("ddf: {shell} in {dir}, exit it to return to the table");
+    let status = process::Command::new(&shell).current_dir(&dir).status();
+    *terminal = ratatui::init();
+    execute!(stdout(), EnableMouseCapture)?;
+    terminal.clear()?;
+    if let Err(error) = status {
+      self.message = Some(format!("{shell}: {error}"));
+    }
+    Ok(())
+  }
+
+  /// Run `action` on the selected filesystem, the mount watcher refreshing the table after.
+  fn run_action(&mut self, action: Action) {
+    let Some(fs) = self.selected() else {
+      return;
+    };
+    let device = fs.mount_info.dev_name.clone();
+    if !device.starts_with("/dev/") {
+      self.message = Some(format!("{device} is not a block device"));
+      return;
+    }
+    self.message = Some(match action.command(&device).output() {
+      Ok(output) if output.status.success() => {
+        format!("{} {device}: done", action.description())
+      }
+      Ok(output) => String::from_utf8_lossy(&output.stderr).trim().to_string(),
+      Err(error) => format!("udisksctl: {error}"),
+    });
+  }
+
+  /// Apply a click or a scroll: the header sorts by the column clicked, a row is selected.
+  fn handle_mouse(&mut self, mouse: MouseEvent) {
+    if self.popup.is_some() {
+      if let MouseEventKind::Down(_) = mouse.kind {
+        self.popup = None;
+      }
+      return;
+    }
+    let last = self.visible().len().checked_sub(1);
+    let selected = self.state.selected().unwrap_or_default();
+    let area = self.table_area;
+    match mouse.kind {
+      MouseEventKind::ScrollDown => self.state.select(last.map(|last| (selected + 1).min(last))),
+      MouseEventKind::ScrollUp => self.state.select(last.map(|_| selected.saturating_sub(1))),
+      MouseEventKind::Down(MouseButton::Left) if mouse.row == area.y => {
+        // Laid out like `Table` does.
+        let columns = Layout::horizontal(WIDTHS)
+          .flex(Flex::Start)
+          .spacing(1)
+          .split(area);
+        if let Some(index) = columns
+          .iter()
+          .position(|column| (column.x..column.right()).contains(&mouse.column))
+        {
+          self.sort_by(Column::ALL[index]);
+        }
+      }
+      MouseEventKind::Down(MouseButton::Left) if mouse.row > area.y && mouse.row < area.bottom() => {
+        let index = self.state.offset() + usize::from(mouse.row - area.y - 1);
+        if last.is_some_and(|last| index <= last) {
+          self.state.select(Some(index));
+        }
+      }
+      _ => {}
+    }
+  }
+
+  /// Text of the cells of a row, in the order of `Column::ALL`.
+  fn cells(&self, fs: &Filesystem) -> [String; 6] {
+    let size = |bytes| self.size_format.format_opt(bytes);
+    [
+      self.settings.device_label(&fs.mount_info).into_owned(),
+      size(fs.size()),
+      size(fs.used()),
+      size(fs.avail()),
+      gauge_ratio(fs, self.inodes).map_or("?".to_string(), |ratio| {
+        format!("{:.0}%", 100.0 * self.settings.shown_ratio(ratio))
+      }),
+      self.settings.mount_label(&fs.mount_info).into_owned(),
+    ]
+  }
+
+  /// A row as copied by `Y`: its cells separated by spaces.
+  fn row_text(&self, fs: &Filesystem) -> String {
+    self.cells(fs).join(" ")
+  }
+
+  fn row(&self, fs: &Filesystem) -> Row<'static> {
+    let ratio = gauge_ratio(fs, self.inodes);
+    let style = Palette::current().severity(ratio.map(|ratio| {
+      // Free space thresholds don't apply to the inodes.
+      let avail = fs.avail().filter(|_| !self.inodes);
+      self
+        .settings
+        .thresholds(&fs.mount_info.mount_dir, &fs.mount_info.fs_type)
+        .severity(ratio, avail)
+    }));
+    let [device, size, used, avail, percent, mount] = self.cells(fs);
+    let right = |text: String| Cell::from(Line::from(text).right_aligned());
+    let changed = self
+      .changed
+      .get(&fs.mount_info.mount_dir)
+      .is_some_and(|at| at.elapsed() < HIGHLIGHT);
+    Row::new([
+      Cell::from(device),
+      right(size),
+      if changed {
+        right(used).style(Palette::current().changed())
+      } else {
+        right(used)
+      },
+      right(avail),
+      right(percent).style(style),
+      Cell::from(mount),
+    ])
+  }
+
+  fn draw(&mut self, frame: &mut Frame) {
+    let [table_area, status_area] =
+      Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(frame.area());
+    let free = self.settings.free_percent.unwrap_or_default();
+    let header = Row::new(Column::ALL.map(|column| {
+      let title = match column {
+        Column::Percent if self.inodes && free => "IFree%",
+        Column::Percent if self.inodes => "IUse%",
+        Column::Percent if free => "Free%",
+        _ => column.title(),
+      };
+      match self.sort {
+        Some(sort) if sort.column == column => {
+          format!("{title}{}", if sort.descending { "▼" } else { "▲" })
+        }
+        _ => title.to_string(),
+      }
+    }))
+    .style(Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED));
+    let visible = self.visible();
+    let count = visible.len();
+    let rows: Vec<Row> = visible.into_iter().map(|fs| self.row(fs)).collect();
+    let table = Table::new(rows, WIDTHS)
+      .header(header)
+      .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    self.page = usize::from(table_area.height.saturating_sub(1)).max(1);
+    self.table_area = table_area;
+    frame.render_stateful_widget(table, table_area, &mut self.state);
+
+    let position = self.state.selected().map_or(0, |selected| selected + 1);
+    let status = if self.editing_filter {
+      format!(" /{}", self.filter)
+    } else if let Some(message) = &self.message {
+      format!(" {message}")
+    } else {
+      let filter = if self.filter.is_empty() {
+        String::new()
+      } else {
+        format!(" · filter: {}", self.filter)
+      };
+      format!(
+        " {} · {position}/{count}{filter} · ↑↓ move · / filter · ? help · q quit",
+        self.settings.hostname_label(host::hostname()),
+      )
+    };
+    frame.render_widget(
+      Paragraph::new(status).style(Style::default().add_modifier(Modifier::REVERSED)),
+      status_area,
+    );
+    match self.popup {
+      Some(Popup::Help) => self.draw_help(frame),
+      Some(Popup::Detail) => self.draw_detail(frame),
+      Some(Popup::Confirm(action)) => self.draw_confirm(frame, action),
+      None => {}
+    }
+  }
+
+  /// Popup listing the keys and the settings in effect.
+  fn draw_help(&self, frame: &mut Frame) {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let mut lines: Vec<Line> = KEYS
+      .iter()
+      .map(|(keys, action)| {
+        Line::from(vec![
+          Span::styled(format!("{keys:>13}  "), bold),
+          (*action).into(),
+        ])
+      })
+      .collect();
+    let settings = self.settings;
+    let rules = |rules: Option<&[MountRule]>, empty: &str| match rules {
+      None | Some([]) => empty.to_string(),
+      Some(rules) => rules
+        .iter()
+        .map(MountRule::to_string)
+        .collect::<Vec<_>>()
+        .join(", "),
+    };
+    lines.extend([
+      Line::default(),
+      Line::from(vec![
+        Span::styled("   Thresholds  ", bold),
+        format!(
+          "medium {}, high {}",
+          settings.medium_threshold(),
+          settings.high_threshold()
+        )
+        .into(),
+      ]),
+      Line::from(vec![
+        Span::styled("   Inclusions  ", bold),
+        rules(settings.include.as_deref(), "all").into(),
+      ]),
+      Line::from(vec![
+        Span::styled("   Exclusions  ", bold),
+        rules(settings.exclude.as_deref(), "none").into(),
+      ]),
+      Line::from(vec![
+        Span::styled("      Aliases  ", bold),
+        settings
+          .alias
+          .as_ref()
+          .map_or(0, |alias| alias.len())
+          .to_string()
+          .into(),
+      ]),
+    ]);
+
+    draw_popup(frame, " Help ", lines);
+  }
+
+  fn draw_confirm(&self, frame: &mut Frame, action: Action) {
+    let Some(fs) = self.selected() else {
+      return;
+    };
+    let lines = vec![
+      Line::from(format!(
+        "{} {} ({})?",
+        action.description(),
+        self.settings.source_label(&fs.mount_info),
+        self.settings.mount_label(&fs.mount_info)
+      )),
+      Line::default(),
+      Line::from("y to confirm, any other key to cancel"),
+    ];
+    draw_popup(frame, " Confirm ", lines);
+  }
+
+  /// Popup describing the selected filesystem in full.
+  fn draw_detail(&self, frame: &mut Frame) {
+    let Some(fs) = self.selected() else {
+      return;
+    };
+    let settings = self.settings;
+    let info = &fs.mount_info;
+    let usage = &fs.usage;
+    let options: Vec<&str> = info.mount_option.split(',').collect();
+    let mut flags = vec![if options.contains(&"ro") {
+      "read-only"
+    } else {
+      "read-write"
+    }];
+    flags.extend(
+      ["nosuid", "nodev", "noexec"]
+        .into_iter()
+        .filter(|flag| options.contains(flag)),
+    );
+    let percent =
+      |ratio: Option<f64>| ratio.map_or("?".to_string(), |ratio| format!("{:.1}%", 100.0 * ratio));
+    let mut fields = vec![
+      ("Device", settings.source_label(info).into_owned()),
+      ("Alias", settings.alias(info).unwrap_or("-").to_string()),
+      ("Id", settings.id_label(fs.id())),
+      ("Device number", or_dash(&info.dev_id)),
+    ];
+    // The disks of a bundle aren't the ones of this machine.
+    let layout = self
+      .reload
+      .as_ref()
+      .and_then(|_| partition::layout(&info.dev_id));
+    if let Some(layout) = &layout {
+      let size = |bytes| self.size_format.format(bytes);
+      let part = layout.partition();
+      let neighbor = |partition: Option<&Partition>, edge: &str| {
+        partition.map_or(edge.to_string(), |p| {
+          format!("{} ({})", p.name, size(p.size))
+        })
+      };
+      fields.extend([
+        (
+          "Partition",
+          format!(
+            "{} of {} ({}) at {}",
+            part.number,
+            layout.disk,
+            size(layout.disk_size),
+            size(part.start)
+          ),
+        ),
+        ("Before", neighbor(layout.previous(), "start of the disk")),
+        ("After", neighbor(layout.next(), "end of the disk")),
+        (
+          "Free after",
+          format!(
+            "{}, {}",
+            size(layout.free_after()),
+            if layout.growable() {
+              "can grow in place"
+            } else {
+              "can't grow in place"
+            }
+          ),
+        ),
+      ]);
+    }
+    fields.extend([
+      ("Type", info.fs_type.clone()),
+      ("Mounted on", settings.mount_label(info).into_owned()),
+      ("Root", or_dash(&info.mount_root)),
+      ("Options", or_dash(&info.mount_option)),
+      ("Flags", flags.join(", ")),
+      ("Block size", usage.blocksize.to_string()),
+      ("Blocks", usage.blocks.to_string()),
+      ("Free blocks", usage.bfree.to_string()),
+      ("Available blocks", usage.bavail.to_string()),
+      ("Used", percent(fs.used_ratio())),
+      ("Inodes", usage.files.to_string()),
+      ("Free inodes", usage.ffree.to_string()),
+      ("Inodes used", percent(usage.inode_ratio())),
+    ]);
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let lines = fields
+      .into_iter()
+      .map(|(name, value)| {
+        Line::from(vec![
+          Span::styled(format!("{name:>16}  "), bold),
+          value.into(),
+        ])
+      })
+      .collect();
+    draw_popup(frame, " Filesystem ", lines);
+  }
+}
+
+/// OSC 52 sequence asking the terminal to put `text` in the system clipboard.
+fn osc52(text: &str) -> String {
+  format!("\x1b]52;c;{}\x07", STANDARD.encode(text))
+}
+
+fn or_dash(value: &str) -> String {
+  if value.is_empty() { "-" } else { value }.to_string()
+}
+
+/// Draw `lines` in a bordered box at the center of the frame.
+fn draw_popup(frame: &mut Frame, title: &str, lines: Vec<Line>) {
+  let area = frame.area();
+  let width = lines.iter().map(Line::width).max().unwrap_or_default() as u16 + 4;
+  let height = lines.len() as u16 + 2;
+  let popup = Rect {
+    x: area.width.saturating_sub(width) / 2,
+    y: area.height.saturating_sub(height) / 2,
+    width: width.min(area.width),
+    height: height.min(area.height),
+  };
+  frame.render_widget(Clear, popup);
+  frame.render_widget(
+    Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+      Block::bordered()
+        .title(title)
+        .padding(Padding::horizontal(1)),
+    ),
+    popup,
+  );
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::fsext::{FsUsage, MountInfo};
+  use ratatui::{backend::TestBackend, Terminal};
+
+  fn tui(count: usize) -> Tui<'static> {
+    let filesystems = (0..count)
+      .map(|i| Filesystem {
+        mount_info: MountInfo {
+          dev_name: format!("/dev/sd{i}"),
+          mount_dir: format!("/mnt/{i}"),
+          ..Default::default()
+        },
+        usage: FsUsage {
+          blocksize: 4096,
+          blocks: 100,
+          bfree: 50,
+          bavail: 50,
+          files: 10,
+          ffree: 9,
+          raw: None,
+        },
+      })
+      .collect();
+    Tui::new(filesystems, Box::leak(Box::default()), SizeFormat::Human)
+  }
+
+  #[test]
+  fn test_navigation() {
+    let mut tui = tui(3);
+    let press = |tui: &mut Tui, code| tui.handle_key(KeyEvent::from(code));
+    assert!(press(&mut tui, KeyCode::Down));
+    assert!(press(&mut tui, KeyCode::Down));
+    assert!(press(&mut tui, KeyCode::Down));
+    assert_eq!(tui.state.selected(), Some(2));
+    assert!(press(&mut tui, KeyCode::Home));
+    assert_eq!(tui.state.selected(), Some(0));
+    assert!(!press(&mut tui, KeyCode::Char('q')));
+  }
+
+  #[test]
+  fn test_sort() {
+    let mut tui = tui(3);
+    tui.filesystems[1].usage.bavail = 10;
+    tui.filesystems[1].usage.bfree = 10;
+    let press = |tui: &mut Tui, code| tui.handle_key(KeyEvent::from(code));
+    press(&mut tui, KeyCode::Down);
+    press(&mut tui, KeyCode::Char('5'));
+    let mounts = |tui: &Tui| {
+      tui
+        .filesystems
+        .iter()
+        .map(|fs| fs.mount_info.mount_dir.clone())
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(mounts(&tui)[0], "/mnt/1");
+    // The selection follows the filesystem.
+    assert_eq!(tui.state.selected(), Some(0));
+    press(&mut tui, KeyCode::Char('5'));
+    assert_eq!(mounts(&tui)[2], "/mnt/1");
+    press(&mut tui, KeyCode::Char('6'));
+    assert_eq!(mounts(&tui), ["/mnt/0", "/mnt/1", "/mnt/2"]);
+    press(&mut tui, KeyCode::Char('r'));
+    assert_eq!(mounts(&tui), ["/mnt/2", "/mnt/1", "/mnt/0"]);
+    press(&mut tui, KeyCode::Char('s'));
+    assert_eq!(tui.sort.map(|sort| sort.column), Some(Column::Size));
+  }
+
+  #[test]
+  fn test_filter() {
+    let mut tui = tui(12);
+    let press = |tui: &mut Tui, code| tui.handle_key(KeyEvent::from(code));
+    press(&mut tui, KeyCode::Char('/'));
+    for c in "SD1".chars() {
+      press(&mut tui, KeyCode::Char(c));
+    }
+    let shown = |tui: &Tui| tui.visible().len();
+    assert_eq!(shown(&tui), 3);
+    // Keys are typed into the filter until Enter.
+    assert!(press(&mut tui, KeyCode::Char('q')));
+    assert_eq!(shown(&tui), 0);
+    press(&mut tui, KeyCode::Backspace);
+    press(&mut tui, KeyCode::Char('$'));
+    press(&mut tui, KeyCode::Enter);
+    assert_eq!(shown(&tui), 1);
+    assert_eq!(tui.selected_mount().as_deref(), Some("/mnt/1"));
+    // An invalid regex is matched as text.
+    tui.set_filter("sd1(".into());
+    assert_eq!(shown(&tui), 0);
+    tui.set_filter("sd1[01]".into());
+    assert_eq!(shown(&tui), 2);
+    // Esc clears the filter before quitting.
+    assert!(press(&mut tui, KeyCode::Esc));
+    assert_eq!(shown(&tui), 12);
+    assert!(!press(&mut tui, KeyCode::Esc));
+  }
+
+  #[test]
+  fn test_help() {
+    let mut tui = tui(1);
+    let mut terminal = Terminal::new(TestBackend::new(80, 30)).unwrap();
+    tui.handle_key(KeyEvent::from(KeyCode::Char('?')));
+    terminal.draw(|frame| tui.draw(frame)).unwrap();
+    let screen: String = terminal
+      .backend()
+      .buffer()
+      .content()
+      .iter()
+      .map(|cell| cell.symbol())
+      .collect();
+    assert!(screen.contains("reverse the sort"));
+    assert!(screen.contains("medium 75%, high 90%"));
+    assert!(screen.contains("Exclusions  none"));
+    // A key closes the help without acting.
+    assert!(tui.handle_key(KeyEvent::from(KeyCode::Esc)));
+    assert_eq!(tui.popup, None);
+  }
+
+  #[test]
+  fn test_detail() {
+    let mut tui = tui(2);
+    tui.filesystems[0].mount_info.mount_option = "ro,nosuid,relatime".to_string();
+    let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+    tui.handle_key(KeyEvent::from(KeyCode::Enter));
+    terminal.draw(|frame| tui.draw(frame)).unwrap();
+    let screen: String = terminal
+      .backend()
+      .buffer()
+      .content()
+      .iter()
+      .map(|cell| cell.symbol())
+      .collect();
+    assert!(screen.contains("Options  ro,nosuid,relatime"));
+    assert!(screen.contains("Flags  read-only, nosuid"));
+    assert!(screen.contains("Inodes used  10.0%"));
+  }
+
+  #[test]
+  fn test_confirm() {
+    let mut tui = tui(2);
+    let press = |tui: &mut Tui, code| tui.handle_key(KeyEvent::from(code));
+    press(&mut tui, KeyCode::Char('u'));
+    assert_eq!(tui.popup, None);
+    assert!(tui.message.is_some());
+    press(&mut tui, KeyCode::Char('!'));
+    assert!(!tui.shell_requested);
+    let mut tui = tui.reload_with(|| Ok(vec![]), None);
+    press(&mut tui, KeyCode::Char('e'));
+    assert_eq!(tui.popup, Some(Popup::Confirm(Action::PowerOff)));
+    press(&mut tui, KeyCode::Char('n'));
+    assert_eq!(tui.popup, None);
+    assert_eq!(tui.message, None);
+  }
+
+  #[test]
+  fn test_mouse() {
+    let mut tui = tui(5);
+    let mut terminal = Terminal::new(TestBackend::new(80, 10)).unwrap();
+    terminal.draw(|frame| tui.draw(frame)).unwrap();
+    let click = |tui: &mut Tui, column, row| {
+      tui.handle_mouse(MouseEvent {
+        kind: MouseEventKind::Down(MouseButton::Left),
+        column,
+        row,
+        modifiers: KeyModifiers::NONE,
+      })
+    };
+    click(&mut tui, 2, 3);
+    assert_eq!(tui.state.selected(), Some(2));
+    // Below the last row.
+    click(&mut tui, 2, 8);
+    assert_eq!(tui.state.selected(), Some(2));
+    click(&mut tui, 75, 0);
+    assert_eq!(tui.sort.map(|sort| sort.column), Some(Column::Mount));
+    tui.handle_mouse(MouseEvent {
+      kind: MouseEventKind::ScrollDown,
+      column: 0,
+      row: 4,
+      modifiers: KeyModifiers::NONE,
+    });
+    assert_eq!(tui.state.selected(), Some(3));
+  }
+
+  #[test]
+  fn test_osc52() {
+    assert_eq!(osc52("/mnt/0"), "\x1b]52;c;L21udC8w\x07");
+  }
+
+  #[test]
+  fn test_changed() {
+    let mut next = tui(2).filesystems;
+    next[1].usage.bfree = 40;
+    let mut tui = tui(2).reload_with(move || Ok(next.clone()), None);
+    tui.refresh().unwrap();
+    assert!(!tui.changed.contains_key("/mnt/0"));
+    assert!(tui.changed.contains_key("/mnt/1"));
+  }
+
+  #[test]
+  fn test_draw() {
+    let mut tui = tui(2);
+    let mut terminal = Terminal::new(TestBackend::new(80, 5)).unwrap();
+    terminal.draw(|frame| tui.draw(frame)).unwrap();
+    let screen: String = terminal
+      .backend()
+      .buffer()
+      .content()
+      .iter()
+      .map(|cell| cell.symbol())
+      .collect();
+    assert!(screen.contains("Mounted on"));
+    assert!(screen.contains("/dev/sd1"));
+    assert!(screen.contains("50%"));
+    assert!(screen.contains("1/2"));
+    assert_eq!(
+      tui.row_text(&tui.filesystems[1]),
+      "/dev/sd1 400KiB 200KiB 200KiB 50% /mnt/1"
+    );
+
+    tui.handle_key(KeyEvent::from(KeyCode::Char('i')));
+    terminal.draw(|frame| tui.draw(frame)).unwrap();
+    let screen: String = terminal
+      .backend()
+      .buffer()
+      .content()
+      .iter()
+      .map(|cell| cell.symbol())
+      .collect();
+    assert!(screen.contains("IUse%"));
+    assert!(screen.contains("10%"));
+  }
+}
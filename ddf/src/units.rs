@@ -0,0 +1,161 @@
+//! Units used to print sizes.
+
+use humansize::{format_size, FormatSizeOptions, BINARY, DECIMAL};
+use serde::Deserialize;
+
+/// Size of the blocks sizes are counted in, as given to `--block-size`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct BlockSize {
+  /// Number of bytes in one block.
+  pub(crate) bytes: u64,
+  /// Unit suffix printed after each count, when the block size is a bare unit like `M` or `1G`.
+  pub(crate) suffix: Option<String>,
+}
+
+/// How sizes are turned into text.
+#[derive(Debug, Clone, Default)]
+pub(crate) enum SizeFormat {
+  /// Human readable sizes with binary prefixes.
+  #[default]
+  Human,
+  /// Human readable sizes with decimal (SI) prefixes.
+  Si,
+  /// Counts of fixed size blocks.
+  Blocks(BlockSize),
+}
+
+impl SizeFormat {
+  pub(crate) fn format(&self, bytes: u64) -> String {
+    match self {
+      SizeFormat::Human | SizeFormat::Si => format_size(
+        bytes,
+        FormatSizeOptions::from(if matches!(self, SizeFormat::Si) {
+          DECIMAL
+        } else {
+          BINARY
+        })
+        .space_after_value(false)
+        .decimal_places(1),
+      ),
+      SizeFormat::Blocks(block_size) => format!(
+        "{}{}",
+        bytes.div_ceil(block_size.bytes),
+        block_size.suffix.as_deref().unwrap_or_default()
+      ),
+    }
+  }
+}
+
+impl SizeFormat {
+  /// Format a size that may be unknown, printed as `?`.
+  pub(crate) fn format_opt(&self, bytes: Option<u64>) -> String {
+    bytes.map_or_else(|| "?".to_string(), |bytes| self.format(bytes))
+  }
+
+  /// Format a change of size with its sign, `+0B` when unchanged.
+  pub(crate) fn format_signed(&self, bytes: i128) -> String {
+    let sign = if bytes < 0 { '-' } else { '+' };
+    format!(
+      "{sign}{}",
+      self.format(bytes.unsigned_abs().min(u64::MAX.into()) as u64)
+    )
+  }
+}
+
+/// Parse a block size such as `1024`, `64K`, `1M`, `1MB` or `2GiB`.
+///
+/// Single letter suffixes and the `iB` forms are powers of 1024, suffixes ending with `B` are
+/// powers of 1000.
+pub(crate) fn parse_block_size(value: &str) -> Result<BlockSize, String> {
+  let value = value.trim();
+  if value.is_empty() {
+    return Err("empty block size".to_string());
+  }
+  let split = value
+    .find(|c: char| !c.is_ascii_digit())
+    .unwrap_or(value.len());
+  let (count, unit) = value.split_at(split);
+  let count: u64 = if count.is_empty() {
+    1
+  } else {
+    count
+      .parse()
+      .map_err(|e| format!("invalid block size `{value}`: {e}"))?
+  };
+
+  let (exponent, base) = match unit.to_ascii_uppercase().as_str() {
+    "" => (0, 1024),
+    "K" | "KIB" => (1, 1024),
+    "M" | "MIB" => (2, 1024),
+    "G" | "GIB" => (3, 1024),
+    "T" | "TIB" => (4, 1024),
+    "KB" => (1, 1000),
+    "MB" => (2, 1000),
+    "GB" => (3, 1000),
+    "TB" => (4, 1000),
+    _ => {
+      return Err(format!(
+        "invalid block size `{value}`: unknown unit `{unit}`"
+      ))
+    }
+  };
+  let bytes = (base as u64)
+    .checked_pow(exponent)
+    .and_then(|multiplier| multiplier.checked_mul(count))
+    .filter(|bytes| *bytes > 0)
+    .ok_or_else(|| format!("invalid block size `{value}`"))?;
+
+  Ok(BlockSize {
+    bytes,
+    suffix: (count == 1 && !unit.is_empty()).then(|| unit.to_string()),
+  })
+}
+
+/// Parse a size such as `500M` or `1G` in bytes, with the units of [`parse_block_size`].
+pub(crate) fn parse_size(value: &str) -> Result<u64, String> {
+  parse_block_size(value).map(|size| size.bytes)
+}
+
+/// A size of the settings, written like the `--block-size` argument.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub(crate) struct ByteSize(pub(crate) u64);
+
+impl TryFrom<String> for ByteSize {
+  type Error = String;
+
+  fn try_from(value: String) -> Result<Self, Self::Error> {
+    parse_size(&value).map(ByteSize)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_block_size() {
+    assert_eq!(parse_block_size("512").unwrap().bytes, 512);
+    assert_eq!(parse_block_size("64K").unwrap().bytes, 64 * 1024);
+    assert_eq!(parse_block_size("1M").unwrap().bytes, 1024 * 1024);
+    assert_eq!(parse_block_size("G").unwrap().bytes, 1 << 30);
+    assert_eq!(parse_block_size("2GiB").unwrap().bytes, 2 << 30);
+    assert_eq!(parse_block_size("1MB").unwrap().bytes, 1_000_000);
+    assert_eq!(parse_block_size("1tb").unwrap().bytes, 1_000_000_000_000);
+    assert!(parse_block_size("0").is_err());
+    assert!(parse_block_size("1X").is_err());
+    assert!(parse_block_size("").is_err());
+    assert_eq!(ByteSize::try_from("1G".to_string()), Ok(ByteSize(1 << 30)));
+  }
+
+  #[test]
+  fn test_block_suffix() {
+    assert_eq!(parse_block_size("1M").unwrap().suffix.as_deref(), Some("M"));
+    assert_eq!(parse_block_size("64K").unwrap().suffix, None);
+    assert_eq!(parse_block_size("4096").unwrap().suffix, None);
+
+    let format = SizeFormat::Blocks(parse_block_size("1K").unwrap());
+    assert_eq!(format.format(4096), "4K");
+    assert_eq!(format.format(4097), "5K");
+  }
+}
@@ -0,0 +1,427 @@
+//! Parallel directory walker for the `top` and `users` scans.
+//!
+//! The walk never leaves the filesystem it starts on: it stops at device boundaries (`st_dev`),
+//! at every mount point of the mount table (bind mounts keep the same `st_dev`, so the device
+//! check alone would count them twice or loop) and at virtual filesystems. Device numbers, inodes
+//! and owners are only known on Unix, the walk fails elsewhere.
+
+use crate::fsext::MountInfo;
+use anyhow::{bail, Result};
+use glob::Pattern;
+use std::{
+  collections::{HashMap, HashSet},
+  fs::{self, Metadata},
+  hash::Hash,
+  path::{Path, PathBuf},
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+  },
+  time::{Duration, Instant},
+};
+use tracing::debug;
+
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+
+/// Filesystems without on-disk data, never walked.
+const VIRTUAL_FS_TYPES: &[&str] = &[
+  "autofs",
+  "binfmt_misc",
+  "bpf",
+  "cgroup",
+  "cgroup2",
+  "configfs",
+  "debugfs",
+  "devpts",
+  "devtmpfs",
+  "efivarfs",
+  "fusectl",
+  "hugetlbfs",
+  "mqueue",
+  "nsfs",
+  "proc",
+  "pstore",
+  "rpc_pipefs",
+  "securityfs",
+  "selinuxfs",
+  "sysfs",
+  "tracefs",
+];
+
+#[derive(Debug, Default)]
+pub(crate) struct WalkOptions {
+  /// Mount points the walk must not enter.
+  pub(crate) mount_points: HashSet<PathBuf>,
+  /// Directories skipped when their path or name matches one of the patterns.
+  pub(crate) exclude_dirs: Vec<Pattern>,
+}
+
+impl WalkOptions {
+  pub(crate) fn new(mounts: &[MountInfo], exclude_dirs: Vec<Pattern>) -> Self {
+    Self {
+      mount_points: mounts.iter().map(|m| PathBuf::from(&m.mount_dir)).collect(),
+      exclude_dirs,
+    }
+  }
+
+  fn excluded(&self, path: &Path) -> bool {
+    let name = path.file_name().map(|n| n.to_string_lossy());
+    self.exclude_dirs.iter().any(|pattern| {
+      pattern.matches_path(path) || name.as_deref().is_some_and(|name| pattern.matches(name))
+    })
+  }
+}
+
+/// Whether the deepest mount containing `path` is a virtual filesystem.
+pub(crate) fn on_virtual_fs(mounts: &[MountInfo], path: &Path) -> bool {
+  mounts
+    .iter()
+    .filter(|m| path.starts_with(&m.mount_dir))
+    .max_by_key(|m| m.mount_dir.len())
+    .is_some_and(|m| VIRTUAL_FS_TYPES.contains(&m.fs_type.as_str()))
+}
+
+/// Bytes allocated to a file, counting every hard linked inode once.
+#[derive(Default)]
+pub(crate) struct DiskUsage {
+  seen: Mutex<HashSet<(u64, u64)>>,
+}
+
+impl DiskUsage {
+  #[cfg(unix)]
+  pub(crate) fn allocated(&self, metadata: &Metadata) -> u64 {
+    if metadata.nlink() > 1
+      && !self
+        .seen
+        .lock()
+        .unwrap()
+        .insert((metadata.dev(), metadata.ino()))
+    {
+      return 0;
+    }
+    metadata.blocks() * 512
+  }
+
+  #[cfg(not(unix))]
+  pub(crate) fn allocated(&self, metadata: &Metadata) -> u64 {
+    metadata.len()
+  }
+}
+
+/// Stops a walk early, when cancelled or when its time budget is spent.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Cancel {
+  cancelled: Arc<AtomicBool>,
+  deadline: Option<Instant>,
+}
+
+impl Cancel {
+  pub(crate) fn with_budget(budget: Option<Duration>) -> Self {
+    Self {
+      cancelled: Arc::default(),
+      deadline: budget.map(|budget| Instant::now() + budget),
+    }
+  }
+
+  pub(crate) fn cancel(&self) {
+    self.cancelled.store(true, Ordering::Relaxed);
+  }
+
+  /// Whether the walk stopped before visiting everything.
+  pub(crate) fn is_cancelled(&self) -> bool {
+    if self
+      .deadline
+      .is_some_and(|deadline| Instant::now() >= deadline)
+    {
+      self.cancel();
+    }
+    self.cancelled.load(Ordering::Relaxed)
+  }
+}
+
+/// Visit every entry below `root` on the same filesystem, `root` excluded.
+///
+/// Directories are walked in parallel on the rayon thread pool, `visit` is called from several
+/// threads at once.
+#[cfg(unix)]
+pub(crate) fn walk<F>(root: &Path, options: &WalkOptions, cancel: &Cancel, visit: &F) -> Result<()>
+where
+  F: Fn(&Path, &Metadata) + Sync,
+{
+  let root_metadata = fs::symlink_metadata(root)?;
+  if !root_metadata.is_dir() {
+    bail!("{} is not a directory", root.display());
+  }
+  let device = root_metadata.dev();
+  rayon::scope(|scope| walk_dir(scope, root.to_path_buf(), device, options, cancel, visit));
+  Ok(())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn walk<F>(
+  _root: &Path,
+  _options: &WalkOptions,
+  _cancel: &Cancel,
+  _visit: &F,
+) -> Result<()>
+where
+  F: Fn(&Path, &Metadata) + Sync,
+{
+  bail!("scanning the directories is only supported on Unix")
+}
+
+#[cfg(unix)]
+fn walk_dir<'s, F>(
+  scope: &rayon::Scope<'s>,
+  dir: PathBuf,
+  device: u64,
+  options: &'s WalkOptions,
+  cancel: &'s Cancel,
+  visit: &'s F,
+) where
+  F: Fn(&Path, &Metadata) + Sync,
+{
+  let entries = match fs::read_dir(&dir) {
+    Ok(entries) => entries,
+    Err(error) => {
+      debug!("Skip {}: {error}", dir.display());
+      return;
+    }
+  };
+  for entry in entries.map_while(Result::ok) {
+    if cancel.is_cancelled() {
+      return;
+    }
+    let path = entry.path();
+    let Ok(metadata) = entry.metadata() else {
+      continue;
+    };
+    if metadata.dev() != device {
+      continue;
+    }
+    if metadata.is_dir() {
+      if options.mount_points.contains(&path) {
+        debug!("Don't cross the mount point {}", path.display());
+        continue;
+      }
+      if options.excluded(&path) {
+        continue;
+      }
+      let subdir = path.clone();
+      scope.spawn(move |scope| walk_dir(scope, subdir, device, options, cancel, visit));
+    }
+    visit(&path, &metadata);
+  }
+}
+
+/// Bytes used per key, filled while a scan runs.
+#[derive(Debug)]
+pub(crate) struct Totals<K>(Mutex<HashMap<K, u64>>);
+
+impl<K> Default for Totals<K> {
+  fn default() -> Self {
+    Self(Mutex::default())
+  }
+}
+
+impl<K: Eq + Hash + Ord + Clone> Totals<K> {
+  fn add(&self, key: K, bytes: u64) {
+    *self.0.lock().unwrap().entry(key).or_default() += bytes;
+  }
+
+  /// The `count` largest entries so far, largest first.
+  pub(crate) fn largest(&self, count: usize) -> Vec<(K, u64)> {
+    let mut totals: Vec<_> = self
+      .0
+      .lock()
+      .unwrap()
+      .iter()
+      .map(|(key, bytes)| (key.clone(), *bytes))
+      .collect();
+    totals.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    totals.truncate(count);
+    totals
+  }
+}
+
+/// Disk usage of every direct child of `root`.
+pub(crate) fn top(
+  root: &Path,
+  options: &WalkOptions,
+  cancel: &Cancel,
+  totals: &Totals<PathBuf>,
+) -> Result<()> {
+  let usage = DiskUsage::default();
+  walk(root, options, cancel, &|path, metadata| {
+    if let Some(child) = path
+      .strip_prefix(root)
+      .ok()
+      .and_then(|relative| relative.components().next())
+    {
+      totals.add(root.join(child), usage.allocated(metadata));
+    }
+  })
+}
+
+/// Disk usage below `root` per owner uid.
+#[cfg(unix)]
+pub(crate) fn users(
+  root: &Path,
+  options: &WalkOptions,
+  cancel: &Cancel,
+  totals: &Totals<u32>,
+) -> Result<()> {
+  let usage = DiskUsage::default();
+  walk(root, options, cancel, &|_, metadata| {
+    totals.add(metadata.uid(), usage.allocated(metadata));
+  })
+}
+
+#[cfg(not(unix))]
+pub(crate) fn users(
+  _root: &Path,
+  _options: &WalkOptions,
+  _cancel: &Cancel,
+  _totals: &Totals<u32>,
+) -> Result<()> {
+  bail!("the usage per user is only supported on Unix")
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+  use super::*;
+  use std::fs::File;
+  use std::io::Write;
+
+  fn tree() -> tempfile::TempDir {
+    let tmp = tempfile::TempDir::new().expect("Failed to create temp dir");
+    for dir in ["a/b", "bind/inner", "cache"] {
+      fs::create_dir_all(tmp.path().join(dir)).unwrap();
+    }
+    for file in ["a/b/file", "bind/inner/file", "cache/file"] {
+      File::create(tmp.path().join(file))
+        .unwrap()
+        .write_all(&[0; 8192])
+        .unwrap();
+    }
+    tmp
+  }
+
+  fn visited(root: &Path, options: &WalkOptions) -> Vec<PathBuf> {
+    let paths = Mutex::new(vec![]);
+    walk(root, options, &Cancel::default(), &|path, _| {
+      paths
+        .lock()
+        .unwrap()
+        .push(path.strip_prefix(root).unwrap().to_path_buf())
+    })
+    .unwrap();
+    let mut paths = paths.into_inner().unwrap();
+    paths.sort();
+    paths
+  }
+
+  #[test]
+  fn test_walk_everything() {
+    let tmp = tree();
+    assert_eq!(visited(tmp.path(), &WalkOptions::default()).len(), 8);
+  }
+
+  #[test]
+  fn test_bind_mount_not_crossed() {
+    let tmp = tree();
+    let options = WalkOptions {
+      mount_points: HashSet::from([tmp.path().join("bind")]),
+      ..Default::default()
+    };
+    let paths = visited(tmp.path(), &options);
+    assert!(!paths.iter().any(|p| p.starts_with("bind")));
+    assert!(paths.contains(&PathBuf::from("a/b/file")));
+  }
+
+  #[test]
+  fn test_exclude_dir() {
+    let tmp = tree();
+    let options = WalkOptions {
+      exclude_dirs: vec![
+        Pattern::new("cache").unwrap(),
+        Pattern::new("*/a/b").unwrap(),
+      ],
+      ..Default::default()
+    };
+    let paths = visited(tmp.path(), &options);
+    assert!(!paths
+      .iter()
+      .any(|p| p.starts_with("cache") || p.starts_with("a/b")));
+    assert!(paths.contains(&PathBuf::from("a")));
+  }
+
+  #[test]
+  fn test_hard_links_counted_once() {
+    let tmp = tree();
+    fs::hard_link(tmp.path().join("a/b/file"), tmp.path().join("a/link")).unwrap();
+    let usage = DiskUsage::default();
+    let first = usage.allocated(&fs::metadata(tmp.path().join("a/b/file")).unwrap());
+    let second = usage.allocated(&fs::metadata(tmp.path().join("a/link")).unwrap());
+    assert!(first > 0);
+    assert_eq!(second, 0);
+  }
+
+  #[test]
+  fn test_top() {
+    let tmp = tree();
+    let options = WalkOptions {
+      mount_points: HashSet::from([tmp.path().join("bind")]),
+      ..Default::default()
+    };
+    let totals = Totals::default();
+    top(tmp.path(), &options, &Cancel::default(), &totals).unwrap();
+    let top = totals.largest(10);
+    assert_eq!(top.len(), 2);
+    assert!(!top.iter().any(|(path, _)| path.ends_with("bind")));
+    assert!(top.iter().all(|(_, bytes)| *bytes >= 8192));
+  }
+
+  #[test]
+  fn test_cancelled() {
+    let tmp = tree();
+    let cancel = Cancel::default();
+    cancel.cancel();
+    let count = Mutex::new(0);
+    walk(tmp.path(), &WalkOptions::default(), &cancel, &|_, _| {
+      *count.lock().unwrap() += 1
+    })
+    .unwrap();
+    assert_eq!(*count.lock().unwrap(), 0);
+    assert!(Cancel::with_budget(Some(Duration::ZERO)).is_cancelled());
+  }
+
+  #[test]
+  fn test_users() {
+    let tmp = tree();
+    let totals = Totals::default();
+    users(
+      tmp.path(),
+      &WalkOptions::default(),
+      &Cancel::default(),
+      &totals,
+    )
+    .unwrap();
+    let users = totals.largest(10);
+    assert_eq!(users.len(), 1);
+    assert!(users[0].1 >= 3 * 8192);
+  }
+
+  #[test]
+  fn test_virtual_fs() {
+    let mount = |dir: &str, fs_type: &str| MountInfo {
+      fs_type: fs_type.to_string(),
+      mount_dir: dir.to_string(),
+      ..Default::default()
+    };
+    let mounts = [mount("/", "ext4"), mount("/proc", "proc")];
+    assert!(on_virtual_fs(&mounts, Path::new("/proc/1")));
+    assert!(!on_virtual_fs(&mounts, Path::new("/home")));
+  }
+}
@@ -68,7 +68,9 @@
         ddf = craneLib.buildPackage (commonArgs // {
           inherit cargoArtifacts;
           postInstall = ''
-                        installShellCompletion --cmd ${commonArgs.pname} --bash <($out/bin/${commonArgs.pname} --completion bash) --fish <($out/bin/${commonArgs.pname} --completion fish) --zsh <($out/bin/${commonArgs.pname} --completion zsh)
+                        $out/bin/${commonArgs.pname} generate --out-dir assets
+                        installShellCompletion assets/completions/${commonArgs.pname}.{bash,fish} --zsh assets/completions/_${commonArgs.pname}
+                        installManPage assets/man/*.1
           '';
           #             wrapProgram "$out/bin/${pname}" --prefix LD_LIBRARY_PATH : "${lib.makeLibraryPath commonArgs.buildInputs}"
         });
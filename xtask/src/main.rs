@@ -0,0 +1,46 @@
+//! `cargo xtask assets [CARGO ARGS]...`: build ddf in release mode and write its shell completions
+//! and man pages to `target/assets`, for packaging.
+
+use anyhow::{bail, Context, Result};
+use std::{
+  env,
+  path::{Path, PathBuf},
+  process::Command,
+};
+
+fn cargo(args: &[String]) -> Result<()> {
+  let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+  let status = Command::new(&cargo)
+    .args(args)
+    .status()
+    .with_context(|| format!("running {cargo}"))?;
+  if !status.success() {
+    bail!("{cargo} {} failed: {status}", args.join(" "));
+  }
+  Ok(())
+}
+
+fn assets(root: &Path, cargo_args: &[String]) -> Result<()> {
+  let out_dir = root.join("target").join("assets");
+  let mut args: Vec<String> = ["run", "--release", "--package", "ddf"]
+    .map(String::from)
+    .to_vec();
+  args.extend_from_slice(cargo_args);
+  args.extend(["--", "generate", "--out-dir"].map(String::from));
+  args.push(out_dir.to_string_lossy().into_owned());
+  cargo(&args)?;
+  println!("assets written to {}", out_dir.display());
+  Ok(())
+}
+
+fn main() -> Result<()> {
+  let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+    .parent()
+    .context("no workspace root")?
+    .to_path_buf();
+  let args: Vec<String> = env::args().skip(1).collect();
+  match args.split_first() {
+    Some((task, rest)) if task == "assets" => assets(&root, rest),
+    _ => bail!("usage: cargo xtask assets [CARGO ARGS]..."),
+  }
+}